@@ -4,21 +4,39 @@ if !has('nvim') || exists('g:GuiLoaded')
 endif
 let g:GuiLoaded = 1
 
+" Channel of NeovimGtk, published by GUI in g:gtk_channel on attach.
+" g:gtk_channel is global, when that GUI is detached other attached one is used.
+function! s:GuiChannel()
+	let l:channel = get(g:, 'gtk_channel', 1)
+	if !empty(nvim_get_chan_info(l:channel))
+		return l:channel
+	endif
+	for l:info in nvim_list_chans()
+		if get(get(l:info, 'client', {}), 'name', '') ==# 'nvim-gtk'
+			let g:gtk_channel = l:info.id
+			return l:info.id
+		endif
+	endfor
+	return l:channel
+endfunction
+
 if exists('g:GuiInternalClipboard')
 	let s:LastRegType = 'v'
 	function! provider#clipboard#Call(method, args) abort
 		if a:method == 'get'
-			return [rpcrequest(1, 'Gui', 'Clipboard', 'Get', a:args[0]), s:LastRegType]
+			return [rpcrequest(s:GuiChannel(), 'Gui', 'Clipboard', 'Get', a:args[0]), s:LastRegType]
 		elseif a:method == 'set'
 			let s:LastRegType = a:args[1]
-			call rpcnotify(1, 'Gui', 'Clipboard', 'Set', a:args[2], join(a:args[0], ''))
+			call rpcnotify(s:GuiChannel(), 'Gui', 'Clipboard', 'Set', a:args[2], join(a:args[0], "\r"))
 		endif
 	endfunction
 endif
 
 " Set GUI font
 function! GuiFont(fname, ...) abort
-	call rpcnotify(1, 'Gui', 'Font', s:NvimQtToPangoFont(a:fname))
+	" comma separated list is a fallback chain, first font is primary
+	let l:fonts = map(split(a:fname, '\\\@<!,'), {_, font -> s:NvimQtToPangoFont(font)})
+	call rpcnotify(s:GuiChannel(), 'Gui', 'Font', join(l:fonts, ','))
 endfunction
 
 " Some subset of parse command from neovim-qt
@@ -55,12 +73,102 @@ endfunction
 command! -nargs=1 -bang Guifont call s:GuiFontCommand("<args>", "<bang>")
 command! -nargs=1 -bang GuiFont call s:GuiFontCommand("<args>", "<bang>")
 
-command! -nargs=? GuiFontFeatures call rpcnotify(1, 'Gui', 'FontFeatures', <q-args>)
-command! -nargs=1 GuiLinespace call rpcnotify(1, 'Gui', 'Linespace', <q-args>)
+" Fonts for bold, italic and bold italic highlights, without argument primary font is used
+command! -nargs=? GuiFontBold call rpcnotify(s:GuiChannel(), 'Gui', 'FontVariant', 'bold', <q-args>)
+command! -nargs=? GuiFontItalic call rpcnotify(s:GuiChannel(), 'Gui', 'FontVariant', 'italic', <q-args>)
+command! -nargs=? GuiFontBoldItalic call rpcnotify(s:GuiChannel(), 'Gui', 'FontVariant', 'bold_italic', <q-args>)
+
+command! -nargs=? GuiFontFeatures call rpcnotify(s:GuiChannel(), 'Gui', 'FontFeatures', <q-args>)
+" Show text under cursor without ligatures, without argument option is toggled
+command! -nargs=? GuiBreakLigaturesAtCursor call rpcnotify(s:GuiChannel(), 'Gui', 'Command', 'BreakLigaturesAtCursor', <f-args>)
+command! -nargs=1 GuiLinespace call rpcnotify(s:GuiChannel(), 'Gui', 'Linespace', <q-args>)
+
+" Toggle smooth scroll animation, optional args are on/off and duration in ms
+command! -nargs=* GuiSmoothScroll call rpcnotify(s:GuiChannel(), 'Gui', 'Command', 'SmoothScroll', <f-args>)
+
+" Cursor highlight of 'guicursor' depends on language mappings, e.g. 'iminsert'
+function! s:GuiLangMapping(active) abort
+	call rpcnotify(s:GuiChannel(), 'Gui', 'LangMapping', a:active ? v:true : v:false)
+endfunction
+
+augroup NvimGtkLangMapping
+	autocmd!
+	autocmd InsertEnter * call s:GuiLangMapping(&iminsert == 1)
+	autocmd OptionSet iminsert call s:GuiLangMapping(mode() =~# '^[iR]' && &iminsert == 1)
+	autocmd InsertLeave * call s:GuiLangMapping(0)
+augroup END
+
+" Toggle cursor glide animation, optional args are on/off, duration in ms and easing
+command! -nargs=* GuiCursorGlide call rpcnotify(s:GuiChannel(), 'Gui', 'Command', 'CursorGlide', <f-args>)
+
+command! NGToggleSidebar call rpcnotify(s:GuiChannel(), 'Gui', 'Command', 'ToggleSidebar')
+command! NGShowProjectView call rpcnotify(s:GuiChannel(), 'Gui', 'Command', 'ShowProjectView')
+command! NGFindFile call rpcnotify(s:GuiChannel(), 'Gui', 'Command', 'ShowFileFinder')
+command! -nargs=+ NGTransparency call rpcnotify(s:GuiChannel(), 'Gui', 'Command', 'Transparency', <f-args>)
+command! -nargs=1 NGPreferDarkTheme call rpcnotify(s:GuiChannel(), 'Gui', 'Command', 'PreferDarkTheme', <q-args>)
+command! -nargs=1 NGSetCursorBlink call rpcnotify(s:GuiChannel(), 'Gui', 'Command', 'SetCursorBlink', <q-args>)
+command! -nargs=+ NGFloatStyle call rpcnotify(s:GuiChannel(), 'Gui', 'Command', 'FloatStyle', <f-args>)
+command! NGSessionSave call rpcnotify(s:GuiChannel(), 'Gui', 'Command', 'SessionSave')
+command! NGSessionLoad call rpcnotify(s:GuiChannel(), 'Gui', 'Command', 'SessionLoad')
+
+
+" Call method of GUI control api, see :help nvim-gtk-api
+function! GuiApi(method, ...) abort
+	return call('rpcrequest', [s:GuiChannel(), 'Gui', 'Api', a:method] + a:000)
+endfunction
+
+" Call GUI control api method that does not return result
+function! GuiApiNotify(method, ...) abort
+	call call('rpcnotify', [s:GuiChannel(), 'Gui', 'Api', a:method] + a:000)
+endfunction
+
+" Show native file chooser, returns list of chosen paths, empty if canceled
+" action is 'open', 'save' or 'select_folder', optional args are title, initial path and multiple
+function! GuiFileChooser(action, ...) abort
+	return call('rpcrequest', [s:GuiChannel(), 'Gui', 'FileChooser', a:action] + a:000)
+endfunction
+
+function! s:GuiOpen(dir) abort
+	let l:dir = empty(a:dir) ? getcwd() : fnamemodify(a:dir, ':p')
+	for l:path in GuiFileChooser('open', 'Open', l:dir, v:true)
+		execute 'edit' fnameescape(l:path)
+	endfor
+endfunction
+
+function! s:GuiSaveAs() abort
+	let l:name = expand('%:p')
+	let l:paths = GuiFileChooser('save', 'Save As', empty(l:name) ? getcwd() : l:name)
+	if !empty(l:paths)
+		" overwrite is already confirmed by dialog
+		execute 'saveas!' fnameescape(l:paths[0])
+	endif
+endfunction
+
+function! s:GuiBrowseDir(dir) abort
+	let l:dir = empty(a:dir) ? getcwd() : fnamemodify(a:dir, ':p')
+	let l:paths = GuiFileChooser('select_folder', 'Change Directory', l:dir)
+	if !empty(l:paths)
+		execute 'cd' fnameescape(l:paths[0])
+	endif
+endfunction
 
-command! NGToggleSidebar call rpcnotify(1, 'Gui', 'Command', 'ToggleSidebar')
-command! NGShowProjectView call rpcnotify(1, 'Gui', 'Command', 'ShowProjectView')
-command! -nargs=+ NGTransparency call rpcnotify(1, 'Gui', 'Command', 'Transparency', <f-args>)
-command! -nargs=1 NGPreferDarkTheme call rpcnotify(1, 'Gui', 'Command', 'PreferDarkTheme', <q-args>)
-command! -nargs=1 NGSetCursorBlink call rpcnotify(1, 'Gui', 'Command', 'SetCursorBlink', <q-args>)
+command! -nargs=? -complete=dir GuiOpen call s:GuiOpen(<q-args>)
+command! GuiSaveAs call s:GuiSaveAs()
+command! -nargs=? -complete=dir GuiBrowseDir call s:GuiBrowseDir(<q-args>)
 
+" Hide entries matching gitignore style patterns in file browser, no patterns clears the list
+command! -nargs=* GuiFileBrowserIgnore call GuiApiNotify('file_browser_ignore_set', [<f-args>])
+
+" Point buffers of file or directory renamed in file browser to the new path
+function! GuiRenameBuffers(old, new) abort
+	for l:buf in getbufinfo()
+		if l:buf.name ==# a:old || stridx(l:buf.name, a:old . '/') == 0
+			call nvim_buf_set_name(l:buf.bufnr, a:new . strpart(l:buf.name, len(a:old)))
+			" renamed buffer is marked as not edited, reload of unmodified buffer clears it,
+			" unloaded buffers are read from the new name once loaded
+			if l:buf.loaded && !l:buf.changed && exists('*nvim_buf_call')
+				call nvim_buf_call(l:buf.bufnr, {-> execute('silent! edit')})
+			endif
+		endif
+	endfor
+endfunction
@@ -112,11 +112,7 @@ impl Level {
     }
 
     fn update_cache(&mut self, render_state: &shell::RenderState) {
-        render::shape_dirty(
-            &render_state.font_ctx,
-            &mut self.model_layout.model,
-            &render_state.hl,
-        );
+        render::shape_dirty(&render_state.font_ctx, &mut self.model_layout.model, false);
     }
 
     fn set_cursor(&mut self, render_state: &shell::RenderState, pos: usize) {
@@ -481,7 +477,7 @@ impl CmdLine {
         self.wild_renderer
             .set_property_foreground_rgba(Some(&render_state.hl.pmenu_fg().into()));
 
-        popup_menu::update_css(&self.wild_css_provider, &render_state.hl);
+        popup_menu::update_css(&self.wild_css_provider, &render_state.hl, 1.0);
 
         // set width
         // this calculation produce width more then needed, but this is looks ok :)
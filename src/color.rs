@@ -1,5 +1,4 @@
 use std;
-use std::borrow::Cow;
 
 use gdk;
 
@@ -55,18 +54,26 @@ impl Color {
         )
     }
 
-    pub fn inverse(&self, inverse_level: f64) -> Cow<Color> {
-        debug_assert!(inverse_level >= 0.0 && inverse_level <= 1.0);
+    /// Css color with given opacity
+    pub fn to_rgba_css(&self, alpha: f64) -> String {
+        format!(
+            "rgba({}, {}, {}, {})",
+            (self.0 * 255.0) as u8,
+            (self.1 * 255.0) as u8,
+            (self.2 * 255.0) as u8,
+            alpha
+        )
+    }
 
-        if inverse_level <= 0.000001 {
-            Cow::Borrowed(self)
-        } else {
-            Cow::Owned(Color(
-                (inverse_level - self.0).abs(),
-                (inverse_level - self.1).abs(),
-                (inverse_level - self.2).abs(),
-            ))
-        }
+    /// Linear interpolation between colors, `t` is in range 0.0..=1.0
+    pub fn mix(&self, other: &Color, t: f64) -> Color {
+        debug_assert!((0.0..=1.0).contains(&t));
+
+        Color(
+            self.0 + (other.0 - self.0) * t,
+            self.1 + (other.1 - self.1) * t,
+            self.2 + (other.2 - self.2) * t,
+        )
     }
 }
 
@@ -2,11 +2,17 @@ use cairo;
 use crate::mode;
 use crate::render;
 use crate::render::CellMetrics;
+use crate::color::Color;
 use crate::highlight::HighlightMap;
 use std::sync::{Arc, Weak};
 use crate::ui::UiMutex;
 
 use glib;
+use gtk::prelude::*;
+
+const DEFAULT_BLINKWAIT: u32 = 500;
+const DEFAULT_BLINKON: u32 = 500;
+const DEFAULT_BLINKOFF: u32 = 300;
 
 struct Alpha(f64);
 
@@ -52,6 +58,117 @@ impl BlinkCount {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Easing {
+    Linear,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "linear" => Ok(Easing::Linear),
+            "ease_out" => Ok(Easing::EaseOut),
+            "ease_in_out" => Ok(Easing::EaseInOut),
+            _ => Err(format!("Unknown easing {}", name)),
+        }
+    }
+
+    fn apply(self, t: f64) -> f64 {
+        let t = t.max(0.0).min(1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+        }
+    }
+}
+
+/// Movement of cursor between positions, driven by frame clock
+struct Glide {
+    enabled: bool,
+    /// In microseconds, same as frame time
+    duration: i64,
+    easing: Easing,
+
+    /// Target position in drawing area pixels
+    pos: Option<(f64, f64)>,
+    from: (f64, f64),
+    start_time: Option<i64>,
+    progress: f64,
+    is_ticking: bool,
+}
+
+impl Glide {
+    fn new() -> Self {
+        Glide {
+            enabled: false,
+            duration: 80_000,
+            easing: Easing::EaseOut,
+            pos: None,
+            from: (0.0, 0.0),
+            start_time: None,
+            progress: 1.0,
+            is_ticking: false,
+        }
+    }
+
+    fn is_moving(&self) -> bool {
+        self.progress < 1.0
+    }
+
+    fn current_pos(&self) -> Option<(f64, f64)> {
+        let (x, y) = self.pos?;
+        if !self.is_moving() {
+            return Some((x, y));
+        }
+
+        let t = self.easing.apply(self.progress);
+        Some((
+            self.from.0 + (x - self.from.0) * t,
+            self.from.1 + (y - self.from.1) * t,
+        ))
+    }
+
+    /// Returns true if movement animation must be started
+    fn move_to(&mut self, pos: (f64, f64)) -> bool {
+        let from = self.current_pos();
+        self.pos = Some(pos);
+
+        match from {
+            Some(from) if self.enabled && from != pos => {
+                self.from = from;
+                self.start_time = None;
+                self.progress = 0.0;
+                true
+            }
+            _ => {
+                self.progress = 1.0;
+                false
+            }
+        }
+    }
+
+    /// Returns false when movement is finished
+    fn tick(&mut self, frame_time: i64) -> bool {
+        let start_time = *self.start_time.get_or_insert(frame_time);
+        self.progress = if self.duration > 0 {
+            ((frame_time - start_time) as f64 / self.duration as f64).min(1.0)
+        } else {
+            1.0
+        };
+
+        self.is_moving()
+    }
+}
+
 struct State<CB: CursorRedrawCb> {
     alpha: Alpha,
     anim_phase: AnimPhase,
@@ -59,6 +176,11 @@ struct State<CB: CursorRedrawCb> {
 
     timer: Option<glib::SourceId>,
     counter: Option<BlinkCount>,
+    /// Blink times from 'guicursor' of current mode
+    blinkon: u32,
+    blinkoff: u32,
+
+    glide: Glide,
 }
 
 impl<CB: CursorRedrawCb> State<CB> {
@@ -69,6 +191,9 @@ impl<CB: CursorRedrawCb> State<CB> {
             redraw_cb,
             timer: None,
             counter: None,
+            blinkon: DEFAULT_BLINKON,
+            blinkoff: DEFAULT_BLINKOFF,
+            glide: Glide::new(),
         }
     }
 
@@ -82,19 +207,22 @@ impl<CB: CursorRedrawCb> State<CB> {
 }
 
 pub trait Cursor {
-    /// return cursor current alpha value
+    /// return alpha value of text color under cursor
     fn draw(
         &self,
         ctx: &cairo::Context,
         font_ctx: &render::Context,
         line_y: f64,
         double_width: bool,
-        hl: &HighlightMap,
+        bg: &Color,
     ) -> f64;
 
     fn is_visible(&self) -> bool;
 
     fn mode_info(&self) -> Option<&mode::ModeInfo>;
+
+    /// Highlight of cursor in current mode, see `HighlightMap::cursor_colors`
+    fn attr_id(&self) -> Option<u64>;
 }
 
 pub struct EmptyCursor;
@@ -112,7 +240,7 @@ impl Cursor for EmptyCursor {
         _font_ctx: &render::Context,
         _line_y: f64,
         _double_width: bool,
-        _color: &HighlightMap,
+        _bg: &Color,
     ) -> f64 {
         0.0
     }
@@ -124,11 +252,68 @@ impl Cursor for EmptyCursor {
     fn mode_info(&self) -> Option<&mode::ModeInfo> {
         None
     }
+
+    fn attr_id(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// Cursor without blinking and animations, used by headless renderer
+pub struct StaticCursor {
+    mode_info: Option<mode::ModeInfo>,
+}
+
+impl StaticCursor {
+    pub fn new() -> Self {
+        StaticCursor { mode_info: None }
+    }
+
+    pub fn set_mode_info(&mut self, mode_info: Option<mode::ModeInfo>) {
+        self.mode_info = mode_info;
+    }
+}
+
+impl Cursor for StaticCursor {
+    fn draw(
+        &self,
+        ctx: &cairo::Context,
+        font_ctx: &render::Context,
+        line_y: f64,
+        double_width: bool,
+        bg: &Color,
+    ) -> f64 {
+        let current_point = ctx.get_current_point();
+        let (y, width, height) = cursor_rect(
+            self.mode_info(),
+            font_ctx.cell_metrics(),
+            line_y,
+            double_width,
+        );
+
+        ctx.set_source_rgb(bg.0, bg.1, bg.2);
+        ctx.rectangle(current_point.0, y, width, height);
+        ctx.fill();
+        1.0
+    }
+
+    fn is_visible(&self) -> bool {
+        true
+    }
+
+    fn mode_info(&self) -> Option<&mode::ModeInfo> {
+        self.mode_info.as_ref()
+    }
+
+    fn attr_id(&self) -> Option<u64> {
+        self.mode_info.as_ref().and_then(|mi| mi.attr_id(false))
+    }
 }
 
 pub struct BlinkCursor<CB: CursorRedrawCb> {
     state: Arc<UiMutex<State<CB>>>,
     mode_info: Option<mode::ModeInfo>,
+    /// Language mappings are active, e.g. 'iminsert' in insert mode
+    lang_mapping: bool,
 }
 
 impl<CB: CursorRedrawCb + 'static> BlinkCursor<CB> {
@@ -136,11 +321,91 @@ impl<CB: CursorRedrawCb + 'static> BlinkCursor<CB> {
         BlinkCursor {
             state: Arc::new(UiMutex::new(State::new(redraw_cb))),
             mode_info: None,
+            lang_mapping: false,
         }
     }
 
     pub fn set_mode_info(&mut self, mode_info: Option<mode::ModeInfo>) {
+        {
+            let mut mut_state = self.state.borrow_mut();
+            let mode_info = mode_info.as_ref();
+            mut_state.blinkon = mode_info
+                .and_then(|mi| mi.blinkon)
+                .unwrap_or(DEFAULT_BLINKON);
+            mut_state.blinkoff = mode_info
+                .and_then(|mi| mi.blinkoff)
+                .unwrap_or(DEFAULT_BLINKOFF);
+        }
+
+        let blink_changed = self
+            .mode_info
+            .as_ref()
+            .map(mode::ModeInfo::is_blink_disabled)
+            != mode_info.as_ref().map(mode::ModeInfo::is_blink_disabled);
         self.mode_info = mode_info;
+
+        // restart blinking with new settings, same as on key press
+        if blink_changed && self.is_blinking() {
+            self.start();
+        }
+    }
+
+    pub fn set_lang_mapping(&mut self, lang_mapping: bool) {
+        self.lang_mapping = lang_mapping;
+    }
+
+    /// Toggle glide animation in case `enabled` is not given
+    pub fn set_glide(
+        &mut self,
+        enabled: Option<bool>,
+        duration_ms: Option<u64>,
+        easing: Option<Easing>,
+    ) {
+        let mut mut_state = self.state.borrow_mut();
+        let glide = &mut mut_state.glide;
+        glide.enabled = enabled.unwrap_or(!glide.enabled);
+        if let Some(duration_ms) = duration_ms {
+            glide.duration = duration_ms as i64 * 1000;
+        }
+        if let Some(easing) = easing {
+            glide.easing = easing;
+        }
+    }
+
+    /// Cursor moved to new position in drawing area pixels,
+    /// in case glide is enabled cursor moves there smoothly
+    pub fn move_to(&mut self, drawing_area: &gtk::DrawingArea, x: f64, y: f64) {
+        let mut mut_state = self.state.borrow_mut();
+        if !mut_state.glide.move_to((x, y)) || mut_state.glide.is_ticking {
+            return;
+        }
+
+        mut_state.glide.is_ticking = true;
+        let state = self.state.clone();
+        drawing_area.add_tick_callback(move |drawing_area, frame_clock| {
+            let mut mut_state = state.borrow_mut();
+            let is_moving = mut_state.glide.tick(frame_clock.get_frame_time());
+            mut_state.glide.is_ticking = is_moving;
+            drawing_area.queue_draw();
+            Continue(is_moving)
+        });
+    }
+
+    /// Draw cursor in the middle of movement, cursor in cell is hidden at this time
+    pub fn draw_glide(&self, ctx: &cairo::Context, font_ctx: &render::Context, hl: &HighlightMap) {
+        let pos = {
+            let state = self.state.borrow();
+            if !state.glide.is_moving() || state.anim_phase == AnimPhase::Busy {
+                return;
+            }
+            state.glide.current_pos()
+        };
+
+        if let Some((x, y)) = pos {
+            let (bg, _) = hl.cursor_colors(self.attr_id(), None);
+            ctx.move_to(x, y);
+            self.draw(ctx, font_ctx, y, false, &bg);
+        }
     }
 
     pub fn set_cursor_blink(&mut self, val: i32) {
@@ -157,7 +422,7 @@ impl<CB: CursorRedrawCb + 'static> BlinkCursor<CB> {
             .mode_info
             .as_ref()
             .and_then(|mi| mi.blinkwait)
-            .unwrap_or(500);
+            .unwrap_or(DEFAULT_BLINKWAIT);
 
         let state = self.state.clone();
         let mut mut_state = self.state.borrow_mut();
@@ -168,10 +433,22 @@ impl<CB: CursorRedrawCb + 'static> BlinkCursor<CB> {
             counter.count = 0;
         }
 
-        mut_state.timer = Some(glib::timeout_add(
-            if blinkwait > 0 { blinkwait } else { 500 },
-            move || anim_step(&state),
-        ));
+        if self
+            .mode_info
+            .as_ref()
+            .map_or(false, mode::ModeInfo::is_blink_disabled)
+        {
+            return;
+        }
+
+        mut_state.timer = Some(glib::timeout_add(blinkwait, move || anim_step(&state)));
+    }
+
+    fn is_blinking(&self) -> bool {
+        match self.state.borrow().anim_phase {
+            AnimPhase::Busy | AnimPhase::NoFocus => false,
+            _ => true,
+        }
     }
 
     pub fn reset_state(&mut self) {
@@ -208,13 +485,12 @@ impl<CB: CursorRedrawCb> Cursor for BlinkCursor<CB> {
         font_ctx: &render::Context,
         line_y: f64,
         double_width: bool,
-        hl: &HighlightMap,
+        bg: &Color,
     ) -> f64 {
         let state = self.state.borrow();
 
         let current_point = ctx.get_current_point();
 
-        let bg = hl.cursor_bg();
         ctx.set_source_rgba(bg.0, bg.1, bg.2, state.alpha.0);
 
         let (y, width, height) = cursor_rect(
@@ -227,17 +503,18 @@ impl<CB: CursorRedrawCb> Cursor for BlinkCursor<CB> {
         ctx.rectangle(current_point.0, y, width, height);
         if state.anim_phase == AnimPhase::NoFocus {
             ctx.stroke();
+            // text is not covered by outline
+            0.0
         } else {
             ctx.fill();
+            state.alpha.0
         }
-
-        state.alpha.0
     }
 
     fn is_visible(&self) -> bool {
         let state = self.state.borrow();
 
-        if state.anim_phase == AnimPhase::Busy {
+        if state.anim_phase == AnimPhase::Busy || state.glide.is_moving() {
             return false;
         }
 
@@ -251,6 +528,12 @@ impl<CB: CursorRedrawCb> Cursor for BlinkCursor<CB> {
     fn mode_info(&self) -> Option<&mode::ModeInfo> {
         self.mode_info.as_ref()
     }
+
+    fn attr_id(&self) -> Option<u64> {
+        self.mode_info
+            .as_ref()
+            .and_then(|mi| mi.attr_id(self.lang_mapping))
+    }
 }
 
 pub fn cursor_rect(
@@ -333,7 +616,7 @@ fn anim_step<CB: CursorRedrawCb + 'static>(state: &Arc<UiMutex<State<CB>>>) -> g
             if !mut_state.alpha.hide(0.3) {
                 mut_state.anim_phase = AnimPhase::Hidden;
 
-                Some(300)
+                Some(mut_state.blinkoff)
             } else {
                 None
             }
@@ -347,7 +630,7 @@ fn anim_step<CB: CursorRedrawCb + 'static>(state: &Arc<UiMutex<State<CB>>>) -> g
             if !mut_state.alpha.show(0.3) {
                 mut_state.anim_phase = AnimPhase::Shown;
 
-                Some(500)
+                Some(mut_state.blinkon)
             } else {
                 None
             }
@@ -387,6 +670,30 @@ mod tests {
     use super::*;
     use std::collections::HashMap;
 
+    #[test]
+    fn test_glide() {
+        let mut glide = Glide::new();
+        glide.enabled = true;
+        glide.duration = 100_000;
+        glide.easing = Easing::Linear;
+
+        assert!(!glide.move_to((0.0, 0.0)));
+        assert!(glide.move_to((100.0, 20.0)));
+        assert_eq!(Some((0.0, 0.0)), glide.current_pos());
+
+        assert!(glide.tick(1_000_000));
+        assert!(glide.tick(1_050_000));
+        assert_eq!(Some((50.0, 10.0)), glide.current_pos());
+
+        // movement continues from current position
+        assert!(glide.move_to((0.0, 0.0)));
+        assert!(glide.tick(2_000_000));
+        assert_eq!(Some((50.0, 10.0)), glide.current_pos());
+
+        assert!(!glide.tick(2_100_000));
+        assert_eq!(Some((0.0, 0.0)), glide.current_pos());
+    }
+
     #[test]
     fn test_cursor_rect_horizontal() {
         let mut mode_data = HashMap::new();
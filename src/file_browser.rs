@@ -1,11 +1,12 @@
 use std::cell::RefCell;
 use std::cmp::Ordering;
-use std::io;
+use std::collections::HashMap;
 use std::fs;
-use std::fs::DirEntry;
+use std::io;
 use std::path::{Component, Path, PathBuf};
 use std::rc::Rc;
 use std::ops::Deref;
+use std::thread;
 
 use gio;
 use gio::prelude::*;
@@ -14,27 +15,62 @@ use gtk::prelude::*;
 
 use neovim_lib::{NeovimApi, NeovimApiAsync};
 
+use crate::git_status::{FileStatus, GitStatus};
+use crate::ignore::{self, IgnoreMatcher};
 use crate::misc::escape_filename;
 use crate::nvim::{ErrorReport, NeovimClient, NeovimRef};
 use crate::shell;
 use crate::subscriptions::SubscriptionKey;
+use crate::ui::UiMutex;
 
 const ICON_FOLDER_CLOSED: &str = "folder-symbolic";
 const ICON_FOLDER_OPEN: &str = "folder-open-symbolic";
 const ICON_FILE: &str = "text-x-generic-symbolic";
 
+/// Highlight groups that give color to entries with git status
+const GIT_STATUS_HIGHLIGHTS: &[(FileStatus, &str)] = &[
+    (FileStatus::Modified, "Statement"),
+    (FileStatus::Added, "String"),
+    (FileStatus::Untracked, "PreProc"),
+    (FileStatus::Ignored, "Comment"),
+    (FileStatus::Conflicted, "WarningMsg"),
+];
+/// Delay of git status refresh after file system changes, they often come in bursts
+const GIT_STATUS_DELAY_MS: u32 = 300;
+
 struct Components {
     dir_list_model: gtk::TreeStore,
     dir_list: gtk::ComboBox,
     context_menu: gtk::Menu,
     show_hidden_checkbox: gtk::CheckMenuItem,
+    show_ignored_checkbox: gtk::CheckMenuItem,
+    hide_ignored_checkbox: gtk::CheckMenuItem,
     cd_action: gio::SimpleAction,
+    /// Actions enabled only if the context menu was opened for a file
+    file_actions: Vec<gio::SimpleAction>,
+    /// Actions enabled only if the context menu was opened for any entry
+    entry_actions: Vec<gio::SimpleAction>,
 }
 
 struct State {
     current_dir: String,
     show_hidden: bool,
+    /// Show entries matched by ignore files, dimmed
+    show_ignored: bool,
+    /// User patterns in gitignore format
+    ignore_patterns: Vec<String>,
+    ignore: IgnoreMatcher,
+    /// Hide entries ignored by git, including ones shown by `show_ignored`
+    hide_ignored: bool,
     selected_path: Option<String>,
+    /// Directories to expand once tree of given dir is loaded, see `restore_expanded_dirs`
+    pending_expand: Option<(String, Vec<String>)>,
+    /// Monitors of expanded directories and the tree root, by directory path
+    monitors: HashMap<String, gio::FileMonitor>,
+    git_status: Option<GitStatus>,
+    /// Colors of git status, taken from nvim highlight groups
+    git_colors: HashMap<FileStatus, String>,
+    git_status_scheduled: bool,
 }
 
 pub struct FileBrowserWidget {
@@ -54,7 +90,7 @@ impl Deref for FileBrowserWidget {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 enum FileType {
     File,
     Dir,
@@ -66,6 +102,17 @@ enum Column {
     Path,
     FileType,
     IconName,
+    Foreground,
+    /// Ignored entries are drawn insensitive, i.e. dimmed
+    Sensitive,
+}
+
+/// Directory entry shown in the tree
+struct Entry {
+    filename: String,
+    path: String,
+    file_type: FileType,
+    ignored: bool,
 }
 
 impl FileBrowserWidget {
@@ -80,6 +127,12 @@ impl FileBrowserWidget {
         let show_hidden_checkbox: gtk::CheckMenuItem = builder
             .get_object("file_browser_show_hidden_checkbox")
             .unwrap();
+        let show_ignored_checkbox: gtk::CheckMenuItem = builder
+            .get_object("file_browser_show_ignored_checkbox")
+            .unwrap();
+        let hide_ignored_checkbox: gtk::CheckMenuItem = builder
+            .get_object("file_browser_hide_ignored_checkbox")
+            .unwrap();
 
         let file_browser = FileBrowserWidget {
             store,
@@ -91,12 +144,25 @@ impl FileBrowserWidget {
                 dir_list,
                 context_menu,
                 show_hidden_checkbox,
+                show_ignored_checkbox,
+                hide_ignored_checkbox,
                 cd_action: gio::SimpleAction::new("cd", None),
+                file_actions: Vec::new(),
+                entry_actions: Vec::new(),
             },
             state: Rc::new(RefCell::new(State {
                 current_dir: "".to_owned(),
                 show_hidden: false,
+                show_ignored: false,
+                ignore_patterns: Vec::new(),
+                ignore: IgnoreMatcher::new(Path::new(""), &[]),
+                hide_ignored: false,
                 selected_path: None,
+                pending_expand: None,
+                monitors: HashMap::new(),
+                git_status: None,
+                git_colors: HashMap::new(),
+                git_status_scheduled: false,
             })),
         };
         file_browser
@@ -116,45 +182,28 @@ impl FileBrowserWidget {
         }
 
         // Populate tree.
-        tree_reload(&self.store, &self.state.borrow());
+        tree_reload(&self.store, &self.state);
 
         let store = &self.store;
         let state_ref = &self.state;
         self.tree.connect_test_expand_row(clone!(store, state_ref => move |_, iter, _| {
-            store.set(&iter, &[Column::IconName as u32], &[&ICON_FOLDER_OPEN]);
-            // We cannot recursively populate all directories. Instead, we have prepared a single
-            // empty child entry for all non-empty directories, so the row will be expandable. Now,
-            // when a directory is expanded, populate its children.
-            let state = state_ref.borrow();
-            if let Some(child) = store.iter_children(Some(iter)) {
-                let filename = store.get_value(&child, Column::Filename as i32);
-                if filename.get::<&str>().is_none() {
-                    store.remove(&child);
-                    let dir_value = store.get_value(&iter, Column::Path as i32);
-                    if let Some(dir) = dir_value.get() {
-                        populate_tree_nodes(&store, &state, dir, Some(iter));
-                    }
-                } else {
-                    // This directory is already populated, i.e. it has been expanded and collapsed
-                    // again. Rows further down the tree might have been silently collapsed without
-                    // getting an event. Update their folder icon.
-                    let mut tree_path = store.get_path(&child).unwrap();
-                    while let Some(iter) = store.get_iter(&tree_path) {
-                        tree_path.next();
-                        let file_type = store
-                            .get_value(&iter, Column::FileType as i32)
-                            .get::<u8>();
-                        if file_type == Some(FileType::Dir as u8) {
-                            store.set(&iter, &[Column::IconName as u32], &[&ICON_FOLDER_CLOSED]);
-                        }
-                    }
+            if let Some(dir) = store.get_value(iter, Column::Path as i32).get::<String>() {
+                // Empty directory is not expanded, it stays expandable in case files appear.
+                if !populate_expanded_dir(&store, &state_ref.borrow(), &dir, iter) {
+                    return Inhibit(true);
                 }
+                watch_dir(&store, &state_ref, &dir);
             }
+            store.set(iter, &[Column::IconName as u32], &[&ICON_FOLDER_OPEN]);
             Inhibit(false)
         }));
 
-        self.tree.connect_row_collapsed(clone!(store => move |_, iter, _| {
-            store.set(&iter, &[Column::IconName as u32], &[&ICON_FOLDER_CLOSED]);
+        self.tree.connect_row_collapsed(clone!(store, state_ref => move |_, iter, _| {
+            store.set(iter, &[Column::IconName as u32], &[&ICON_FOLDER_CLOSED]);
+            // Collapsing a row silently collapses all rows below it as well.
+            if let Some(dir) = store.get_value(iter, Column::Path as i32).get::<String>() {
+                unwatch_dir(&mut state_ref.borrow_mut(), Path::new(&dir));
+            }
         }));
 
         // Further initialization.
@@ -163,16 +212,77 @@ impl FileBrowserWidget {
         self.connect_events();
     }
 
-    fn init_actions(&self) {
+    /// Expanded directories, relative to current dir
+    pub fn expanded_dirs(&self) -> Vec<String> {
+        let state = self.state.borrow();
+        let cwd = Path::new(&state.current_dir);
+        let store = &self.store;
+
+        let mut dirs = Vec::new();
+        self.tree.map_expanded_rows(|_, tree_path| {
+            let dir = store
+                .get_iter(tree_path)
+                .and_then(|iter| store.get_value(&iter, Column::Path as i32).get::<String>());
+            if let Some(dir) = dir {
+                if let Some(rel_dir) = Path::new(&dir).strip_prefix(cwd).ok().and_then(Path::to_str) {
+                    dirs.push(rel_dir.to_owned());
+                }
+            }
+        });
+        dirs
+    }
+
+    /// Expand directories relative to `root`
+    ///
+    /// In case `root` is not current dir yet, directories expanded once it becomes current
+    pub fn restore_expanded_dirs(&self, root: &str, dirs: Vec<String>) {
+        if self.state.borrow().current_dir == root {
+            expand_dirs_in_tree(&self.store, &self.tree, &dirs);
+        } else {
+            self.state.borrow_mut().pending_expand = Some((root.to_owned(), dirs));
+        }
+    }
+
+    /// Reveals and selects absolute or current dir relative path
+    ///
+    /// Returns `false` if path is outside of current dir or does not exists in tree
+    pub fn reveal_path(&self, path: &str) -> bool {
+        let state = self.state.borrow();
+        let path = Path::new(path);
+        let rel_path = if path.is_absolute() {
+            match path.strip_prefix(&state.current_dir) {
+                Ok(rel_path) => rel_path,
+                Err(_) => return false,
+            }
+        } else {
+            path
+        };
+
+        reveal_path_in_tree(&self.store, &self.tree, rel_path)
+    }
+
+    /// User patterns, in addition to `.gitignore` and `.ignore` files
+    pub fn ignore_patterns(&self) -> Vec<String> {
+        self.state.borrow().ignore_patterns.clone()
+    }
+
+    pub fn set_ignore_patterns(&self, patterns: Vec<String>) {
+        self.state.borrow_mut().ignore_patterns = patterns;
+        tree_reload(&self.store, &self.state);
+    }
+
+    fn init_actions(&mut self) {
         let actions = gio::SimpleActionGroup::new();
 
         let store = &self.store;
+        let tree = &self.tree;
+        let widget = &self.widget;
         let state_ref = &self.state;
         let nvim_ref = self.nvim.as_ref().unwrap();
 
         let reload_action = gio::SimpleAction::new("reload", None);
         reload_action.connect_activate(clone!(store, state_ref => move |_, _| {
-            tree_reload(&store, &state_ref.borrow());
+            tree_reload(&store, &state_ref);
         }));
         actions.add_action(&reload_action);
 
@@ -187,6 +297,75 @@ impl FileBrowserWidget {
         }));
         actions.add_action(cd_action);
 
+        for &(name, cmd) in &[
+            ("open_split", "split"),
+            ("open_vsplit", "vsplit"),
+            ("open_tab", "tabedit"),
+        ] {
+            let action = gio::SimpleAction::new(name, None);
+            action.connect_activate(clone!(state_ref, nvim_ref => move |_, _| {
+                let state = state_ref.borrow();
+                if let Some(ref path) = state.selected_path {
+                    open_file(&nvim_ref, &state.current_dir, path, cmd);
+                }
+            }));
+            actions.add_action(&action);
+            self.comps.file_actions.push(action);
+        }
+
+        for &(name, file_type) in &[("new_file", FileType::File), ("new_dir", FileType::Dir)] {
+            let action = gio::SimpleAction::new(name, None);
+            action.connect_activate(
+                clone!(store, tree, widget, state_ref, nvim_ref => move |_, _| {
+                    create_entry(&store, &tree, &widget, &state_ref, &nvim_ref, file_type);
+                }),
+            );
+            actions.add_action(&action);
+        }
+
+        let rename_action = gio::SimpleAction::new("rename", None);
+        rename_action.connect_activate(clone!(store, widget, state_ref, nvim_ref => move |_, _| {
+            rename_entry(&store, &widget, &state_ref, &nvim_ref);
+        }));
+        actions.add_action(&rename_action);
+
+        let duplicate_action = gio::SimpleAction::new("duplicate", None);
+        duplicate_action.connect_activate(clone!(store, tree, state_ref, nvim_ref => move |_, _| {
+            duplicate_entry(&store, &tree, &state_ref, &nvim_ref);
+        }));
+        actions.add_action(&duplicate_action);
+
+        let trash_action = gio::SimpleAction::new("trash", None);
+        trash_action.connect_activate(clone!(store, state_ref, nvim_ref => move |_, _| {
+            trash_entry(&store, &state_ref, &nvim_ref);
+        }));
+        actions.add_action(&trash_action);
+
+        let copy_path_action = gio::SimpleAction::new("copy_path", None);
+        copy_path_action.connect_activate(clone!(state_ref => move |_, _| {
+            if let Some(ref path) = state_ref.borrow().selected_path {
+                copy_to_clipboard(path);
+            }
+        }));
+        actions.add_action(&copy_path_action);
+
+        let copy_rel_path_action = gio::SimpleAction::new("copy_rel_path", None);
+        copy_rel_path_action.connect_activate(clone!(state_ref => move |_, _| {
+            let state = state_ref.borrow();
+            if let Some(ref path) = state.selected_path {
+                copy_to_clipboard(relative_path(&state.current_dir, path));
+            }
+        }));
+        actions.add_action(&copy_rel_path_action);
+
+        self.comps.entry_actions.extend(vec![
+            rename_action,
+            duplicate_action,
+            trash_action,
+            copy_path_action,
+            copy_rel_path_action,
+        ]);
+
         self.comps
             .context_menu
             .insert_action_group("filebrowser", Some(&actions));
@@ -198,15 +377,24 @@ impl FileBrowserWidget {
         let state_ref = &self.state;
         let dir_list_model = &self.comps.dir_list_model;
         let dir_list = &self.comps.dir_list;
+        let tree = &self.tree;
         shell_state.subscribe(
             SubscriptionKey::from("DirChanged"),
             &["getcwd()"],
-            clone!(store, state_ref, dir_list_model, dir_list => move |args| {
+            clone!(store, state_ref, dir_list_model, dir_list, tree => move |args| {
                 let dir = args.into_iter().next().unwrap();
                 if dir != state_ref.borrow().current_dir {
                     state_ref.borrow_mut().current_dir = dir.to_owned();
                     update_dir_list(&dir, &dir_list_model, &dir_list);
-                    tree_reload(&store, &state_ref.borrow());
+                    tree_reload(&store, &state_ref);
+
+                    let pending_expand = state_ref.borrow_mut().pending_expand.take();
+                    match pending_expand {
+                        Some((root, ref dirs)) if root == dir => {
+                            expand_dirs_in_tree(&store, &tree, dirs)
+                        }
+                        pending_expand => state_ref.borrow_mut().pending_expand = pending_expand,
+                    }
                 }
             }),
         );
@@ -232,6 +420,39 @@ impl FileBrowserWidget {
             }),
         );
         shell_state.run_now(&subscription);
+
+        // Refresh git status when nvim writes a file or other programs might have changed them.
+        shell_state.subscribe(
+            SubscriptionKey::from("BufWritePost,FocusGained"),
+            &[],
+            clone!(store, state_ref => move |_| {
+                schedule_git_status(&store, &state_ref);
+            }),
+        );
+
+        // Take colors of git status from highlight groups.
+        let args: Vec<String> = GIT_STATUS_HIGHLIGHTS
+            .iter()
+            .map(|(_, hl_group)| {
+                format!("synIDattr(synIDtrans(hlID('{}')), 'fg#', 'gui')", hl_group)
+            })
+            .collect();
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let subscription = shell_state.subscribe(
+            SubscriptionKey::from("ColorScheme"),
+            &args,
+            clone!(store, state_ref => move |args| {
+                let git_colors = GIT_STATUS_HIGHLIGHTS
+                    .iter()
+                    .zip(args)
+                    .filter(|(_, color)| !color.is_empty())
+                    .map(|(&(status, _), color)| (status, color))
+                    .collect();
+                state_ref.borrow_mut().git_colors = git_colors;
+                apply_git_status(&store, &state_ref);
+            }),
+        );
+        shell_state.run_now(&subscription);
     }
 
     fn connect_events(&self) {
@@ -258,21 +479,7 @@ impl FileBrowserWidget {
                 }
             } else {
                 // FileType::File
-                let cwd = &state_ref.borrow().current_dir;
-                let cwd = Path::new(cwd);
-                let file_path = if let Some(rel_path) = Path::new(&file_path)
-                    .strip_prefix(&cwd)
-                    .ok()
-                    .and_then(|p| p.to_str())
-                {
-                    rel_path
-                } else {
-                    &file_path
-                };
-                let file_path = escape_filename(file_path);
-                nvim_ref.nvim().unwrap().command_async(&format!(":e {}", file_path))
-                    .cb(|r| r.report_err())
-                    .call();
+                open_file(&nvim_ref, &state_ref.borrow().current_dir, &file_path, "e");
             }
         }));
 
@@ -294,8 +501,11 @@ impl FileBrowserWidget {
 
         let context_menu = &self.comps.context_menu;
         let cd_action = &self.comps.cd_action;
+        let file_actions = &self.comps.file_actions;
+        let entry_actions = &self.comps.entry_actions;
         self.tree.connect_button_press_event(
-            clone!(store, state_ref, context_menu, cd_action => move |tree, ev_btn| {
+            clone!(store, state_ref, context_menu, cd_action, file_actions, entry_actions =>
+                   move |tree, ev_btn| {
                 // Open context menu on right click.
                 if ev_btn.get_button() == 3 {
                     context_menu.popup_at_pointer(Some(&**ev_btn));
@@ -313,6 +523,12 @@ impl FileBrowserWidget {
                         });
                     // Enable the "Go To Directory" action only if the user clicked on a folder.
                     cd_action.set_enabled(file_type == Some(FileType::Dir as u8));
+                    for action in &file_actions {
+                        action.set_enabled(file_type == Some(FileType::File as u8));
+                    }
+                    for action in &entry_actions {
+                        action.set_enabled(file_type.is_some());
+                    }
                     let path = iter
                         .and_then(|iter| {
                             store
@@ -327,38 +543,54 @@ impl FileBrowserWidget {
 
         // Show / hide hidden files when corresponding menu item is toggled.
         self.comps.show_hidden_checkbox.connect_toggled(clone!(state_ref, store => move |ev| {
-            let mut state = state_ref.borrow_mut();
-            state.show_hidden = ev.get_active();
-            tree_reload(&store, &state);
+            state_ref.borrow_mut().show_hidden = ev.get_active();
+            tree_reload(&store, &state_ref);
+        }));
+
+        // Show / hide files matched by ignore files.
+        self.comps.show_ignored_checkbox.connect_toggled(clone!(state_ref, store => move |ev| {
+            state_ref.borrow_mut().show_ignored = ev.get_active();
+            tree_reload(&store, &state_ref);
+        }));
+
+        // Show / hide files ignored by git.
+        self.comps.hide_ignored_checkbox.connect_toggled(clone!(state_ref, store => move |ev| {
+            state_ref.borrow_mut().hide_ignored = ev.get_active();
+            tree_reload(&store, &state_ref);
         }));
     }
 }
 
-/// Compare function for dir entries.
+/// Compare function for entries given as `(is_dir, filename)`.
 ///
 /// Sorts directories above files.
-fn cmp_dirs_first(lhs: &DirEntry, rhs: &DirEntry) -> io::Result<Ordering> {
-    let lhs_metadata = fs::metadata(lhs.path())?;
-    let rhs_metadata = fs::metadata(rhs.path())?;
-    if lhs_metadata.is_dir() == rhs_metadata.is_dir() {
-        Ok(lhs.path()
-            .to_string_lossy()
-            .to_lowercase()
-            .cmp(&rhs.path().to_string_lossy().to_lowercase()))
-    } else {
-        if lhs_metadata.is_dir() {
-            Ok(Ordering::Less)
-        } else {
-            Ok(Ordering::Greater)
-        }
-    }
+fn cmp_dirs_first(lhs: (bool, &str), rhs: (bool, &str)) -> Ordering {
+    rhs.0
+        .cmp(&lhs.0)
+        .then_with(|| lhs.1.to_lowercase().cmp(&rhs.1.to_lowercase()))
 }
 
 /// Clears an repopulate the entire tree.
-fn tree_reload(store: &gtk::TreeStore, state: &State) {
-    let dir = &state.current_dir;
+fn tree_reload(store: &gtk::TreeStore, state_ref: &Rc<RefCell<State>>) {
+    let dir = {
+        let mut state = state_ref.borrow_mut();
+        for (_, monitor) in state.monitors.drain() {
+            monitor.cancel();
+        }
+        // Keep status until it is refreshed, unless it is of another work tree.
+        let is_other_work_tree = state.git_status.as_ref().map_or(false, |git_status| {
+            !Path::new(&state.current_dir).starts_with(git_status.root())
+        });
+        if is_other_work_tree {
+            state.git_status = None;
+        }
+        state.ignore = IgnoreMatcher::new(Path::new(&state.current_dir), &state.ignore_patterns);
+        state.current_dir.clone()
+    };
     store.clear();
-    populate_tree_nodes(store, state, dir, None);
+    populate_tree_nodes(store, &state_ref.borrow(), &dir, None);
+    watch_dir(store, state_ref, &dir);
+    refresh_git_status(store, state_ref);
 }
 
 /// Updates the dirctory list on top of the file browser.
@@ -451,72 +683,920 @@ fn populate_tree_nodes(
     dir: &str,
     parent: Option<&gtk::TreeIter>,
 ) {
-    let path = Path::new(dir);
-    let read_dir = match path.read_dir() {
+    for entry in read_dir_entries(state, dir) {
+        let iter = store.append(parent);
+        set_entry(store, state, &iter, &entry);
+    }
+}
+
+/// Populates the directory when it is expanded.
+///
+/// Returns `false` if the directory is empty, its row keeps the placeholder then.
+fn populate_expanded_dir(
+    store: &gtk::TreeStore,
+    state: &State,
+    dir: &str,
+    iter: &gtk::TreeIter,
+) -> bool {
+    // We cannot recursively populate all directories. Instead, we have prepared a single
+    // empty child entry for all directories, so the row will be expandable. Now, when a
+    // directory is expanded, populate its children.
+    if let Some(child) = store.iter_children(Some(iter)) {
+        let filename = store.get_value(&child, Column::Filename as i32);
+        if filename.get::<&str>().is_none() {
+            store.remove(&child);
+            populate_tree_nodes(store, state, dir, Some(iter));
+        } else {
+            // This directory is already populated, i.e. it has been expanded and collapsed
+            // again. It was not watched while collapsed, so bring its entries up to date.
+            sync_tree_nodes(store, state, dir, Some(iter));
+            // Rows further down the tree might have been silently collapsed without
+            // getting an event. Update their folder icon.
+            if let Some(child) = store.iter_children(Some(iter)) {
+                let mut tree_path = store.get_path(&child).unwrap();
+                while let Some(iter) = store.get_iter(&tree_path) {
+                    tree_path.next();
+                    let file_type = store.get_value(&iter, Column::FileType as i32).get::<u8>();
+                    if file_type == Some(FileType::Dir as u8) {
+                        store.set(&iter, &[Column::IconName as u32], &[&ICON_FOLDER_CLOSED]);
+                    }
+                }
+            }
+        }
+    }
+    if store.iter_has_child(iter) {
+        true
+    } else {
+        append_placeholder(store, iter);
+        false
+    }
+}
+
+/// Appends an empty child row, so the directory row is expandable before it is populated.
+fn append_placeholder(store: &gtk::TreeStore, parent: &gtk::TreeIter) {
+    let iter = store.append(Some(parent));
+    store.set(&iter, &[], &[]);
+}
+
+/// Updates already populated directory to its current contents.
+///
+/// Existing rows are kept, so their expansion and selection state is preserved.
+fn sync_tree_nodes(
+    store: &gtk::TreeStore,
+    state: &State,
+    dir: &str,
+    parent: Option<&gtk::TreeIter>,
+) {
+    let rows = child_rows(store, parent, Column::Path, |value| value.get::<String>());
+    let (matched, new_entries) = match_rows(&rows, read_dir_entries(state, dir));
+    if let Some(iter) = store.iter_children(parent) {
+        for entry in matched {
+            let has_next = match entry {
+                Some(entry) => {
+                    store.set(&iter, &[Column::Sensitive as u32], &[&!entry.ignored]);
+                    store.iter_next(&iter)
+                }
+                None => store.remove(&iter),
+            };
+            if !has_next {
+                break;
+            }
+        }
+    }
+    for entry in new_entries {
+        insert_entry(store, state, parent, &entry);
+    }
+    if let Some(parent) = parent {
+        if !store.iter_has_child(parent) {
+            append_placeholder(store, parent);
+        }
+    }
+}
+
+/// Values of the column for all rows directly below `parent`.
+fn child_rows<T, F: Fn(&glib::Value) -> Option<T>>(
+    store: &gtk::TreeStore,
+    parent: Option<&gtk::TreeIter>,
+    column: Column,
+    get: F,
+) -> Vec<Option<T>> {
+    let column = column as i32;
+    let mut rows = Vec::new();
+    if let Some(iter) = store.iter_children(parent) {
+        loop {
+            rows.push(get(&store.get_value(&iter, column)));
+            if !store.iter_next(&iter) {
+                break;
+            }
+        }
+    }
+    rows
+}
+
+/// Matches rows, given by their paths, with current entries of the directory.
+///
+/// Returns the entry of each row, `None` if the row should be removed, and entries that
+/// have no row yet.
+fn match_rows(
+    rows: &[Option<String>],
+    mut entries: Vec<Entry>,
+) -> (Vec<Option<Entry>>, Vec<Entry>) {
+    let matched = rows
+        .iter()
+        .map(|path| {
+            let pos = path
+                .as_ref()
+                .and_then(|path| entries.iter().position(|entry| entry.path == *path))?;
+            Some(entries.remove(pos))
+        })
+        .collect();
+    (matched, entries)
+}
+
+/// Entries of the directory that should be shown, sorted.
+fn read_dir_entries(state: &State, dir: &str) -> Vec<Entry> {
+    let read_dir = match Path::new(dir).read_dir() {
         Ok(read_dir) => read_dir,
         Err(err) => {
             error!("Couldn't populate tree: {}", err);
-            return;
+            return Vec::new();
         }
     };
-    let iter = read_dir.filter_map(Result::ok);
-    let mut entries: Vec<DirEntry> = if state.show_hidden {
-        iter.collect()
+    let mut entries: Vec<Entry> = read_dir
+        .filter_map(Result::ok)
+        .filter_map(|entry| read_entry(state, &entry.path()))
+        .collect();
+    entries.sort_unstable_by(|lhs, rhs| {
+        cmp_dirs_first(
+            (lhs.file_type == FileType::Dir, &lhs.filename),
+            (rhs.file_type == FileType::Dir, &rhs.filename),
+        )
+    });
+    entries
+}
+
+/// Reads the entry at the given path, `None` if it should not be shown.
+fn read_entry(state: &State, path: &Path) -> Option<Entry> {
+    // Skip paths that contain invalid unicode.
+    let filename = path.file_name()?.to_str()?;
+    if !state.show_hidden && (filename.starts_with('.') || filename.ends_with('~')) {
+        return None;
+    }
+    if state.hide_ignored && git_status(state, path) == Some(FileStatus::Ignored) {
+        return None;
+    }
+    // In case of invalid symlinks, we cannot obtain metadata.
+    let file_type = fs::metadata(path).ok()?.file_type();
+    let file_type = if file_type.is_dir() {
+        FileType::Dir
+    } else if file_type.is_file() {
+        FileType::File
     } else {
-        iter.filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
-            .filter(|entry| !entry.file_name().to_string_lossy().ends_with('~'))
-            .collect()
+        return None;
     };
-    entries.sort_unstable_by(|lhs, rhs| cmp_dirs_first(lhs, rhs).unwrap_or(Ordering::Equal));
-    for entry in entries {
-        let path = if let Some(path) = entry.path().to_str() {
-            path.to_owned()
+    let ignored = state.ignore.is_ignored(path, file_type == FileType::Dir);
+    if ignored && !state.show_ignored {
+        return None;
+    }
+    Some(Entry {
+        filename: filename.to_owned(),
+        path: path.to_str()?.to_owned(),
+        file_type,
+        ignored,
+    })
+}
+
+/// Fills a row of the tree with the given entry.
+fn set_entry(store: &gtk::TreeStore, state: &State, iter: &gtk::TreeIter, entry: &Entry) {
+    let icon = match entry.file_type {
+        FileType::Dir => ICON_FOLDER_CLOSED,
+        FileType::File => ICON_FILE,
+    };
+    let color = git_color(state, Path::new(&entry.path));
+    store.set(
+        iter,
+        &[0, 1, 2, 3, 4, 5],
+        &[
+            &entry.filename,
+            &entry.path,
+            &(entry.file_type as u8),
+            &icon,
+            &color,
+            &!entry.ignored,
+        ],
+    );
+    // For directories, append a single empty entry, so the expand arrow is shown. Its
+    // contents are dynamically populated when expanded (see `init`). Empty directories get it
+    // as well, since they are not watched until expanded and files might appear in them.
+    if let FileType::Dir = entry.file_type {
+        append_placeholder(store, iter);
+    }
+}
+
+/// Inserts the entry below `parent`, keeping the rows sorted.
+fn insert_entry(
+    store: &gtk::TreeStore,
+    state: &State,
+    parent: Option<&gtk::TreeIter>,
+    entry: &Entry,
+) {
+    let sibling = find_sorted_position(store, parent, entry, None);
+    let iter = store.insert_before(parent, sibling.as_ref());
+    set_entry(store, state, &iter, entry);
+}
+
+/// First row below `parent` that sorts after the entry, ignoring row `skip`.
+fn find_sorted_position(
+    store: &gtk::TreeStore,
+    parent: Option<&gtk::TreeIter>,
+    entry: &Entry,
+    skip: Option<&gtk::TreeIter>,
+) -> Option<gtk::TreeIter> {
+    let filenames = child_rows(store, parent, Column::Filename, |value| {
+        value.get::<String>()
+    });
+    let file_types = child_rows(store, parent, Column::FileType, |value| value.get::<u8>());
+    let rows: Vec<Option<(bool, String)>> = filenames
+        .into_iter()
+        .zip(file_types)
+        .map(|(filename, file_type)| {
+            filename.map(|filename| (file_type == Some(FileType::Dir as u8), filename))
+        })
+        .collect();
+    let skip = skip
+        .and_then(|skip| store.get_path(skip))
+        .and_then(|path| path.get_indices().last().cloned())
+        .map(|idx| idx as usize);
+    let pos = sorted_position(&rows, entry, skip)?;
+    store.iter_nth_child(parent, pos as i32)
+}
+
+/// Index of the first row that sorts after the entry, ignoring row `skip`.
+///
+/// Rows are given as `(is_dir, filename)`, `None` is the placeholder row.
+fn sorted_position(
+    rows: &[Option<(bool, String)>],
+    entry: &Entry,
+    skip: Option<usize>,
+) -> Option<usize> {
+    rows.iter()
+        .enumerate()
+        .find(|&(idx, row)| match *row {
+            Some((is_dir, ref filename)) if Some(idx) != skip => {
+                cmp_dirs_first(
+                    (entry.file_type == FileType::Dir, &entry.filename),
+                    (is_dir, filename),
+                ) == Ordering::Less
+            }
+            _ => false,
+        })
+        .map(|(idx, _)| idx)
+}
+
+/// Finds the row of the given path directly below `parent`.
+fn find_child(
+    store: &gtk::TreeStore,
+    parent: Option<&gtk::TreeIter>,
+    path: &Path,
+) -> Option<gtk::TreeIter> {
+    let iter = store.iter_children(parent)?;
+    loop {
+        let row_path = store.get_value(&iter, Column::Path as i32).get::<String>();
+        if row_path.as_ref().map(Path::new) == Some(path) {
+            return Some(iter);
+        }
+        if !store.iter_next(&iter) {
+            return None;
+        }
+    }
+}
+
+/// Finds the row of the given directory, `Some(None)` is the tree root.
+fn find_dir(store: &gtk::TreeStore, root: &str, dir: &Path) -> Option<Option<gtk::TreeIter>> {
+    let rel_dir = dir.strip_prefix(root).ok()?;
+    let mut path = PathBuf::from(root);
+    let mut iter = None;
+    for component in rel_dir.components() {
+        path.push(component);
+        iter = Some(find_child(store, iter.as_ref(), &path)?);
+    }
+    Some(iter)
+}
+
+/// Starts watching the directory, its changes are applied to the tree.
+fn watch_dir(store: &gtk::TreeStore, state_ref: &Rc<RefCell<State>>, dir: &str) {
+    let monitor = match gio::File::new_for_path(dir).monitor_directory(
+        gio::FileMonitorFlags::WATCH_MOVES,
+        None::<&gio::Cancellable>,
+    ) {
+        Ok(monitor) => monitor,
+        Err(err) => {
+            error!("Couldn't watch directory {}: {}", dir, err);
+            return;
+        }
+    };
+    // State owns the monitor, so do not keep state alive from its handler.
+    let state_weak = Rc::downgrade(state_ref);
+    monitor.connect_changed(clone!(store => move |_, file, other_file, event| {
+        if let Some(state_ref) = state_weak.upgrade() {
+            apply_dir_change(&store, &state_ref, file, other_file, event);
+        }
+    }));
+    if let Some(monitor) = state_ref
+        .borrow_mut()
+        .monitors
+        .insert(dir.to_owned(), monitor)
+    {
+        monitor.cancel();
+    }
+}
+
+/// Stops watching the directory and all directories below it.
+fn unwatch_dir(state: &mut State, dir: &Path) {
+    state.monitors.retain(|path, monitor| {
+        if Path::new(path).starts_with(dir) {
+            monitor.cancel();
+            false
         } else {
-            // Skip paths that contain invalid unicode.
-            continue;
-        };
-        let filename = entry.file_name().to_str().unwrap().to_owned();
-        let file_type = if let Ok(metadata) = fs::metadata(entry.path()) {
-            let file_type = metadata.file_type();
-            if file_type.is_dir() {
-                FileType::Dir
-            } else if file_type.is_file() {
-                FileType::File
+            true
+        }
+    });
+}
+
+fn apply_dir_change(
+    store: &gtk::TreeStore,
+    state_ref: &Rc<RefCell<State>>,
+    file: &gio::File,
+    other_file: Option<&gio::File>,
+    event: gio::FileMonitorEvent,
+) {
+    let path = match file.get_path() {
+        Some(path) => path,
+        None => return,
+    };
+    match event {
+        gio::FileMonitorEvent::Created | gio::FileMonitorEvent::MovedIn => {
+            insert_path(store, &state_ref.borrow(), &path);
+        }
+        gio::FileMonitorEvent::Deleted | gio::FileMonitorEvent::MovedOut => {
+            remove_path(store, state_ref, &path);
+        }
+        gio::FileMonitorEvent::Renamed => {
+            if let Some(new_path) = other_file.and_then(|file| file.get_path()) {
+                rename_path(store, state_ref, &path, &new_path);
+            }
+        }
+        _ => (),
+    }
+    if ignore::is_ignore_file(&path) {
+        if let Some(dir) = path.parent() {
+            state_ref.borrow().ignore.invalidate(dir);
+        }
+        sync_watched_dirs(store, state_ref);
+    }
+    // Any change, including file content, might change git status.
+    schedule_git_status(store, state_ref);
+}
+
+/// Updates content of the tree root and expanded directories, e.g. when ignore rules change.
+fn sync_watched_dirs(store: &gtk::TreeStore, state_ref: &Rc<RefCell<State>>) {
+    let mut removed = Vec::new();
+    {
+        let state = state_ref.borrow();
+        let mut dirs: Vec<&String> = state.monitors.keys().collect();
+        // Parents first, rows of their subdirectories might be removed.
+        dirs.sort();
+        for dir in dirs {
+            match find_dir(store, &state.current_dir, Path::new(dir)) {
+                Some(parent) => sync_tree_nodes(store, &state, dir, parent.as_ref()),
+                None => removed.push(PathBuf::from(dir)),
+            }
+        }
+    }
+    for dir in removed {
+        unwatch_dir(&mut state_ref.borrow_mut(), &dir);
+    }
+}
+
+/// Inserts a row for a new file, in case its directory is shown.
+fn insert_path(store: &gtk::TreeStore, state: &State, path: &Path) {
+    let parent = match path
+        .parent()
+        .and_then(|dir| find_dir(store, &state.current_dir, dir))
+    {
+        Some(parent) => parent,
+        None => return,
+    };
+    if find_child(store, parent.as_ref(), path).is_some() {
+        return;
+    }
+    // Directories that were never expanded are populated once they are.
+    if let Some(child) = store.iter_children(parent.as_ref()) {
+        let filename = store.get_value(&child, Column::Filename as i32);
+        if filename.get::<&str>().is_none() {
+            return;
+        }
+    }
+    if let Some(entry) = read_entry(state, path) {
+        insert_entry(store, state, parent.as_ref(), &entry);
+    }
+}
+
+/// Removes the row of a deleted file.
+fn remove_path(store: &gtk::TreeStore, state_ref: &Rc<RefCell<State>>, path: &Path) {
+    let iter = {
+        let state = state_ref.borrow();
+        path.parent()
+            .and_then(|dir| find_dir(store, &state.current_dir, dir))
+            .and_then(|parent| find_child(store, parent.as_ref(), path))
+    };
+    if let Some(iter) = iter {
+        unwatch_dir(&mut state_ref.borrow_mut(), path);
+        let parent = store.iter_parent(&iter);
+        store.remove(&iter);
+        // Directory became empty, it is populated again once expanded.
+        if let Some(parent) = parent {
+            if !store.iter_has_child(&parent) {
+                append_placeholder(store, &parent);
+                if let Some(dir) = store
+                    .get_value(&parent, Column::Path as i32)
+                    .get::<String>()
+                {
+                    unwatch_dir(&mut state_ref.borrow_mut(), Path::new(&dir));
+                }
+            }
+        }
+    }
+}
+
+/// Renames the row in place, so it keeps its expanded children and selection.
+fn rename_path(
+    store: &gtk::TreeStore,
+    state_ref: &Rc<RefCell<State>>,
+    old_path: &Path,
+    new_path: &Path,
+) {
+    let (iter, entry) = {
+        let state = state_ref.borrow();
+        let parent = old_path
+            .parent()
+            .and_then(|dir| find_dir(store, &state.current_dir, dir));
+        let iter = parent.and_then(|parent| find_child(store, parent.as_ref(), old_path));
+        (iter, read_entry(&state, new_path))
+    };
+    let (iter, entry) = match (iter, entry) {
+        (Some(iter), Some(entry)) => (iter, entry),
+        (Some(_), None) => return remove_path(store, state_ref, old_path),
+        (None, Some(_)) => return insert_path(store, &state_ref.borrow(), new_path),
+        (None, None) => return,
+    };
+
+    store.set(
+        &iter,
+        &[Column::Filename as u32, Column::Path as u32],
+        &[&entry.filename, &entry.path],
+    );
+    update_child_paths(store, &iter, new_path);
+    let parent = store.iter_parent(&iter);
+    let sibling = find_sorted_position(store, parent.as_ref(), &entry, Some(&iter));
+    store.move_before(&iter, sibling.as_ref());
+
+    // Monitors of expanded directories below still watch the same directories, but report
+    // their old paths.
+    let watched_dirs = renamed_dirs(state_ref.borrow().monitors.keys(), old_path, new_path);
+    unwatch_dir(&mut state_ref.borrow_mut(), old_path);
+    for dir in watched_dirs {
+        watch_dir(store, state_ref, &dir);
+    }
+}
+
+/// New paths of the directories that are below `old_path`, after it was renamed to `new_path`.
+fn renamed_dirs<'a, I: Iterator<Item = &'a String>>(
+    dirs: I,
+    old_path: &Path,
+    new_path: &Path,
+) -> Vec<String> {
+    dirs.filter_map(|dir| Path::new(dir).strip_prefix(old_path).ok())
+        .map(|rel_dir| {
+            // joining empty path appends a trailing separator
+            if rel_dir.as_os_str().is_empty() {
+                new_path.to_path_buf()
             } else {
-                continue;
+                new_path.join(rel_dir)
             }
-        } else {
-            // In case of invalid symlinks, we cannot obtain metadata.
-            continue;
-        };
-        let icon = match file_type {
-            FileType::Dir => ICON_FOLDER_CLOSED,
-            FileType::File => ICON_FILE,
-        };
-        // When we get until here, we want to show the entry. Append it to the tree.
-        let iter = store.append(parent);
-        store.set(
-            &iter,
-            &[0, 1, 2, 3],
-            &[&filename, &path, &(file_type as u8), &icon],
+        })
+        .filter_map(|dir| dir.to_str().map(str::to_owned))
+        .collect()
+}
+
+/// Updates path of all rows below `parent` after the directory was renamed.
+fn update_child_paths(store: &gtk::TreeStore, parent: &gtk::TreeIter, dir: &Path) {
+    let iter = match store.iter_children(Some(parent)) {
+        Some(iter) => iter,
+        None => return,
+    };
+    loop {
+        let filename = store
+            .get_value(&iter, Column::Filename as i32)
+            .get::<String>();
+        if let Some(path) =
+            filename.and_then(|filename| dir.join(filename).to_str().map(str::to_owned))
+        {
+            store.set(&iter, &[Column::Path as u32], &[&path]);
+            update_child_paths(store, &iter, Path::new(&path));
+        }
+        if !store.iter_next(&iter) {
+            return;
+        }
+    }
+}
+
+fn git_status(state: &State, path: &Path) -> Option<FileStatus> {
+    state
+        .git_status
+        .as_ref()
+        .and_then(|git_status| git_status.get(path))
+}
+
+fn git_color<'a>(state: &'a State, path: &Path) -> Option<&'a str> {
+    git_status(state, path)
+        .and_then(|status| state.git_colors.get(&status))
+        .map(String::as_str)
+}
+
+/// Refreshes git status after a short delay, so bursts of changes cause one refresh only.
+fn schedule_git_status(store: &gtk::TreeStore, state_ref: &Rc<RefCell<State>>) {
+    {
+        let mut state = state_ref.borrow_mut();
+        if state.git_status_scheduled {
+            return;
+        }
+        state.git_status_scheduled = true;
+    }
+
+    let state_weak = Rc::downgrade(state_ref);
+    gtk::timeout_add(
+        GIT_STATUS_DELAY_MS,
+        clone!(store => move || {
+            if let Some(state_ref) = state_weak.upgrade() {
+                state_ref.borrow_mut().git_status_scheduled = false;
+                refresh_git_status(&store, &state_ref);
+            }
+            Continue(false)
+        }),
+    );
+}
+
+/// Loads git status of current dir in background and applies it to the tree.
+fn refresh_git_status(store: &gtk::TreeStore, state_ref: &Rc<RefCell<State>>) {
+    let dir = state_ref.borrow().current_dir.clone();
+    let store = UiMutex::new(store.clone());
+    let state_weak = UiMutex::new(Rc::downgrade(state_ref));
+
+    thread::spawn(move || {
+        let mut git_status = Some(GitStatus::load(Path::new(&dir)));
+
+        glib::idle_add(move || {
+            if let Some(state_ref) = state_weak.borrow().upgrade() {
+                // Current dir changed while git was running, there is another refresh.
+                if state_ref.borrow().current_dir == dir {
+                    state_ref.borrow_mut().git_status = git_status.take().unwrap();
+                    apply_git_status(&store.borrow(), &state_ref);
+                }
+            }
+            Continue(false)
+        });
+    });
+}
+
+/// Updates colors of all rows, removes ignored entries if they should be hidden.
+fn apply_git_status(store: &gtk::TreeStore, state_ref: &Rc<RefCell<State>>) {
+    let mut ignored = Vec::new();
+    {
+        let state = state_ref.borrow();
+        store.foreach(|_, _, iter| {
+            if let Some(path) = store.get_value(iter, Column::Path as i32).get::<String>() {
+                let path = Path::new(&path);
+                store.set(
+                    iter,
+                    &[Column::Foreground as u32],
+                    &[&git_color(&state, path)],
+                );
+                if state.hide_ignored && git_status(&state, path) == Some(FileStatus::Ignored) {
+                    ignored.push(path.to_path_buf());
+                }
+            }
+            false
+        });
+    }
+
+    // Rows are visited parents first, so remove children first.
+    for path in ignored.into_iter().rev() {
+        remove_path(store, state_ref, &path);
+    }
+}
+
+/// Path relative to current dir, if it is below it.
+fn relative_path<'a>(cwd: &str, path: &'a str) -> &'a str {
+    Path::new(path)
+        .strip_prefix(cwd)
+        .ok()
+        .and_then(Path::to_str)
+        .unwrap_or(path)
+}
+
+/// Opens the file in nvim with the given command, e.g. `e` or `split`.
+pub fn open_file(nvim_ref: &NeovimClient, cwd: &str, file_path: &str, cmd: &str) {
+    let file_path = escape_filename(relative_path(cwd, file_path));
+    nvim_ref
+        .nvim()
+        .unwrap()
+        .command_async(&format!(":{} {}", cmd, file_path))
+        .cb(|r| r.report_err())
+        .call();
+}
+
+/// Shows the error of a file operation in nvim.
+fn report_file_err(nvim_ref: &NeovimClient, msg: &str) {
+    error!("{}", msg);
+    if let Some(mut nvim) = nvim_ref.nvim() {
+        nvim.err_writeln_async(msg).cb(|r| r.report_err()).call();
+    }
+}
+
+fn copy_to_clipboard(text: &str) {
+    let clipboard = gtk::Clipboard::get(&gdk::Atom::intern("CLIPBOARD"));
+    clipboard.set_text(text);
+}
+
+/// Directory to create new entries in, the one the context menu was opened for.
+fn target_dir(state: &State) -> PathBuf {
+    match state.selected_path {
+        Some(ref path) if Path::new(path).is_dir() => PathBuf::from(path),
+        Some(ref path) => Path::new(path)
+            .parent()
+            .map_or_else(|| PathBuf::from(&state.current_dir), Path::to_path_buf),
+        None => PathBuf::from(&state.current_dir),
+    }
+}
+
+/// Shows the new entry in the tree and selects it.
+///
+/// Rows are added right away, events of the directory monitor for them are ignored later.
+fn reveal_new_path(store: &gtk::TreeStore, tree: &gtk::TreeView, state: &State, path: &Path) {
+    insert_path(store, state, path);
+    if let Ok(rel_path) = path.strip_prefix(&state.current_dir) {
+        reveal_path_in_tree(store, tree, rel_path);
+    }
+}
+
+fn create_entry(
+    store: &gtk::TreeStore,
+    tree: &gtk::TreeView,
+    widget: &gtk::Box,
+    state_ref: &Rc<RefCell<State>>,
+    nvim_ref: &NeovimClient,
+    file_type: FileType,
+) {
+    let title = match file_type {
+        FileType::File => "New File",
+        FileType::Dir => "New Directory",
+    };
+    let name = match show_name_dlg(parent_window(widget).as_ref(), title, "") {
+        Some(name) => name,
+        None => return,
+    };
+    let path = target_dir(&state_ref.borrow()).join(name);
+
+    let result = match file_type {
+        FileType::File => path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| {
+                fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&path)
+            })
+            .map(|_| ()),
+        FileType::Dir => fs::create_dir(&path),
+    };
+    if let Err(err) = result {
+        report_file_err(
+            nvim_ref,
+            &format!("Couldn't create {}: {}", path.display(), err),
+        );
+        return;
+    }
+
+    let state = state_ref.borrow();
+    reveal_new_path(store, tree, &state, &path);
+    if let (FileType::File, Some(path)) = (file_type, path.to_str()) {
+        open_file(nvim_ref, &state.current_dir, path, "e");
+    }
+}
+
+/// Renames the selected entry, buffers of renamed files are renamed in nvim as well.
+fn rename_entry(
+    store: &gtk::TreeStore,
+    widget: &gtk::Box,
+    state_ref: &Rc<RefCell<State>>,
+    nvim_ref: &NeovimClient,
+) {
+    let old_path = match state_ref.borrow().selected_path {
+        Some(ref path) => PathBuf::from(path),
+        None => return,
+    };
+    let old_name = match old_path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => name.to_owned(),
+        None => return,
+    };
+    let name = match show_name_dlg(parent_window(widget).as_ref(), "Rename", &old_name) {
+        Some(ref name) if *name != old_name => name.clone(),
+        _ => return,
+    };
+    let new_path = old_path.with_file_name(name);
+
+    // `fs::rename` silently replaces existing files.
+    let result = if new_path.exists() {
+        Err(io::Error::new(io::ErrorKind::AlreadyExists, "file exists"))
+    } else {
+        fs::rename(&old_path, &new_path)
+    };
+    if let Err(err) = result {
+        report_file_err(
+            nvim_ref,
+            &format!("Couldn't rename {}: {}", old_path.display(), err),
         );
-        // For directories, check whether the directory is empty. If not, append a single empty
-        // entry, so the expand arrow is shown. Its contents are dynamically populated when
-        // expanded (see `init`).
-        if let FileType::Dir = file_type {
-            let not_empty = if let Ok(mut dir) = entry.path().read_dir() {
-                dir.next().is_some()
+        return;
+    }
+
+    rename_path(store, state_ref, &old_path, &new_path);
+    if let (Some(old_path), Some(new_path)) = (old_path.to_str(), new_path.to_str()) {
+        state_ref.borrow_mut().selected_path = Some(new_path.to_owned());
+        nvim_ref
+            .nvim()
+            .unwrap()
+            .call_function_async("GuiRenameBuffers", vec![old_path.into(), new_path.into()])
+            .cb(|r| r.report_err())
+            .call();
+    }
+}
+
+fn duplicate_entry(
+    store: &gtk::TreeStore,
+    tree: &gtk::TreeView,
+    state_ref: &Rc<RefCell<State>>,
+    nvim_ref: &NeovimClient,
+) {
+    let path = match state_ref.borrow().selected_path {
+        Some(ref path) => PathBuf::from(path),
+        None => return,
+    };
+    let new_path = match duplicate_path(&path, path.is_dir(), |path| path.exists()) {
+        Some(new_path) => new_path,
+        None => return,
+    };
+    if let Err(err) = copy_recursive(&path, &new_path) {
+        report_file_err(
+            nvim_ref,
+            &format!("Couldn't duplicate {}: {}", path.display(), err),
+        );
+        return;
+    }
+    reveal_new_path(store, tree, &state_ref.borrow(), &new_path);
+}
+
+fn trash_entry(store: &gtk::TreeStore, state_ref: &Rc<RefCell<State>>, nvim_ref: &NeovimClient) {
+    let path = match state_ref.borrow().selected_path {
+        Some(ref path) => PathBuf::from(path),
+        None => return,
+    };
+    match gio::File::new_for_path(&path).trash(None::<&gio::Cancellable>) {
+        Ok(()) => {
+            state_ref.borrow_mut().selected_path = None;
+            remove_path(store, state_ref, &path);
+        }
+        Err(err) => report_file_err(
+            nvim_ref,
+            &format!("Couldn't move {} to trash: {}", path.display(), err),
+        ),
+    }
+}
+
+/// Free path for a copy of the file, like `name copy.ext` or `name copy 2.ext`.
+fn duplicate_path<F: Fn(&Path) -> bool>(path: &Path, is_dir: bool, exists: F) -> Option<PathBuf> {
+    let (stem, extension) = if is_dir {
+        (path.file_name()?.to_str()?, None)
+    } else {
+        (
+            path.file_stem()?.to_str()?,
+            path.extension().and_then(|ext| ext.to_str()),
+        )
+    };
+    (1..)
+        .map(|idx| {
+            let name = if idx == 1 {
+                format!("{} copy", stem)
             } else {
-                false
+                format!("{} copy {}", stem, idx)
             };
-            if not_empty {
-                let iter = store.append(Some(&iter));
-                store.set(&iter, &[], &[]);
+            match extension {
+                Some(extension) => path.with_file_name(format!("{}.{}", name, extension)),
+                None => path.with_file_name(name),
             }
+        })
+        .find(|path| !exists(path))
+}
+
+fn copy_recursive(from: &Path, to: &Path) -> io::Result<()> {
+    // Symlinks are not followed, so links to directories are not copied with their targets
+    let file_type = fs::symlink_metadata(from)?.file_type();
+    if file_type.is_symlink() {
+        copy_symlink(from, to)
+    } else if file_type.is_dir() {
+        fs::create_dir(to)?;
+        for entry in from.read_dir()? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
         }
+        Ok(())
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(windows)]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    let target = fs::read_link(from)?;
+    if from.is_dir() {
+        std::os::windows::fs::symlink_dir(target, to)
+    } else {
+        std::os::windows::fs::symlink_file(target, to)
     }
 }
 
+fn parent_window(widget: &gtk::Box) -> Option<gtk::Window> {
+    widget
+        .get_toplevel()
+        .and_then(|toplevel| toplevel.downcast::<gtk::Window>().ok())
+}
+
+/// Asks for the name of a new or renamed entry.
+fn show_name_dlg(parent: Option<&gtk::Window>, title: &str, name: &str) -> Option<String> {
+    let flags = gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT;
+    let dlg = gtk::Dialog::new_with_buttons(
+        Some(title),
+        parent,
+        flags,
+        &[
+            ("_Cancel", gtk::ResponseType::Cancel),
+            ("_OK", gtk::ResponseType::Ok),
+        ],
+    );
+    dlg.set_default_response(gtk::ResponseType::Ok);
+
+    let content = dlg.get_content_area();
+    content.set_spacing(6);
+    content.set_border_width(10);
+
+    let label = gtk::Label::new(Some("Name"));
+    label.set_halign(gtk::Align::Start);
+    content.pack_start(&label, false, true, 0);
+
+    let entry = gtk::Entry::new();
+    entry.set_activates_default(true);
+    entry.set_width_chars(40);
+    entry.set_text(name);
+    content.pack_start(&entry, false, true, 0);
+
+    content.show_all();
+
+    // Select the name without extension, like file managers do.
+    let stem_len = Path::new(name)
+        .file_stem()
+        .map_or(0, |stem| stem.to_string_lossy().chars().count());
+    entry.grab_focus();
+    entry.select_region(0, stem_len as i32);
+
+    let name = match dlg.run() {
+        gtk::ResponseType::Ok => {
+            let name = entry.get_text().map(|t| t.trim().to_owned());
+            name.filter(|n| !n.is_empty())
+        }
+        _ => None,
+    };
+
+    dlg.destroy();
+
+    name
+}
+
 fn get_current_dir(nvim: &mut NeovimRef) -> Option<String> {
     match nvim.eval("getcwd()") {
         Ok(cwd) => cwd.as_str().map(|s| s.to_owned()),
@@ -531,6 +1611,29 @@ fn get_current_dir(nvim: &mut NeovimRef) -> Option<String> {
 ///
 /// Returns `true` if the file could be successfully revealed.
 fn reveal_path_in_tree(store: &gtk::TreeStore, tree: &gtk::TreeView, rel_file_path: &Path) -> bool {
+    if let Some(tree_path) = expand_path_in_tree(store, tree, rel_file_path) {
+        tree.set_cursor(&tree_path, Option::<&gtk::TreeViewColumn>::None, false);
+        true
+    } else {
+        false
+    }
+}
+
+/// Expands the given directories, paths are relative to the tree root.
+fn expand_dirs_in_tree(store: &gtk::TreeStore, tree: &gtk::TreeView, rel_dirs: &[String]) {
+    for dir in rel_dirs {
+        expand_path_in_tree(store, tree, Path::new(dir));
+    }
+}
+
+/// Expands all directories on the way to the given path, including the path itself.
+///
+/// Returns the tree path of the entry if it exists.
+fn expand_path_in_tree(
+    store: &gtk::TreeStore,
+    tree: &gtk::TreeView,
+    rel_file_path: &Path,
+) -> Option<gtk::TreePath> {
     let mut tree_path = gtk::TreePath::new();
     'components: for component in rel_file_path.components() {
         if let Component::Normal(component) = component {
@@ -544,14 +1647,150 @@ fn reveal_path_in_tree(store: &gtk::TreeStore, tree: &gtk::TreeView, rel_file_pa
                 }
                 tree_path.next();
             }
-            return false;
+            return None;
         } else {
-            return false;
+            return None;
         }
     }
     if tree_path.get_depth() == 0 {
-        return false;
+        return None;
+    }
+    Some(tree_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cmp_dirs_first() {
+        assert_eq!(
+            Ordering::Less,
+            cmp_dirs_first((true, "src"), (false, "Cargo.toml"))
+        );
+        assert_eq!(Ordering::Greater, cmp_dirs_first((false, "a"), (true, "b")));
+        assert_eq!(
+            Ordering::Less,
+            cmp_dirs_first((false, "main.rs"), (false, "Makefile"))
+        );
+        assert_eq!(
+            Ordering::Greater,
+            cmp_dirs_first((true, "tests"), (true, "Resources"))
+        );
+    }
+
+    fn entry(path: &str, file_type: FileType) -> Entry {
+        Entry {
+            filename: Path::new(path)
+                .file_name()
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_owned(),
+            path: path.to_owned(),
+            file_type,
+            ignored: false,
+        }
+    }
+
+    #[test]
+    fn test_match_rows() {
+        let rows = [
+            Some("/src/bin".to_owned()),
+            Some("/src/lib.rs".to_owned()),
+            None,
+            Some("/src/main.rs".to_owned()),
+        ];
+        let entries = vec![
+            entry("/src/main.rs", FileType::File),
+            entry("/src/bin", FileType::Dir),
+            entry("/src/util.rs", FileType::File),
+        ];
+
+        let (matched, new_entries) = match_rows(&rows, entries);
+        let matched: Vec<Option<&str>> = matched
+            .iter()
+            .map(|entry| entry.as_ref().map(|entry| entry.path.as_str()))
+            .collect();
+        assert_eq!(
+            vec![Some("/src/bin"), None, None, Some("/src/main.rs")],
+            matched
+        );
+        assert_eq!(1, new_entries.len());
+        assert_eq!("/src/util.rs", new_entries[0].path);
+    }
+
+    #[test]
+    fn test_sorted_position() {
+        let rows = [
+            Some((true, "bin".to_owned())),
+            Some((false, "lib.rs".to_owned())),
+            Some((false, "main.rs".to_owned())),
+        ];
+
+        assert_eq!(
+            Some(1),
+            sorted_position(&rows, &entry("/src/tests", FileType::Dir), None)
+        );
+        assert_eq!(
+            Some(2),
+            sorted_position(&rows, &entry("/src/LICENSE", FileType::File), None)
+        );
+        assert_eq!(
+            None,
+            sorted_position(&rows, &entry("/src/util.rs", FileType::File), None)
+        );
+        assert_eq!(
+            Some(0),
+            sorted_position(&rows, &entry("/src/a", FileType::Dir), None)
+        );
+        // lib.rs renamed to mod.rs moves after main.rs
+        assert_eq!(
+            None,
+            sorted_position(&rows, &entry("/src/mod.rs", FileType::File), Some(1))
+        );
+        // placeholder row is never a position
+        assert_eq!(
+            None,
+            sorted_position(&[None], &entry("/src/a", FileType::Dir), None)
+        );
+    }
+
+    #[test]
+    fn test_renamed_dirs() {
+        let dirs = [
+            "/src".to_owned(),
+            "/src/old".to_owned(),
+            "/src/old/inner".to_owned(),
+            "/src/older".to_owned(),
+        ];
+
+        assert_eq!(
+            vec!["/src/new".to_owned(), "/src/new/inner".to_owned()],
+            renamed_dirs(dirs.iter(), Path::new("/src/old"), Path::new("/src/new"))
+        );
+    }
+
+    #[test]
+    fn test_duplicate_path() {
+        let existing = [Path::new("/src/main copy.rs")];
+        let exists = |path: &Path| existing.contains(&path);
+
+        assert_eq!(
+            Some(PathBuf::from("/src/lib copy.rs")),
+            duplicate_path(Path::new("/src/lib.rs"), false, exists)
+        );
+        assert_eq!(
+            Some(PathBuf::from("/src/main copy 2.rs")),
+            duplicate_path(Path::new("/src/main.rs"), false, exists)
+        );
+        assert_eq!(
+            Some(PathBuf::from("/src/.vimrc copy")),
+            duplicate_path(Path::new("/src/.vimrc"), false, exists)
+        );
+        assert_eq!(
+            Some(PathBuf::from("/src/dir.d copy")),
+            duplicate_path(Path::new("/src/dir.d"), true, exists)
+        );
     }
-    tree.set_cursor(&tree_path, Option::<&gtk::TreeViewColumn>::None, false);
-    true
 }
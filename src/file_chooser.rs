@@ -0,0 +1,70 @@
+use std::cell::RefCell;
+use std::path::Path;
+
+use gtk::prelude::*;
+
+use crate::nvim::{FileChooserAction, FileChooserArgs};
+
+/// Shows native file chooser, `cb` gets chosen paths
+///
+/// Dialog is modal but does not run own main loop, `cb` is called from response handler.
+/// List of paths is empty if dialog is canceled.
+pub fn show<F>(parent: Option<&gtk::Window>, args: FileChooserArgs, cb: F)
+where
+    F: FnOnce(Vec<String>) + 'static,
+{
+    let (action, accept_label) = match args.action {
+        FileChooserAction::Open => (gtk::FileChooserAction::Open, "_Open"),
+        FileChooserAction::Save => (gtk::FileChooserAction::Save, "_Save"),
+        FileChooserAction::SelectFolder => (gtk::FileChooserAction::SelectFolder, "_Select"),
+    };
+
+    let dlg = gtk::FileChooserNative::new(
+        args.title.as_ref().map(String::as_str),
+        parent,
+        action,
+        Some(accept_label),
+        None,
+    );
+    dlg.set_do_overwrite_confirmation(true);
+    dlg.set_select_multiple(args.multiple && args.action == FileChooserAction::Open);
+
+    if let Some(ref path) = args.path {
+        let path = Path::new(path);
+        if path.is_dir() {
+            dlg.set_current_folder(path);
+        } else if args.action == FileChooserAction::Save {
+            // file may not exist yet, so set folder and name separately
+            if let Some(dir) = path.parent() {
+                dlg.set_current_folder(dir);
+            }
+            if let Some(name) = path.file_name() {
+                dlg.set_current_name(name);
+            }
+        } else {
+            dlg.set_filename(path);
+        }
+    }
+
+    dlg.set_modal(true);
+
+    // handler keeps dialog alive until response, destroy disconnects it
+    let cb = RefCell::new(Some(cb));
+    let dlg_ref = dlg.clone();
+    dlg.connect_response(move |_, response| {
+        let paths = if response == gtk::ResponseType::Accept {
+            dlg_ref
+                .get_filenames()
+                .into_iter()
+                .filter_map(|path| path.to_str().map(str::to_owned))
+                .collect()
+        } else {
+            vec![]
+        };
+        if let Some(cb) = cb.borrow_mut().take() {
+            cb(paths);
+        }
+        dlg_ref.destroy();
+    });
+    dlg.show();
+}
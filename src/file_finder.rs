@@ -0,0 +1,367 @@
+//! Fuzzy file finder popover, see `:help nvim-gtk-file-finder`
+
+use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::thread;
+
+use gtk::prelude::*;
+use gtk::{ListStore, Orientation, PolicyType, Popover, ScrolledWindow, TreeView, TreeViewColumn};
+
+use htmlescape::encode_minimal;
+use neovim_lib::NeovimApi;
+
+use crate::file_browser;
+use crate::fuzzy::Pattern;
+use crate::ignore::IgnoreMatcher;
+use crate::nvim::ErrorReport;
+use crate::shell::Shell;
+use crate::ui::UiMutex;
+
+/// Only best matches are shown, the rest is counted only
+const MAX_RESULTS: usize = 100;
+/// Indexing stops after this many files, e.g. when current dir is home directory
+const MAX_FILES: usize = 100_000;
+
+enum Column {
+    /// Path with highlighted matched characters
+    Markup,
+    /// Path relative to indexed directory
+    Path,
+}
+
+pub struct FileFinder {
+    shell: Rc<RefCell<Shell>>,
+    popup: Popover,
+    search_entry: gtk::SearchEntry,
+    tree: TreeView,
+    store: ListStore,
+    status_label: gtk::Label,
+    /// Indexed directory, files are relative to it
+    dir: String,
+    files: Vec<String>,
+    /// Directory being indexed in background
+    indexing: Option<String>,
+}
+
+impl FileFinder {
+    pub fn new(ref_widget: &gtk::Box, shell: Rc<RefCell<Shell>>) -> Arc<UiMutex<FileFinder>> {
+        let finder = FileFinder {
+            shell,
+            popup: Popover::new(Some(ref_widget)),
+            search_entry: gtk::SearchEntry::new(),
+            tree: TreeView::new(),
+            store: ListStore::new(&[glib::Type::String, glib::Type::String]),
+            status_label: gtk::Label::new(None),
+            dir: String::new(),
+            files: Vec::new(),
+            indexing: None,
+        };
+
+        finder.popup.set_position(gtk::PositionType::Bottom);
+
+        finder.tree.set_model(Some(&finder.store));
+        finder.tree.set_headers_visible(false);
+        finder.tree.set_activate_on_single_click(true);
+        finder.tree.set_can_focus(false);
+
+        let renderer = gtk::CellRendererText::new();
+        renderer.set_property_ellipsize(pango::EllipsizeMode::Start);
+        renderer.set_padding(5, 2);
+        let column = TreeViewColumn::new();
+        column.pack_start(&renderer, true);
+        column.add_attribute(&renderer, "markup", Column::Markup as i32);
+        finder.tree.append_column(&column);
+
+        let scroll = ScrolledWindow::new(
+            Option::<&gtk::Adjustment>::None,
+            Option::<&gtk::Adjustment>::None,
+        );
+        scroll.set_policy(PolicyType::Never, PolicyType::Automatic);
+        scroll.set_shadow_type(gtk::ShadowType::In);
+        scroll.set_size_request(500, 300);
+        scroll.add(&finder.tree);
+
+        finder.status_label.set_halign(gtk::Align::Start);
+        finder
+            .status_label
+            .get_style_context()
+            .add_class(&gtk::STYLE_CLASS_DIM_LABEL);
+
+        let vbox = gtk::Box::new(Orientation::Vertical, 5);
+        vbox.set_border_width(5);
+        vbox.pack_start(&finder.search_entry, false, true, 0);
+        vbox.pack_start(&scroll, true, true, 0);
+        vbox.pack_start(&finder.status_label, false, true, 0);
+        vbox.show_all();
+        finder.popup.add(&vbox);
+
+        let finder = Arc::new(UiMutex::new(finder));
+
+        let finder_ref = finder.clone();
+        finder
+            .borrow()
+            .search_entry
+            .connect_search_changed(move |_| finder_ref.borrow().filter());
+
+        let finder_ref = finder.clone();
+        finder
+            .borrow()
+            .search_entry
+            .connect_activate(move |_| open_selected(&finder_ref));
+
+        // Selection is moved while focus stays in search entry.
+        let finder_ref = finder.clone();
+        finder
+            .borrow()
+            .search_entry
+            .connect_key_press_event(move |_, ev| {
+                let step = match ev.get_keyval() {
+                    gdk::enums::key::Down => 1,
+                    gdk::enums::key::Up => -1,
+                    _ => return Inhibit(false),
+                };
+                finder_ref.borrow().move_selection(step);
+                Inhibit(true)
+            });
+
+        let finder_ref = finder.clone();
+        finder
+            .borrow()
+            .tree
+            .connect_row_activated(move |_, _, _| open_selected(&finder_ref));
+
+        let finder_ref = finder.clone();
+        finder
+            .borrow()
+            .popup
+            .connect_closed(move |_| finder_ref.borrow().store.clear());
+
+        finder
+    }
+
+    /// Shows finder for current dir, files are indexed again each time
+    ///
+    /// `ignore_patterns` are used in addition to ignore files, same as in file browser.
+    pub fn show(finder_ref: &Arc<UiMutex<FileFinder>>, ignore_patterns: &[String]) {
+        let mut finder = finder_ref.borrow_mut();
+        let dir = match finder.current_dir() {
+            Some(dir) => dir,
+            None => return,
+        };
+
+        if finder.dir != dir {
+            finder.dir = dir.clone();
+            finder.files.clear();
+        }
+        if finder.indexing.as_ref() != Some(&dir) {
+            finder.indexing = Some(dir.clone());
+            index_files_async(finder_ref.clone(), dir, ignore_patterns.to_vec());
+        }
+
+        // Point to the top center of the shell.
+        if let Some(widget) = finder.popup.get_relative_to() {
+            finder.popup.set_pointing_to(&gtk::Rectangle {
+                x: widget.get_allocated_width() / 2,
+                y: 0,
+                width: 1,
+                height: 1,
+            });
+        }
+        finder.search_entry.set_text("");
+        finder.filter();
+        finder.popup.popup();
+        finder.search_entry.grab_focus();
+    }
+
+    fn current_dir(&self) -> Option<String> {
+        let shell = self.shell.borrow();
+        let state = shell.state.borrow();
+        let mut nvim = state.try_nvim()?;
+        let dir = nvim.call_function("getcwd", vec![]).ok_and_report()?;
+        dir.as_str().map(str::to_owned)
+    }
+
+    /// Shows best matches of search text
+    fn filter(&self) {
+        let query = self.search_entry.get_text();
+        let query = query.as_ref().map_or("", |query| query.as_str());
+
+        let pattern = Pattern::new(query);
+        let mut matches: Vec<_> = self
+            .files
+            .iter()
+            .filter_map(|path| pattern.best_match(path).map(|m| (m, path)))
+            .collect();
+        // Best score first, shorter paths on equal score.
+        matches.sort_by(|(a, a_path), (b, b_path)| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| a_path.len().cmp(&b_path.len()))
+                .then_with(|| a_path.cmp(b_path))
+        });
+
+        self.store.clear();
+        for (m, path) in matches.iter().take(MAX_RESULTS) {
+            self.store.insert_with_values(
+                None,
+                &[Column::Markup as u32, Column::Path as u32],
+                &[&highlight_markup(path, &m.positions), path],
+            );
+        }
+        if let Some(iter) = self.store.get_iter_first() {
+            self.tree.get_selection().select_iter(&iter);
+        }
+
+        let status = if self.indexing.is_some() && self.files.is_empty() {
+            "Indexing…".to_owned()
+        } else {
+            format!("{} of {} files", matches.len(), self.files.len())
+        };
+        self.status_label.set_text(&status);
+    }
+
+    fn move_selection(&self, step: i32) {
+        let selection = self.tree.get_selection();
+        let row = selection
+            .get_selected()
+            .and_then(|(_, iter)| self.store.get_path(&iter))
+            .and_then(|path| path.get_indices().first().cloned())
+            .unwrap_or(0);
+        let count = self.store.iter_n_children(None);
+        let row = (row + step).max(0).min(count - 1);
+        if let Some(iter) = self.store.iter_nth_child(None, row) {
+            selection.select_iter(&iter);
+            if let Some(path) = self.store.get_path(&iter) {
+                self.tree
+                    .scroll_to_cell(Some(&path), None::<&TreeViewColumn>, false, 0.0, 0.0);
+            }
+        }
+    }
+
+    /// Opens selected file in current window, returns `false` if nothing is selected
+    fn open_selected(&self) -> bool {
+        let path = self
+            .tree
+            .get_selection()
+            .get_selected()
+            .and_then(|(model, iter)| model.get_value(&iter, Column::Path as i32).get::<String>());
+        let path = match path {
+            Some(path) => Path::new(&self.dir).join(path),
+            None => return false,
+        };
+
+        if let Some(path) = path.to_str() {
+            let nvim = self.shell.borrow().state.borrow().nvim_clone();
+            file_browser::open_file(&nvim, &self.dir, path, "e");
+        }
+        true
+    }
+}
+
+fn open_selected(finder_ref: &Arc<UiMutex<FileFinder>>) {
+    if finder_ref.borrow().open_selected() {
+        // closed handler borrows finder itself
+        let popup = finder_ref.borrow().popup.clone();
+        popup.popdown();
+    }
+}
+
+fn index_files_async(finder_ref: Arc<UiMutex<FileFinder>>, dir: String, patterns: Vec<String>) {
+    thread::spawn(move || {
+        let mut files = Some(index_files(Path::new(&dir), &patterns));
+
+        glib::idle_add(move || {
+            let mut finder = finder_ref.borrow_mut();
+            // Current dir changed while indexing, there is another run.
+            if finder.indexing.as_ref() == Some(&dir) {
+                finder.indexing = None;
+                finder.files = files.take().unwrap();
+                if finder.popup.is_visible() {
+                    finder.filter();
+                }
+            }
+            Continue(false)
+        });
+    });
+}
+
+/// Files below `dir`, relative to it, hidden and ignored ones are skipped
+fn index_files(dir: &Path, ignore_patterns: &[String]) -> Vec<String> {
+    let ignore = IgnoreMatcher::new(dir, ignore_patterns);
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+
+    while let Some(current) = dirs.pop() {
+        let entries = match fs::read_dir(&current) {
+            Ok(entries) => entries,
+            Err(err) => {
+                warn!("Can't read directory {}: {}", current.display(), err);
+                continue;
+            }
+        };
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            let hidden = entry
+                .file_name()
+                .to_str()
+                .map_or(true, |name| name.starts_with('.') || name.ends_with('~'));
+            // Symlinks to directories are not followed, they might form a loop.
+            let is_dir = match entry.file_type() {
+                Ok(file_type) if file_type.is_symlink() => {
+                    if !path.is_file() {
+                        continue;
+                    }
+                    false
+                }
+                Ok(file_type) => file_type.is_dir(),
+                Err(_) => continue,
+            };
+            if hidden || ignore.is_ignored(&path, is_dir) {
+                continue;
+            }
+
+            if is_dir {
+                dirs.push(path);
+            } else if let Some(rel_path) = relative_path(dir, &path) {
+                files.push(rel_path);
+                if files.len() >= MAX_FILES {
+                    warn!("Too many files in {}, index is incomplete", dir.display());
+                    return files;
+                }
+            }
+        }
+    }
+    files
+}
+
+fn relative_path(dir: &Path, path: &Path) -> Option<String> {
+    path.strip_prefix(dir)
+        .ok()
+        .and_then(Path::to_str)
+        .map(str::to_owned)
+}
+
+/// Markup of text with characters at `positions` in bold
+fn highlight_markup(text: &str, positions: &[usize]) -> String {
+    let mut markup = String::new();
+    let mut positions = positions.iter().peekable();
+    let mut bold = false;
+    for (idx, ch) in text.chars().enumerate() {
+        let matched = positions.peek() == Some(&&idx);
+        if matched {
+            positions.next();
+        }
+        if matched != bold {
+            markup.push_str(if matched { "<b>" } else { "</b>" });
+            bold = matched;
+        }
+        markup.push_str(&encode_minimal(&ch.to_string()));
+    }
+    if bold {
+        markup.push_str("</b>");
+    }
+    markup
+}
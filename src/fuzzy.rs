@@ -0,0 +1,197 @@
+//! Fuzzy matching of file paths
+//!
+//! Pattern characters must appear in the candidate in order. Among all such alignments the one
+//! with the best score is chosen: matches at the start of path segments and words, consecutive
+//! matches and matches in the file name are preferred, gaps are penalized. Matching ignores case
+//! unless the pattern contains uppercase characters.
+
+const BONUS_SEGMENT: i32 = 10;
+const BONUS_WORD: i32 = 8;
+const BONUS_CAMEL: i32 = 7;
+const BONUS_CONSECUTIVE: i32 = 5;
+const BONUS_FILENAME: i32 = 2;
+const PENALTY_GAP: i32 = 1;
+
+#[derive(Debug, PartialEq)]
+pub struct Match {
+    pub score: i32,
+    /// Character indices of matched characters in candidate
+    pub positions: Vec<usize>,
+}
+
+/// Search pattern, folded once and matched against many candidates
+pub struct Pattern {
+    chars: Vec<char>,
+    ignore_case: bool,
+}
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Self {
+        let ignore_case = !pattern.chars().any(char::is_uppercase);
+        Pattern {
+            chars: pattern.chars().map(|ch| fold(ch, ignore_case)).collect(),
+            ignore_case,
+        }
+    }
+
+    /// Checks that all characters of pattern appear in candidate in order, without allocation
+    pub fn is_match(&self, candidate: &str) -> bool {
+        let mut chars = candidate.chars().map(|ch| fold(ch, self.ignore_case));
+        self.chars.iter().all(|&ch| chars.any(|c| c == ch))
+    }
+
+    /// Best match in `candidate`, `None` if not all characters of pattern are found
+    ///
+    /// Most candidates do not match, so they are rejected by `is_match` before scoring.
+    pub fn best_match(&self, candidate: &str) -> Option<Match> {
+        if self.chars.is_empty() {
+            return Some(Match {
+                score: 0,
+                positions: Vec::new(),
+            });
+        }
+        if !self.is_match(candidate) {
+            return None;
+        }
+
+        let pattern = &self.chars;
+        let chars: Vec<char> = candidate.chars().collect();
+        let folded: Vec<char> = chars.iter().map(|&ch| fold(ch, self.ignore_case)).collect();
+
+        let filename_start = chars
+            .iter()
+            .rposition(|&ch| ch == '/')
+            .map_or(0, |pos| pos + 1);
+        let bonus: Vec<i32> = (0..chars.len())
+            .map(|idx| {
+                let filename_bonus = if idx >= filename_start {
+                    BONUS_FILENAME
+                } else {
+                    0
+                };
+                position_bonus(&chars, idx) + filename_bonus
+            })
+            .collect();
+
+        // scores[i][j]: best score of pattern[..=i] with pattern[i] matched at candidate[j],
+        // prev[i][j]: position of pattern[i - 1] in that alignment.
+        let len = chars.len();
+        let mut scores = vec![vec![None; len]; pattern.len()];
+        let mut prev = vec![vec![0; len]; pattern.len()];
+        for (i, &pattern_ch) in pattern.iter().enumerate() {
+            // Best `scores[i - 1][k] + k * PENALTY_GAP` for k < j - 1, the gap to j is
+            // subtracted later.
+            let mut best_gap: Option<(i32, usize)> = None;
+            for j in 0..len {
+                if i > 0 && j >= 2 {
+                    if let Some(score) = scores[i - 1][j - 2] {
+                        let score = score + (j - 2) as i32 * PENALTY_GAP;
+                        if best_gap.map_or(true, |(best, _)| score > best) {
+                            best_gap = Some((score, j - 2));
+                        }
+                    }
+                }
+                if folded[j] != pattern_ch {
+                    continue;
+                }
+
+                if i == 0 {
+                    scores[i][j] = Some(bonus[j]);
+                    continue;
+                }
+                let consecutive = if j >= 1 {
+                    scores[i - 1][j - 1].map(|score| (score + BONUS_CONSECUTIVE, j - 1))
+                } else {
+                    None
+                };
+                let gap = best_gap.map(|(score, k)| (score - (j - 1) as i32 * PENALTY_GAP, k));
+                let best = match (consecutive, gap) {
+                    (Some(c), Some(g)) if g.0 > c.0 => Some(g),
+                    (Some(c), _) => Some(c),
+                    (None, g) => g,
+                };
+                if let Some((score, k)) = best {
+                    scores[i][j] = Some(score + bonus[j]);
+                    prev[i][j] = k;
+                }
+            }
+        }
+
+        let last = pattern.len() - 1;
+        let (mut pos, score) = scores[last]
+            .iter()
+            .enumerate()
+            .filter_map(|(j, score)| score.map(|score| (j, score)))
+            // On equal scores prefer the last position, it is more likely in the file name.
+            .max_by_key(|&(j, score)| (score, j))?;
+
+        let mut positions = vec![pos; pattern.len()];
+        for i in (1..pattern.len()).rev() {
+            pos = prev[i][pos];
+            positions[i - 1] = pos;
+        }
+        Some(Match { score, positions })
+    }
+}
+
+fn fold(ch: char, ignore_case: bool) -> char {
+    if ignore_case {
+        ch.to_lowercase().next().unwrap_or(ch)
+    } else {
+        ch
+    }
+}
+
+fn position_bonus(chars: &[char], idx: usize) -> i32 {
+    let prev = match idx.checked_sub(1) {
+        Some(prev) => chars[prev],
+        None => return BONUS_SEGMENT,
+    };
+    match prev {
+        '/' => BONUS_SEGMENT,
+        '_' | '-' | '.' | ' ' => BONUS_WORD,
+        _ if prev.is_lowercase() && chars[idx].is_uppercase() => BONUS_CAMEL,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(pattern: &str, candidate: &str) -> Option<Vec<usize>> {
+        Pattern::new(pattern)
+            .best_match(candidate)
+            .map(|m| m.positions)
+    }
+
+    fn score(pattern: &str, candidate: &str) -> i32 {
+        Pattern::new(pattern).best_match(candidate).unwrap().score
+    }
+
+    #[test]
+    fn test_is_match() {
+        let pattern = Pattern::new("fb");
+        assert!(pattern.is_match("src/file_browser.rs"));
+        assert!(pattern.is_match("src/FileBrowser.rs"));
+        assert!(!pattern.is_match("src/browser_file.rs"));
+        assert!(!Pattern::new("Fb").is_match("src/file_browser.rs"));
+        assert!(Pattern::new("").is_match(""));
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert_eq!(None, positions("abc", "acb"));
+        assert_eq!(Some(vec![]), positions("", "src/main.rs"));
+        assert_eq!(Some(vec![4, 5, 6, 7]), positions("main", "src/main.rs"));
+        // start of path segments and words
+        assert_eq!(Some(vec![0, 4]), positions("sm", "src/main.rs"));
+        assert_eq!(Some(vec![4, 9]), positions("fb", "src/file_browser.rs"));
+        // smart case
+        assert_eq!(None, positions("Main", "src/main.rs"));
+        assert_eq!(Some(vec![0, 1]), positions("rE", "rEadme"));
+
+        assert!(score("main", "src/main.rs") > score("main", "src/mainwindow/lib.rs"));
+        assert!(score("ui", "src/ui.rs") > score("ui", "src/value/util.rs"));
+    }
+}
@@ -0,0 +1,163 @@
+//! Status of files in git work tree
+//!
+//! Parsed from `git status --porcelain=v2 -z --ignored`. Directories get the most important
+//! status of their content, except for ignored files.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FileStatus {
+    Ignored,
+    Untracked,
+    Added,
+    Modified,
+    Conflicted,
+}
+
+pub struct GitStatus {
+    root: PathBuf,
+    files: HashMap<PathBuf, FileStatus>,
+    dirs: HashMap<PathBuf, FileStatus>,
+}
+
+impl GitStatus {
+    /// Status of the work tree containing `dir`, `None` if it is not in a work tree
+    pub fn load(dir: &Path) -> Option<GitStatus> {
+        // Root is derived from `dir`, so paths are spelled same way, even with symlinks.
+        let prefix = git_output(dir, &["rev-parse", "--show-prefix"])?;
+        let prefix = String::from_utf8_lossy(&prefix);
+        let mut root = dir.to_path_buf();
+        for _ in Path::new(prefix.trim_end()).components() {
+            root.pop();
+        }
+
+        let output = git_output(&root, &["status", "--porcelain=v2", "-z", "--ignored"])?;
+        Some(GitStatus::parse(root, &output))
+    }
+
+    fn parse(root: PathBuf, output: &[u8]) -> GitStatus {
+        let mut git_status = GitStatus {
+            root,
+            files: HashMap::new(),
+            dirs: HashMap::new(),
+        };
+
+        let mut records = output
+            .split(|&b| b == 0)
+            .map(|record| String::from_utf8_lossy(record));
+        while let Some(record) = records.next() {
+            let (status, path) = match record.chars().next() {
+                Some('1') => (parse_xy(&record), record.splitn(9, ' ').nth(8)),
+                Some('2') => {
+                    // Original path of rename or copy is in the next record.
+                    records.next();
+                    (parse_xy(&record), record.splitn(10, ' ').nth(9))
+                }
+                Some('u') => (FileStatus::Conflicted, record.splitn(11, ' ').nth(10)),
+                Some('?') => (FileStatus::Untracked, record.get(2..)),
+                Some('!') => (FileStatus::Ignored, record.get(2..)),
+                _ => continue,
+            };
+            if let Some(path) = path {
+                git_status.insert(path.trim_end_matches('/'), status);
+            }
+        }
+
+        git_status
+    }
+
+    fn insert(&mut self, rel_path: &str, status: FileStatus) {
+        let path = self.root.join(rel_path);
+        if status != FileStatus::Ignored {
+            for dir in path.ancestors().skip(1) {
+                if !dir.starts_with(&self.root) || dir == self.root {
+                    break;
+                }
+                let dir_status = self.dirs.entry(dir.to_path_buf()).or_insert(status);
+                *dir_status = status.max(*dir_status);
+            }
+        }
+        self.files.insert(path, status);
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn get(&self, path: &Path) -> Option<FileStatus> {
+        self.files
+            .get(path)
+            .or_else(|| self.dirs.get(path))
+            // Content of untracked and ignored directories is reported as the directory only.
+            .or_else(|| path.ancestors().skip(1).find_map(|dir| self.files.get(dir)))
+            .copied()
+    }
+}
+
+/// Status of changed entry, from index (X) and work tree (Y) state
+fn parse_xy(record: &str) -> FileStatus {
+    let xy = record.get(2..4).unwrap_or("");
+    if xy.contains('A') || xy.starts_with('R') || xy.starts_with('C') {
+        FileStatus::Added
+    } else {
+        FileStatus::Modified
+    }
+}
+
+fn git_output(dir: &Path, args: &[&str]) -> Option<Vec<u8>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output();
+
+    match output {
+        Ok(output) if output.status.success() => Some(output.stdout),
+        // Not a work tree.
+        Ok(_) => None,
+        Err(err) => {
+            error!("Can't run git: {}", err);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let output = [
+            "1 .M N... 100644 100644 100644 1111 1111 src/main.rs",
+            "1 A. N... 000000 100644 100644 0000 2222 src/ui/new file.rs",
+            "2 R. N... 100644 100644 100644 3333 3333 R100 doc/new.txt",
+            "doc/old.txt",
+            "u UU N... 100644 100644 100644 100644 4444 5555 6666 Cargo.lock",
+            "? notes/",
+            "! target/",
+            "",
+        ]
+        .join("\0");
+        let git_status = GitStatus::parse(PathBuf::from("/repo"), output.as_bytes());
+        let get = |path: &str| git_status.get(Path::new(path));
+
+        assert_eq!(Some(FileStatus::Modified), get("/repo/src/main.rs"));
+        assert_eq!(Some(FileStatus::Added), get("/repo/src/ui/new file.rs"));
+        assert_eq!(Some(FileStatus::Added), get("/repo/doc/new.txt"));
+        assert_eq!(None, get("/repo/doc/old.txt"));
+        assert_eq!(Some(FileStatus::Conflicted), get("/repo/Cargo.lock"));
+        assert_eq!(Some(FileStatus::Untracked), get("/repo/notes/todo.md"));
+        assert_eq!(Some(FileStatus::Ignored), get("/repo/target/debug"));
+        assert_eq!(None, get("/repo/README.md"));
+
+        // directories aggregate content
+        assert_eq!(Some(FileStatus::Modified), get("/repo/src"));
+        assert_eq!(Some(FileStatus::Added), get("/repo/src/ui"));
+        assert_eq!(None, get("/repo"));
+    }
+}
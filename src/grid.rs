@@ -1,8 +1,10 @@
+use std::cmp::{max, min};
 use std::ops::{Index, IndexMut};
 use std::rc::Rc;
 
 use fnv::FnvHashMap;
 
+use neovim_lib::neovim_api::Window;
 use neovim_lib::Value;
 
 use crate::highlight::{Highlight, HighlightMap};
@@ -10,8 +12,16 @@ use crate::ui_model::{ModelRect, ModelRectVec, UiModel};
 
 const DEFAULT_GRID: u64 = 1;
 
+/// zindex used by neovim for message grid
+const MESSAGE_GRID_ZINDEX: u64 = 200;
+
+/// Max depth of float anchor chain, protects from anchor loops
+const MAX_ANCHOR_DEPTH: usize = 16;
+
 pub struct GridMap {
     grids: FnvHashMap<u64, Grid>,
+    cursor_grid: u64,
+    order: u64,
 }
 
 impl Index<u64> for GridMap {
@@ -32,19 +42,18 @@ impl GridMap {
     pub fn new() -> Self {
         GridMap {
             grids: FnvHashMap::default(),
+            cursor_grid: DEFAULT_GRID,
+            order: 0,
         }
     }
 
-    pub fn current(&self) -> Option<&Grid> {
-        self.grids.get(&DEFAULT_GRID)
-    }
-
-    pub fn current_model_mut(&mut self) -> Option<&mut UiModel> {
-        self.grids.get_mut(&DEFAULT_GRID).map(|g| &mut g.model)
-    }
-
-    pub fn current_model(&self) -> Option<&UiModel> {
-        self.grids.get(&DEFAULT_GRID).map(|g| &g.model)
+    /// Grid that currently holds cursor
+    pub fn current_idx(&self) -> u64 {
+        if self.grids.contains_key(&self.cursor_grid) {
+            self.cursor_grid
+        } else {
+            DEFAULT_GRID
+        }
     }
 
     pub fn get_or_create(&mut self, idx: u64) -> &mut Grid {
@@ -52,7 +61,11 @@ impl GridMap {
             return self.grids.get_mut(&idx).unwrap();
         }
 
-        self.grids.insert(idx, Grid::new());
+        let mut grid = Grid::new();
+        if idx == DEFAULT_GRID {
+            grid.placement = Placement::Default;
+        }
+        self.grids.insert(idx, grid);
         self.grids.get_mut(&idx).unwrap()
     }
 
@@ -65,21 +78,363 @@ impl GridMap {
             grid.model.clear_glyphs();
         }
     }
+
+    pub fn models_mut(&mut self) -> impl Iterator<Item = (u64, &mut UiModel)> {
+        self.grids.iter_mut().map(|(&idx, g)| (idx, &mut g.model))
+    }
+
+    pub fn win_pos(&mut self, idx: u64, row: u64, col: u64) {
+        let order = self.next_order();
+        let grid = self.get_or_create(idx);
+        grid.placement = Placement::Window {
+            row: row as usize,
+            col: col as usize,
+        };
+        grid.hidden = false;
+        grid.order = order;
+    }
+
+    pub fn win_float_pos(&mut self, idx: u64, pos: FloatPos) {
+        let order = self.next_order();
+        let grid = self.get_or_create(idx);
+        grid.placement = Placement::Float(pos);
+        grid.hidden = false;
+        grid.order = order;
+    }
+
+    pub fn msg_set_pos(&mut self, idx: u64, row: u64) {
+        let order = self.next_order();
+        let grid = self.get_or_create(idx);
+        grid.placement = Placement::Message { row: row as usize };
+        grid.hidden = false;
+        grid.order = order;
+    }
+
+    pub fn hide(&mut self, idx: u64) {
+        if let Some(grid) = self.grids.get_mut(&idx) {
+            grid.hidden = true;
+        }
+    }
+
+    fn next_order(&mut self) -> u64 {
+        self.order += 1;
+        self.order
+    }
+
+    /// Visible grids, ordered from bottom to top
+    pub fn visible(&self) -> Vec<u64> {
+        let mut visible: Vec<_> = self
+            .grids
+            .iter()
+            .filter(|(_, grid)| grid.is_visible())
+            .map(|(&idx, grid)| (grid.z_order(), idx))
+            .collect();
+        visible.sort();
+        visible.into_iter().map(|(_, idx)| idx).collect()
+    }
+
+    /// Grids of given nvim windows including hidden ones, ordered from bottom to top
+    ///
+    /// Floats are skipped as their position depends on grids of current tabpage
+    pub fn window_grids(&self, windows: &[Window]) -> Vec<u64> {
+        let mut grids: Vec<_> = self
+            .grids
+            .iter()
+            .filter(|(_, grid)| match (&grid.placement, &grid.window) {
+                (Placement::Window { .. }, Some(window)) => windows.contains(window),
+                _ => false,
+            })
+            .map(|(&idx, grid)| (grid.order, idx))
+            .collect();
+        grids.sort();
+        grids.into_iter().map(|(_, idx)| idx).collect()
+    }
+
+    /// Copy of models of window grids that show given windows with their (row, col) position
+    pub fn window_models(&self, windows: &[Window]) -> Vec<(usize, usize, UiModel)> {
+        self.window_grids(windows)
+            .into_iter()
+            .filter_map(|idx| {
+                let (row, col) = self.position(idx)?;
+                Some((row, col, self[idx].model.clone()))
+            })
+            .collect()
+    }
+
+    /// Position of top left corner of grid in screen cells as (row, col)
+    pub fn position(&self, idx: u64) -> Option<(usize, usize)> {
+        self.position_depth(idx, 0)
+    }
+
+    fn position_depth(&self, idx: u64, depth: usize) -> Option<(usize, usize)> {
+        let grid = self.grids.get(&idx)?;
+
+        match grid.placement {
+            Placement::None => None,
+            Placement::Default => Some((0, 0)),
+            Placement::Window { row, col } => Some((row, col)),
+            Placement::Message { row } => Some((row, 0)),
+            Placement::Float(ref pos) => {
+                if depth > MAX_ANCHOR_DEPTH {
+                    return None;
+                }
+
+                let (anchor_row, anchor_col) = self
+                    .position_depth(pos.anchor_grid, depth + 1)
+                    .unwrap_or((0, 0));
+
+                let mut row = anchor_row as f64 + pos.anchor_row;
+                let mut col = anchor_col as f64 + pos.anchor_col;
+
+                match pos.anchor {
+                    FloatAnchor::NW => (),
+                    FloatAnchor::NE => col -= grid.model.columns as f64,
+                    FloatAnchor::SW => row -= grid.model.rows as f64,
+                    FloatAnchor::SE => {
+                        row -= grid.model.rows as f64;
+                        col -= grid.model.columns as f64;
+                    }
+                }
+
+                Some((row.max(0.0) as usize, col.max(0.0) as usize))
+            }
+        }
+    }
+
+    /// Translate grid local rect to screen coordinates
+    pub fn to_screen(&self, idx: u64, rect: &ModelRect) -> Option<ModelRect> {
+        if !self.grids.get(&idx)?.is_visible() {
+            return None;
+        }
+
+        let (row, col) = self.position(idx)?;
+        Some(ModelRect::new(
+            rect.top + row,
+            rect.bot + row,
+            rect.left + col,
+            rect.right + col,
+        ))
+    }
+
+    /// Part of screen rect that is covered by given grid, in grid local coordinates
+    pub fn to_grid(&self, idx: u64, rect: &ModelRect) -> Option<ModelRect> {
+        let grid = self.grids.get(&idx)?;
+        let (row, col) = self.position(idx)?;
+
+        if grid.model.rows == 0 || grid.model.columns == 0 {
+            return None;
+        }
+
+        let top = max(rect.top, row);
+        let bot = min(rect.bot, row + grid.model.rows - 1);
+        let left = max(rect.left, col);
+        let right = min(rect.right, col + grid.model.columns - 1);
+
+        if top > bot || left > right {
+            return None;
+        }
+
+        Some(ModelRect::new(top - row, bot - row, left - col, right - col))
+    }
+
+    /// Topmost grid that accepts mouse input at given screen position,
+    /// returns grid id and grid local (row, col)
+    pub fn grid_at(&self, row: usize, col: usize) -> Option<(u64, usize, usize)> {
+        for idx in self.visible().into_iter().rev() {
+            let grid = &self.grids[&idx];
+            if !grid.is_focusable() {
+                continue;
+            }
+
+            if let Some(local) = self.to_grid(idx, &ModelRect::point(col, row)) {
+                return Some((idx, local.top, local.left));
+            }
+        }
+
+        None
+    }
+
+    /// Screen position in local coordinates of given grid, clamped to its size
+    ///
+    /// Used for drag and release events, they go to the grid where button was pressed.
+    pub fn grid_pos_clamped(&self, idx: u64, row: usize, col: usize) -> Option<(usize, usize)> {
+        let grid = self.grids.get(&idx)?;
+        if !grid.is_visible() {
+            return None;
+        }
+
+        let (grid_row, grid_col) = self.position(idx)?;
+        Some((
+            min(row.saturating_sub(grid_row), grid.model.rows - 1),
+            min(col.saturating_sub(grid_col), grid.model.columns - 1),
+        ))
+    }
+
+    /// Cursor position in screen coordinates
+    pub fn cur_point(&self) -> Option<ModelRect> {
+        let idx = self.current_idx();
+        self.grids
+            .get(&idx)
+            .and_then(|grid| self.to_screen(idx, &grid.cur_point()))
+    }
+
+    /// Whole line with cursor, in screen coordinates
+    pub fn cur_line(&self) -> Option<ModelRect> {
+        let grid = self.grids.get(&self.cursor_grid)?;
+        let (row, _) = grid.model.get_cursor();
+        if grid.model.columns == 0 {
+            return None;
+        }
+
+        self.to_screen(
+            self.cursor_grid,
+            &ModelRect::new(row, row, 0, grid.model.columns - 1),
+        )
+    }
+
+    /// Line with cursor must be shaped again
+    pub fn mark_cur_line_dirty(&mut self) {
+        if let Some(grid) = self.grids.get_mut(&self.cursor_grid) {
+            let (row, _) = grid.model.get_cursor();
+            if let Some(line) = grid.model.model_mut().get_mut(row) {
+                line.dirty_line = true;
+            }
+        }
+    }
+
+    pub fn cursor_goto(&mut self, idx: u64, row: usize, col: usize) -> ModelRectVec {
+        let mut repaint_area = ModelRectVec::empty();
+        if let Some(prev_point) = self.cur_point() {
+            repaint_area.join(&prev_point);
+        }
+
+        self.cursor_grid = idx;
+        for rect in self[idx].cursor_goto(row, col).list {
+            if let Some(rect) = self.to_screen(idx, &rect) {
+                repaint_area.join(&rect);
+            }
+        }
+
+        repaint_area
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FloatAnchor {
+    NW,
+    NE,
+    SW,
+    SE,
+}
+
+impl FloatAnchor {
+    pub fn parse(anchor: &str) -> Result<Self, String> {
+        match anchor {
+            "NW" => Ok(FloatAnchor::NW),
+            "NE" => Ok(FloatAnchor::NE),
+            "SW" => Ok(FloatAnchor::SW),
+            "SE" => Ok(FloatAnchor::SE),
+            _ => Err(format!("Unknown float anchor {}", anchor)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FloatPos {
+    pub anchor: FloatAnchor,
+    pub anchor_grid: u64,
+    pub anchor_row: f64,
+    pub anchor_col: f64,
+    pub focusable: bool,
+    pub zindex: u64,
+}
+
+#[derive(Clone, Debug)]
+enum Placement {
+    None,
+    Default,
+    Window { row: usize, col: usize },
+    Float(FloatPos),
+    Message { row: usize },
+}
+
+/// Part of buffer displayed in window grid, as reported by win_viewport
+#[derive(Clone, Debug, Default)]
+pub struct Viewport {
+    /// First buffer line shown in window
+    pub topline: u64,
 }
 
 pub struct Grid {
     model: UiModel,
+    placement: Placement,
+    hidden: bool,
+    order: u64,
+    viewport: Option<Viewport>,
+    window: Option<Window>,
 }
 
 impl Grid {
     pub fn new() -> Self {
         Grid {
             model: UiModel::empty(),
+            placement: Placement::None,
+            hidden: false,
+            order: 0,
+            viewport: None,
+            window: None,
+        }
+    }
+
+    pub fn model(&self) -> &UiModel {
+        &self.model
+    }
+
+    pub fn model_mut(&mut self) -> &mut UiModel {
+        &mut self.model
+    }
+
+    pub fn is_visible(&self) -> bool {
+        match self.placement {
+            Placement::None => false,
+            _ => !self.hidden && self.model.rows > 0 && self.model.columns > 0,
+        }
+    }
+
+    pub fn is_float(&self) -> bool {
+        match self.placement {
+            Placement::Float(_) => true,
+            _ => false,
+        }
+    }
+
+    fn is_focusable(&self) -> bool {
+        match self.placement {
+            Placement::Float(ref pos) => pos.focusable,
+            _ => true,
+        }
+    }
+
+    fn z_order(&self) -> (u8, u64, u64) {
+        match self.placement {
+            Placement::None | Placement::Default => (0, 0, self.order),
+            Placement::Window { .. } => (1, 0, self.order),
+            Placement::Float(ref pos) => (2, pos.zindex, self.order),
+            Placement::Message { .. } => (2, MESSAGE_GRID_ZINDEX, self.order),
         }
     }
 
-    pub fn get_cursor(&self) -> (usize, usize) {
-        self.model.get_cursor()
+    pub fn viewport(&self) -> Option<&Viewport> {
+        self.viewport.as_ref()
+    }
+
+    pub fn set_viewport(&mut self, viewport: Viewport) {
+        self.viewport = Some(viewport);
+    }
+
+    /// Nvim window shown in this grid
+    pub fn set_window(&mut self, window: Window) {
+        self.window = Some(window);
     }
 
     pub fn cur_point(&self) -> ModelRect {
@@ -149,3 +504,100 @@ impl Grid {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid_map() -> GridMap {
+        let mut grids = GridMap::new();
+        grids.get_or_create(1).resize(80, 24);
+        grids.get_or_create(2).resize(40, 10);
+        grids.win_pos(2, 2, 10);
+        grids.get_or_create(3).resize(20, 5);
+        grids.win_float_pos(
+            3,
+            FloatPos {
+                anchor: FloatAnchor::SE,
+                anchor_grid: 2,
+                anchor_row: 8.0,
+                anchor_col: 30.0,
+                focusable: true,
+                zindex: 50,
+            },
+        );
+        grids
+    }
+
+    #[test]
+    fn test_float_position() {
+        let grids = grid_map();
+
+        assert_eq!(Some((0, 0)), grids.position(1));
+        assert_eq!(Some((2, 10)), grids.position(2));
+        assert_eq!(Some((5, 20)), grids.position(3));
+        assert_eq!(vec![1, 2, 3], grids.visible());
+    }
+
+    #[test]
+    fn test_grid_at() {
+        let mut grids = grid_map();
+
+        assert_eq!(Some((1, 0, 0)), grids.grid_at(0, 0));
+        assert_eq!(Some((2, 1, 1)), grids.grid_at(3, 11));
+        assert_eq!(Some((3, 1, 1)), grids.grid_at(6, 21));
+
+        grids.hide(3);
+        assert_eq!(Some((2, 4, 11)), grids.grid_at(6, 21));
+    }
+
+    #[test]
+    fn test_grid_pos_clamped() {
+        let grids = grid_map();
+
+        assert_eq!(Some((1, 1)), grids.grid_pos_clamped(2, 3, 11));
+        assert_eq!(Some((0, 0)), grids.grid_pos_clamped(2, 0, 0));
+        assert_eq!(Some((9, 39)), grids.grid_pos_clamped(2, 20, 70));
+        assert_eq!(None, grids.grid_pos_clamped(4, 0, 0));
+    }
+
+    #[test]
+    fn test_to_grid() {
+        let grids = grid_map();
+
+        assert_eq!(
+            Some(ModelRect::new(0, 1, 0, 4)),
+            grids.to_grid(2, &ModelRect::new(0, 3, 0, 14))
+        );
+        assert_eq!(None, grids.to_grid(2, &ModelRect::new(0, 1, 0, 14)));
+    }
+
+    #[test]
+    fn test_window_grids() {
+        let mut grids = grid_map();
+        grids.get_or_create(2).set_window(Window::new(Value::from(1000)));
+        grids.get_or_create(3).set_window(Window::new(Value::from(1001)));
+        grids.hide(2);
+
+        assert_eq!(
+            vec![2],
+            grids.window_grids(&[
+                Window::new(Value::from(1000)),
+                Window::new(Value::from(1001))
+            ])
+        );
+        assert!(grids.window_grids(&[Window::new(Value::from(1002))]).is_empty());
+    }
+
+    #[test]
+    fn test_window_models() {
+        let mut grids = grid_map();
+        grids.get_or_create(2).set_window(Window::new(Value::from(1000)));
+
+        let models = grids.window_models(&[Window::new(Value::from(1000))]);
+        assert_eq!(1, models.len());
+        let (row, col, ref model) = models[0];
+        assert_eq!((2, 10), (row, col));
+        assert_eq!((10, 40), (model.rows, model.columns));
+    }
+}
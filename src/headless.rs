@@ -0,0 +1,548 @@
+//! Rendering of nvim redraw events without display
+//!
+//! Recorded `redraw` batches are applied to grids through `redraw_handler::call_grid`,
+//! same as in `shell::State`, and grids are rendered to image surface by `render::render`.
+//! Used by snapshot tests and `--snapshot` command line option, batches are recorded
+//! by running NeovimGtk with `NVIM_GTK_RECORD_REDRAW=<file>` environment variable.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use neovim_lib::neovim_api::Window;
+use neovim_lib::Value;
+use pango::FontDescription;
+
+use crate::cursor::{EmptyCursor, StaticCursor};
+use crate::grid::{FloatPos, GridMap, Viewport};
+use crate::misc::split_at_comma;
+use crate::mode;
+use crate::nvim::{self, GridRedraw, RepaintMode};
+use crate::render;
+use crate::shell::RenderState;
+use crate::value;
+
+const DEFAULT_FONT_NAME: &str = "Monospace 12";
+
+pub struct Headless {
+    grids: GridMap,
+    render_state: RenderState,
+    cursor: StaticCursor,
+}
+
+impl Headless {
+    pub fn new() -> Self {
+        Headless {
+            grids: GridMap::new(),
+            render_state: RenderState::new(create_pango_context(DEFAULT_FONT_NAME)),
+            cursor: StaticCursor::new(),
+        }
+    }
+
+    /// Apply params of one `redraw` notification
+    pub fn redraw(&mut self, params: Vec<Value>) -> Result<(), String> {
+        nvim::each_event(params, |method, args| {
+            if method == "option_set" {
+                self.option_set(&args);
+                Ok(())
+            } else {
+                nvim::call_grid(self, method, args).map(|_| ())
+            }
+        })
+    }
+
+    fn option_set(&mut self, args: &[Value]) {
+        if let (Some("guifont"), Some(fonts)) = (
+            args.first().and_then(Value::as_str),
+            args.get(1).and_then(Value::as_str),
+        ) {
+            let font_ctx = &self.render_state.font_ctx;
+            if let Some(desc) = render::fallback_font_desc(
+                &split_at_comma(fonts),
+                &font_ctx.font_families(),
+                font_ctx.font_description().get_size(),
+            ) {
+                self.set_font_desc(&desc.to_string());
+            }
+        }
+    }
+
+    pub fn set_font_desc(&mut self, desc: &str) {
+        self.render_state
+            .font_ctx
+            .update(create_pango_context(desc));
+        self.grids.clear_glyphs();
+    }
+
+    /// Render visible grids, `None` in case there is nothing to render
+    pub fn render(&mut self) -> Option<cairo::ImageSurface> {
+        let font_ctx = &self.render_state.font_ctx;
+        let hl = &self.render_state.hl;
+
+        let cursor_grid = self.grids.current_idx();
+        for (idx, model) in self.grids.models_mut() {
+            render::shape_dirty(font_ctx, model, idx == cursor_grid);
+        }
+
+        let visible = self.grids.visible();
+        let (columns, rows) = visible
+            .iter()
+            .filter_map(|&idx| {
+                let (row, col) = self.grids.position(idx)?;
+                let model = self.grids[idx].model();
+                Some((col + model.columns, row + model.rows))
+            })
+            .fold((0, 0), |(columns, rows), (grid_columns, grid_rows)| {
+                (columns.max(grid_columns), rows.max(grid_rows))
+            });
+        if columns == 0 || rows == 0 {
+            return None;
+        }
+
+        let cell_metrics = font_ctx.cell_metrics();
+        let surface = cairo::ImageSurface::create(
+            cairo::Format::ARgb32,
+            (columns as f64 * cell_metrics.char_width).ceil() as i32,
+            (rows as f64 * cell_metrics.line_height).ceil() as i32,
+        )
+        .map_err(|err| error!("Can't create snapshot surface {:?}", err))
+        .ok()?;
+
+        let ctx = cairo::Context::new(&surface);
+        render::fill_background(&ctx, hl, None);
+
+        for idx in visible {
+            let model = self.grids[idx].model();
+            let (row, col) = self.grids.position(idx).unwrap_or((0, 0));
+
+            ctx.save();
+            ctx.translate(
+                col as f64 * cell_metrics.char_width,
+                row as f64 * cell_metrics.line_height,
+            );
+            ctx.rectangle(
+                0.0,
+                0.0,
+                model.columns as f64 * cell_metrics.char_width,
+                model.rows as f64 * cell_metrics.line_height,
+            );
+            ctx.clip();
+            render::fill_background(&ctx, hl, None);
+
+            if idx == cursor_grid {
+                render::render(&ctx, &self.cursor, font_ctx, model, hl, None);
+            } else {
+                render::render(&ctx, &EmptyCursor::new(), font_ctx, model, hl, None);
+            }
+            ctx.restore();
+        }
+
+        drop(ctx);
+        surface.flush();
+        Some(surface)
+    }
+}
+
+impl GridRedraw for Headless {
+    fn grid_line(
+        &mut self,
+        grid: u64,
+        row: u64,
+        col_start: u64,
+        cells: Vec<Vec<Value>>,
+    ) -> RepaintMode {
+        let hl = &self.render_state.hl;
+        self.grids[grid].line(row as usize, col_start as usize, cells, hl);
+        RepaintMode::Nothing
+    }
+
+    fn grid_clear(&mut self, grid: u64) -> RepaintMode {
+        self.grids[grid].clear(&self.render_state.hl.default_hl());
+        RepaintMode::Nothing
+    }
+
+    fn grid_destroy(&mut self, grid: u64) -> RepaintMode {
+        self.grids.destroy(grid);
+        RepaintMode::Nothing
+    }
+
+    fn grid_cursor_goto(&mut self, grid: u64, row: u64, column: u64) -> RepaintMode {
+        self.grids.cursor_goto(grid, row as usize, column as usize);
+        RepaintMode::Nothing
+    }
+
+    fn grid_scroll(
+        &mut self,
+        grid: u64,
+        top: u64,
+        bot: u64,
+        left: u64,
+        right: u64,
+        rows: i64,
+        cols: i64,
+    ) -> RepaintMode {
+        let default_hl = self.render_state.hl.default_hl();
+        self.grids[grid].scroll(top, bot, left, right, rows, cols, &default_hl);
+        RepaintMode::Nothing
+    }
+
+    fn grid_resize(&mut self, grid: u64, columns: u64, rows: u64) -> RepaintMode {
+        self.grids.get_or_create(grid).resize(columns, rows);
+        RepaintMode::Nothing
+    }
+
+    fn win_pos(
+        &mut self,
+        grid: u64,
+        win: Value,
+        start_row: u64,
+        start_col: u64,
+        width: u64,
+        height: u64,
+    ) -> RepaintMode {
+        let grid_ref = self.grids.get_or_create(grid);
+        grid_ref.resize(width, height);
+        grid_ref.set_window(Window::new(win));
+        self.grids.win_pos(grid, start_row, start_col);
+        RepaintMode::Nothing
+    }
+
+    fn win_float_pos(&mut self, grid: u64, pos: FloatPos) -> RepaintMode {
+        self.grids.win_float_pos(grid, pos);
+        RepaintMode::Nothing
+    }
+
+    fn win_hide(&mut self, grid: u64) -> RepaintMode {
+        self.grids.hide(grid);
+        RepaintMode::Nothing
+    }
+
+    fn win_close(&mut self, grid: u64) -> RepaintMode {
+        self.grids.hide(grid);
+        RepaintMode::Nothing
+    }
+
+    fn win_viewport(
+        &mut self,
+        grid: u64,
+        _: Value,
+        topline: u64,
+        _: u64,
+        _: u64,
+        _: u64,
+    ) -> RepaintMode {
+        self.grids
+            .get_or_create(grid)
+            .set_viewport(Viewport { topline });
+        RepaintMode::Nothing
+    }
+
+    fn msg_set_pos(&mut self, grid: u64, row: u64) -> RepaintMode {
+        self.grids.msg_set_pos(grid, row);
+        RepaintMode::Nothing
+    }
+
+    fn flush(&mut self) -> RepaintMode {
+        RepaintMode::Nothing
+    }
+
+    fn default_colors_set(
+        &mut self,
+        fg: i64,
+        bg: i64,
+        sp: i64,
+        cterm_fg: i64,
+        cterm_bg: i64,
+    ) -> RepaintMode {
+        self.render_state
+            .hl
+            .set_defaults(fg, bg, sp, cterm_fg, cterm_bg);
+        RepaintMode::Nothing
+    }
+
+    fn hl_attr_define(
+        &mut self,
+        id: u64,
+        rgb_attr: HashMap<String, Value>,
+        _: &Value,
+        info: Vec<HashMap<String, Value>>,
+    ) -> RepaintMode {
+        self.render_state.hl.set(id, &rgb_attr, &info);
+        RepaintMode::Nothing
+    }
+
+    fn on_mode_change(&mut self, mode: String, idx: u64) -> RepaintMode {
+        let render_state = &mut self.render_state;
+        render_state.mode.update(&mode, idx as usize);
+        self.cursor
+            .set_mode_info(render_state.mode.mode_info().cloned());
+        RepaintMode::Nothing
+    }
+
+    fn mode_info_set(
+        &mut self,
+        cursor_style_enabled: bool,
+        mode_infos: Vec<HashMap<String, Value>>,
+    ) -> RepaintMode {
+        match mode_infos.iter().map(mode::ModeInfo::new).collect() {
+            Ok(mode_info_arr) => self
+                .render_state
+                .mode
+                .set_info(cursor_style_enabled, mode_info_arr),
+            Err(err) => error!("Error load mode info: {}", err),
+        }
+        RepaintMode::Nothing
+    }
+}
+
+fn create_pango_context(font_desc: &str) -> pango::Context {
+    let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 1, 1)
+        .expect("Can't create image surface");
+    let ctx = cairo::Context::new(&surface);
+    let pango_context =
+        pangocairo::functions::create_context(&ctx).expect("Can't create pango context");
+    pango_context.set_font_description(&FontDescription::from_string(font_desc));
+    pango_context
+}
+
+/// Read redraw batches recorded by `RedrawRecorder`, one json array per line
+pub fn load_redraw(path: &Path) -> io::Result<Vec<Vec<Value>>> {
+    let file = BufReader::new(File::open(path)?);
+    let mut batches = Vec::new();
+
+    for line in file.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let json: serde_json::Value = serde_json::from_str(&line)?;
+        match value::from_json(&json) {
+            Value::Array(params) => batches.push(params),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Redraw batch must be an array",
+                ))
+            }
+        }
+    }
+
+    Ok(batches)
+}
+
+pub fn write_png(surface: &cairo::ImageSurface, path: &Path) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    surface
+        .write_to_png(&mut file)
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("{:?}", err)))
+}
+
+/// Render recorded redraw batches to png, implementation of `--snapshot` option
+pub fn render_snapshot(redraw_path: &Path, png_path: &Path) -> Result<(), String> {
+    let batches = load_redraw(redraw_path)
+        .map_err(|err| format!("Can't read {}: {}", redraw_path.display(), err))?;
+
+    let mut headless = Headless::new();
+    for batch in batches {
+        headless.redraw(batch)?;
+    }
+
+    let surface = headless
+        .render()
+        .ok_or_else(|| "Nothing to render, grids are empty".to_owned())?;
+    write_png(&surface, png_path)
+        .map_err(|err| format!("Can't write {}: {}", png_path.display(), err))
+}
+
+/// Append `redraw` notifications to file given by `NVIM_GTK_RECORD_REDRAW`
+pub struct RedrawRecorder {
+    out: BufWriter<File>,
+}
+
+impl RedrawRecorder {
+    pub fn from_env() -> Option<Self> {
+        let path = env::var_os("NVIM_GTK_RECORD_REDRAW")?;
+        match OpenOptions::new().create(true).append(true).open(&path) {
+            Ok(file) => Some(RedrawRecorder {
+                out: BufWriter::new(file),
+            }),
+            Err(err) => {
+                error!("Can't open redraw record file: {}", err);
+                None
+            }
+        }
+    }
+
+    pub fn record(&mut self, params: &[Value]) {
+        let json = serde_json::Value::Array(params.iter().map(value::to_json).collect());
+        if let Err(err) = writeln!(self.out, "{}", json).and_then(|_| self.out.flush()) {
+            error!("Can't record redraw event: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Max difference of pixel channel, covers small antialiasing differences
+    const CHANNEL_TOLERANCE: u8 = 8;
+
+    fn snapshots_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("snapshots")
+    }
+
+    /// Pixels of surface in ARGB32 format
+    fn pixels(surface: &cairo::ImageSurface) -> (i32, i32, Vec<u8>) {
+        let (width, height) = (surface.get_width(), surface.get_height());
+        let mut argb = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height).unwrap();
+        {
+            let ctx = cairo::Context::new(&argb);
+            ctx.set_source_surface(surface, 0.0, 0.0);
+            ctx.set_operator(cairo::Operator::Source);
+            ctx.paint();
+        }
+        argb.flush();
+
+        let stride = argb.get_stride() as usize;
+        let data = argb.get_data().unwrap();
+        let pixels = data
+            .chunks(stride)
+            .flat_map(|row| row[..width as usize * 4].iter().cloned())
+            .collect();
+        (width, height, pixels)
+    }
+
+    fn compare(golden: &cairo::ImageSurface, actual: &cairo::ImageSurface) -> Result<(), String> {
+        let (golden_width, golden_height, golden) = pixels(golden);
+        let (width, height, actual) = pixels(actual);
+
+        if (golden_width, golden_height) != (width, height) {
+            return Err(format!(
+                "size {}x{}, expected {}x{}",
+                width, height, golden_width, golden_height
+            ));
+        }
+
+        let diff_count = golden
+            .chunks(4)
+            .zip(actual.chunks(4))
+            .filter(|(golden, actual)| {
+                golden
+                    .iter()
+                    .zip(actual.iter())
+                    .any(|(g, a)| (i16::from(*g) - i16::from(*a)).abs() > CHANNEL_TOLERANCE.into())
+            })
+            .count();
+
+        if diff_count > 0 {
+            Err(format!("{} pixels differ", diff_count))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Render `<name>.redraw` and compare result with golden image `<name>.png`
+    ///
+    /// `NVIM_GTK_UPDATE_SNAPSHOTS=1` writes golden image instead of comparing, missing golden
+    /// image fails the test otherwise. Glyphs depend on installed fonts, so golden images must
+    /// be generated with the same fonts as tests are run.
+    fn assert_snapshot(name: &str) {
+        let dir = snapshots_dir();
+        let golden_path = dir.join(format!("{}.png", name));
+
+        let mut headless = Headless::new();
+        for batch in load_redraw(&dir.join(format!("{}.redraw", name))).unwrap() {
+            headless.redraw(batch).unwrap();
+        }
+        let actual = headless.render().expect("Nothing rendered");
+
+        let update = env::var("NVIM_GTK_UPDATE_SNAPSHOTS")
+            .map(|opt| opt.trim() == "1")
+            .unwrap_or(false);
+        if update {
+            write_png(&actual, &golden_path).unwrap();
+            info!("Golden image {} written", golden_path.display());
+            return;
+        }
+        assert!(
+            golden_path.exists(),
+            "Golden image {} is missing, run with NVIM_GTK_UPDATE_SNAPSHOTS=1 to create it",
+            golden_path.display()
+        );
+
+        let golden =
+            cairo::ImageSurface::create_from_png(&mut File::open(&golden_path).unwrap()).unwrap();
+        if let Err(msg) = compare(&golden, &actual) {
+            let actual_path = dir.join(format!("{}.actual.png", name));
+            write_png(&actual, &actual_path).unwrap();
+            panic!(
+                "Snapshot {} differs from golden image: {}, result saved to {}",
+                name,
+                msg,
+                actual_path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let golden = cairo::ImageSurface::create(cairo::Format::ARgb32, 2, 2).unwrap();
+        let actual = cairo::ImageSurface::create(cairo::Format::Rgb24, 2, 2).unwrap();
+        {
+            let ctx = cairo::Context::new(&golden);
+            ctx.set_source_rgb(0.0, 0.0, 0.0);
+            ctx.paint();
+        }
+        assert_eq!(Ok(()), compare(&golden, &actual));
+
+        {
+            let ctx = cairo::Context::new(&actual);
+            ctx.set_source_rgb(1.0, 0.0, 0.0);
+            ctx.rectangle(0.0, 0.0, 1.0, 1.0);
+            ctx.fill();
+        }
+        assert_eq!(Err("1 pixels differ".to_owned()), compare(&golden, &actual));
+    }
+
+    /// Glyph cache must render same as direct drawing of glyphs
+    #[test]
+    fn test_glyph_cache() {
+        let render = |glyph_cache: bool| {
+            let mut headless = Headless::new();
+            headless
+                .render_state
+                .font_ctx
+                .glyph_cache()
+                .borrow_mut()
+                .set_enabled(glyph_cache);
+            for batch in load_redraw(&snapshots_dir().join("text.redraw")).unwrap() {
+                headless.redraw(batch).unwrap();
+            }
+            let surface = headless.render().expect("Nothing rendered");
+            let cached_glyphs = headless.render_state.font_ctx.glyph_cache().borrow().len();
+            (surface, cached_glyphs)
+        };
+
+        let (direct, _) = render(false);
+        let (cached, cached_glyphs) = render(true);
+        assert!(cached_glyphs > 0);
+        assert_eq!(Ok(()), compare(&direct, &cached));
+    }
+
+    #[test]
+    fn test_snapshot_text() {
+        assert_snapshot("text");
+    }
+
+    #[test]
+    fn test_snapshot_float() {
+        assert_snapshot("float");
+    }
+}
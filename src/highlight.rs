@@ -48,19 +48,21 @@ impl HighlightMap {
         self.default_hl.clone()
     }
 
-    pub fn set_defaults(
-        &mut self,
-        fg: Color,
-        bg: Color,
-        sp: Color,
-        cterm_fg: Color,
-        cterm_bg: Color,
-    ) {
-        self.fg_color = fg;
-        self.bg_color = bg;
-        self.sp_color = sp;
-        self.cterm_fg_color = cterm_fg;
-        self.cterm_bg_color = cterm_bg;
+    /// Set default colors from arguments of `default_colors_set` event
+    pub fn set_defaults(&mut self, fg: i64, bg: i64, sp: i64, cterm_fg: i64, cterm_bg: i64) {
+        self.fg_color = Color::from_indexed_color(fg as u64);
+        self.bg_color = Color::from_indexed_color(bg as u64);
+        self.sp_color = Color::from_indexed_color(sp as u64);
+        self.cterm_fg_color = if cterm_fg > 0 {
+            Color::from_cterm((cterm_fg - 1) as u8)
+        } else {
+            COLOR_WHITE
+        };
+        self.cterm_bg_color = if cterm_bg > 0 {
+            Color::from_cterm((cterm_bg - 1) as u8)
+        } else {
+            COLOR_BLACK
+        };
     }
 
     pub fn set_use_cterm(&mut self, cterm_color: bool) {
@@ -109,14 +111,6 @@ impl HighlightMap {
         self.highlights.insert(idx, hl);
     }
 
-    pub fn cell_fg<'a>(&'a self, cell: &'a Cell) -> Option<&'a Color> {
-        if !cell.hl.reverse {
-            cell.hl.foreground.as_ref()
-        } else {
-            cell.hl.background.as_ref().or_else(|| Some(self.bg()))
-        }
-    }
-
     pub fn actual_cell_fg<'a>(&'a self, cell: &'a Cell) -> &'a Color {
         if !cell.hl.reverse {
             cell.hl.foreground.as_ref().unwrap_or_else(|| self.fg())
@@ -182,12 +176,32 @@ impl HighlightMap {
         }
     }
 
-    pub fn cursor_bg(&self) -> &Color {
-        if !self.cursor.reverse {
-            self.cursor.background.as_ref().unwrap_or_else(|| self.bg())
-        } else {
-            self.cursor.foreground.as_ref().unwrap_or_else(|| self.fg())
-        }
+    /// Cursor `(bg, fg)` colors for highlight `attr_id` of mode info,
+    /// colors not defined by highlight are taken from cell under cursor swapped.
+    /// Without `attr_id` highlight group `Cursor` is used.
+    pub fn cursor_colors(&self, attr_id: Option<u64>, cell: Option<&Cell>) -> (Color, Color) {
+        let cell_fg = cell.map_or_else(|| self.fg(), |cell| self.actual_cell_fg(cell));
+        let cell_bg = cell
+            .and_then(|cell| self.cell_bg(cell))
+            .unwrap_or_else(|| self.bg());
+
+        let cursor_hl = match attr_id {
+            None => Some(&self.cursor),
+            // same as TUI, 0 means colors of cell are swapped
+            Some(0) => None,
+            Some(id) => self.highlights.get(&id),
+        };
+
+        let (bg, fg) = match cursor_hl {
+            Some(hl) if !hl.reverse => (hl.background.as_ref(), hl.foreground.as_ref()),
+            Some(hl) => (hl.foreground.as_ref(), hl.background.as_ref()),
+            None => (None, None),
+        };
+
+        (
+            bg.unwrap_or(cell_fg).clone(),
+            fg.unwrap_or(cell_bg).clone(),
+        )
     }
 }
 
@@ -197,11 +211,18 @@ pub struct Highlight {
     pub bold: bool,
     pub underline: bool,
     pub undercurl: bool,
+    pub underdouble: bool,
+    pub underdotted: bool,
+    pub underdashed: bool,
     pub strikethrough: bool,
     pub foreground: Option<Color>,
     pub background: Option<Color>,
     pub special: Option<Color>,
     pub reverse: bool,
+    /// Blend level of background, 0 to 100
+    pub blend: u64,
+    pub altfont: bool,
+    pub nocombine: bool,
 }
 
 impl Highlight {
@@ -214,8 +235,14 @@ impl Highlight {
             bold: false,
             underline: false,
             undercurl: false,
+            underdouble: false,
+            underdotted: false,
+            underdashed: false,
             strikethrough: false,
             reverse: false,
+            blend: 0,
+            altfont: false,
+            nocombine: false,
         }
     }
 
@@ -244,11 +271,116 @@ impl Highlight {
                 "italic" => model_attrs.italic = true,
                 "underline" => model_attrs.underline = true,
                 "undercurl" => model_attrs.undercurl = true,
+                // nvim 0.7 names: underlineline, underdot, underdash
+                "underdouble" | "underlineline" => model_attrs.underdouble = true,
+                "underdotted" | "underdot" => model_attrs.underdotted = true,
+                "underdashed" | "underdash" => model_attrs.underdashed = true,
                 "strikethrough" => model_attrs.strikethrough = true,
+                "blend" => {
+                    if let Some(blend) = val.as_u64() {
+                        model_attrs.blend = blend.min(100);
+                    }
+                }
+                "altfont" => model_attrs.altfont = true,
+                "nocombine" => model_attrs.nocombine = true,
                 attr_key => error!("unknown attribute {}", attr_key),
             };
         }
 
         model_attrs
     }
+
+    /// Background opacity of blended cell, None in case cell is opaque
+    pub fn blend_alpha(&self) -> Option<f64> {
+        if self.blend > 0 {
+            Some(1.0 - self.blend as f64 / 100.0)
+        } else {
+            None
+        }
+    }
+
+    /// Underline style, nvim allows only one of them, so first set one is returned
+    pub fn underline_style(&self) -> Option<UnderlineStyle> {
+        if self.undercurl {
+            Some(UnderlineStyle::Curl)
+        } else if self.underdouble {
+            Some(UnderlineStyle::Double)
+        } else if self.underdotted {
+            Some(UnderlineStyle::Dotted)
+        } else if self.underdashed {
+            Some(UnderlineStyle::Dashed)
+        } else if self.underline {
+            Some(UnderlineStyle::Single)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnderlineStyle {
+    Single,
+    Curl,
+    Double,
+    Dotted,
+    Dashed,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_underline_style() {
+        let mut attrs = HashMap::new();
+        attrs.insert("underline".to_owned(), Value::from(true));
+        attrs.insert("underdotted".to_owned(), Value::from(true));
+        attrs.insert("blend".to_owned(), Value::from(30));
+
+        let hl = Highlight::from_value_map(&attrs);
+        assert_eq!(Some(UnderlineStyle::Dotted), hl.underline_style());
+        assert_eq!(30, hl.blend);
+
+        let mut attrs = HashMap::new();
+        attrs.insert("underlineline".to_owned(), Value::from(true));
+        let hl = Highlight::from_value_map(&attrs);
+        assert_eq!(Some(UnderlineStyle::Double), hl.underline_style());
+
+        assert_eq!(None, Highlight::new().underline_style());
+    }
+
+    #[test]
+    fn test_blend_alpha() {
+        let mut attrs = HashMap::new();
+        attrs.insert("blend".to_owned(), Value::from(30));
+        let alpha = Highlight::from_value_map(&attrs).blend_alpha().unwrap();
+        assert!((alpha - 0.7).abs() < 1e-9);
+
+        assert_eq!(None, Highlight::new().blend_alpha());
+    }
+
+    #[test]
+    fn test_cursor_colors() {
+        let mut hl_map = HighlightMap::new();
+
+        let mut attrs = HashMap::new();
+        attrs.insert("background".to_owned(), Value::from(0x00ff00));
+        hl_map.set(1, &attrs, &[]);
+
+        let mut cell = Cell::new_empty();
+        let mut cell_attrs = HashMap::new();
+        cell_attrs.insert("foreground".to_owned(), Value::from(0x0000ff));
+        cell.hl = Rc::new(Highlight::from_value_map(&cell_attrs));
+
+        // cursor without highlight swaps cell colors
+        assert_eq!(
+            (Color(0.0, 0.0, 1.0), COLOR_BLACK),
+            hl_map.cursor_colors(Some(0), Some(&cell))
+        );
+        // missing foreground is taken from cell background
+        assert_eq!(
+            (Color(0.0, 1.0, 0.0), COLOR_BLACK),
+            hl_map.cursor_colors(Some(1), Some(&cell))
+        );
+    }
 }
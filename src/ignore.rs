@@ -0,0 +1,295 @@
+//! Matching of files with gitignore style patterns
+//!
+//! Patterns are read from `.gitignore` and `.ignore` of each directory, from `.git/info/exclude`
+//! of the work tree and from user list. Global git excludes are not read.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore"];
+
+pub fn is_ignore_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map_or(false, |name| IGNORE_FILES.contains(&name))
+}
+
+pub struct Pattern {
+    regex: Regex,
+    negated: bool,
+    dir_only: bool,
+    /// Pattern with separator is relative to directory of ignore file,
+    /// otherwise it matches file name at any level
+    anchored: bool,
+}
+
+impl Pattern {
+    /// Parses line of ignore file, `None` for comments and blank lines
+    pub fn parse(line: &str) -> Option<Pattern> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let negated = line.starts_with('!');
+        let line = if negated { &line[1..] } else { line };
+        // `\#` and `\!` are escaped first characters
+        let line = if line.starts_with("\\#") || line.starts_with("\\!") {
+            &line[1..]
+        } else {
+            line
+        };
+        let dir_only = line.ends_with('/');
+        let line = if dir_only {
+            &line[..line.len() - 1]
+        } else {
+            line
+        };
+        let anchored = line.contains('/');
+        let line = if line.starts_with('/') {
+            &line[1..]
+        } else {
+            line
+        };
+        if line.is_empty() {
+            return None;
+        }
+
+        Some(Pattern {
+            regex: Regex::new(&glob_to_regex(line)).ok()?,
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    /// `rel_path` is relative to directory of ignore file
+    fn matches(&self, rel_path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        if self.anchored {
+            self.regex.is_match(rel_path)
+        } else {
+            let name = rel_path.rsplit('/').next().unwrap_or(rel_path);
+            self.regex.is_match(name)
+        }
+    }
+}
+
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    // zero or more directories
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => {
+                let rest: String = chars.clone().collect();
+                match rest.find(']') {
+                    Some(end) if end > 0 => {
+                        let class = &rest[..end];
+                        // skip class and closing bracket
+                        for _ in 0..=class.chars().count() {
+                            chars.next();
+                        }
+                        regex.push('[');
+                        let class = if class.starts_with('!') {
+                            regex.push('^');
+                            &class[1..]
+                        } else {
+                            class
+                        };
+                        for ch in class.chars() {
+                            match ch {
+                                '\\' | '[' | '&' | '~' => regex.push('\\'),
+                                _ => (),
+                            }
+                            regex.push(ch);
+                        }
+                        regex.push(']');
+                    }
+                    _ => regex.push_str("\\["),
+                }
+            }
+            '\\' => {
+                if let Some(ch) = chars.next() {
+                    regex.push_str(&regex::escape(&ch.to_string()));
+                }
+            }
+            _ => regex.push_str(&regex::escape(&ch.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+fn read_patterns(path: &Path) -> Vec<Pattern> {
+    fs::read_to_string(path)
+        .map(|content| content.lines().filter_map(Pattern::parse).collect())
+        .unwrap_or_default()
+}
+
+pub struct IgnoreMatcher {
+    /// Root of git work tree or directory given to `new`, ignore files above are not read
+    root: PathBuf,
+    /// `.git/info/exclude` and user patterns, relative to root
+    root_patterns: Vec<Pattern>,
+    /// Patterns of ignore files by directory, read on demand
+    dir_patterns: RefCell<HashMap<PathBuf, Vec<Pattern>>>,
+}
+
+impl IgnoreMatcher {
+    /// Matcher for entries below `dir`, user patterns are relative to root of its work tree
+    pub fn new(dir: &Path, user_patterns: &[String]) -> Self {
+        let root = dir
+            .ancestors()
+            .find(|dir| dir.join(".git").exists())
+            .unwrap_or(dir)
+            .to_path_buf();
+        let mut root_patterns = read_patterns(&root.join(".git/info/exclude"));
+        root_patterns.extend(user_patterns.iter().filter_map(|line| Pattern::parse(line)));
+
+        IgnoreMatcher {
+            root,
+            root_patterns,
+            dir_patterns: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Ignore files of directory are read again when needed
+    pub fn invalidate(&self, dir: &Path) {
+        self.dir_patterns.borrow_mut().remove(dir);
+    }
+
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let rel_path = match path.strip_prefix(&self.root) {
+            Ok(rel_path) => rel_path,
+            Err(_) => return false,
+        };
+
+        // Content of ignored directory can't be included again, same as in git.
+        let mut path = self.root.clone();
+        let mut components = rel_path.components().peekable();
+        while let Some(component) = components.next() {
+            path.push(component);
+            let is_dir = is_dir || components.peek().is_some();
+            if self.matches(&path, is_dir) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Checks patterns for the path itself, not for its parents
+    fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        if path.file_name().map_or(false, |name| name == ".git") {
+            return true;
+        }
+
+        let mut dir_patterns = self.dir_patterns.borrow_mut();
+        let mut ignored = None;
+        let mut check = |dir: &Path, patterns: &[Pattern]| {
+            let rel_path = path.strip_prefix(dir).ok().and_then(Path::to_str);
+            if let Some(rel_path) = rel_path {
+                // Last matching pattern decides.
+                if let Some(pattern) = patterns.iter().rev().find(|p| p.matches(rel_path, is_dir)) {
+                    ignored = Some(!pattern.negated);
+                }
+            }
+        };
+
+        check(&self.root, &self.root_patterns);
+        // Ignore files of deeper directories take precedence.
+        let dirs: Vec<&Path> = path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(&self.root))
+            .collect();
+        for dir in dirs.into_iter().rev() {
+            let patterns = dir_patterns.entry(dir.to_path_buf()).or_insert_with(|| {
+                IGNORE_FILES
+                    .iter()
+                    .flat_map(|name| read_patterns(&dir.join(name)))
+                    .collect()
+            });
+            check(dir, patterns);
+        }
+
+        ignored == Some(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(patterns: &[&str]) -> IgnoreMatcher {
+        IgnoreMatcher {
+            root: PathBuf::from("/repo"),
+            root_patterns: patterns.iter().filter_map(|p| Pattern::parse(p)).collect(),
+            dir_patterns: RefCell::new(HashMap::new()),
+        }
+    }
+
+    #[test]
+    fn test_glob_to_regex() {
+        assert_eq!("^[^/]*\\.o$", glob_to_regex("*.o"));
+        assert_eq!("^(?:.*/)?build/.*$", glob_to_regex("**/build/**"));
+        assert_eq!("^file[^0-9]$", glob_to_regex("file[!0-9]"));
+        assert_eq!("^\\[a$", glob_to_regex("[a"));
+    }
+
+    #[test]
+    fn test_is_ignored() {
+        let matcher = matcher(&[
+            "# comment",
+            "*.o",
+            "!keep.o",
+            "target/",
+            "/TODO",
+            "doc/**/*.html",
+            "\\#notes",
+            "\\!draft",
+        ]);
+        let is_ignored = |path: &str, is_dir| matcher.is_ignored(Path::new(path), is_dir);
+
+        assert!(is_ignored("/repo/src/main.o", false));
+        assert!(!is_ignored("/repo/src/keep.o", false));
+        assert!(!is_ignored("/repo/src/main.rs", false));
+
+        assert!(is_ignored("/repo/target", true));
+        assert!(is_ignored("/repo/sub/target", true));
+        assert!(!is_ignored("/repo/target", false));
+        // content of ignored directory
+        assert!(is_ignored("/repo/target/debug/main", false));
+
+        assert!(is_ignored("/repo/TODO", false));
+        assert!(!is_ignored("/repo/src/TODO", false));
+
+        assert!(is_ignored("/repo/doc/index.html", false));
+        assert!(is_ignored("/repo/doc/api/index.html", false));
+        assert!(!is_ignored("/repo/index.html", false));
+
+        // escaped first characters
+        assert!(is_ignored("/repo/#notes", false));
+        assert!(is_ignored("/repo/src/!draft", false));
+
+        assert!(is_ignored("/repo/.git", true));
+        assert!(!is_ignored("/other/main.o", false));
+    }
+}
@@ -41,6 +41,19 @@ pub fn keyval_to_input_string(in_str: &str, in_state: gdk::ModifierType) -> Stri
         val = "lt";
     }
 
+    input.push_str(&modifier_to_input_string(state));
+    input.push_str(val);
+
+    if input.chars().count() > 1 {
+        format!("<{}>", input)
+    } else {
+        input
+    }
+}
+
+pub fn modifier_to_input_string(state: gdk::ModifierType) -> String {
+    let mut input = String::new();
+
     if state.contains(gdk::ModifierType::SHIFT_MASK) {
         input.push_str("S-");
     }
@@ -51,13 +64,7 @@ pub fn keyval_to_input_string(in_str: &str, in_state: gdk::ModifierType) -> Stri
         input.push_str("A-");
     }
 
-    input.push_str(val);
-
-    if input.chars().count() > 1 {
-        format!("<{}>", input)
-    } else {
-        input
-    }
+    input
 }
 
 pub fn convert_key(ev: &EventKey) -> Option<String> {
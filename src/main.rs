@@ -22,41 +22,160 @@ mod cmd_line;
 mod cursor;
 mod error;
 mod file_browser;
+mod file_chooser;
+mod file_finder;
+mod fuzzy;
+mod git_status;
 mod grid;
+mod headless;
 mod highlight;
+mod ignore;
 mod input;
+mod messages;
 mod misc;
 mod nvim;
 mod plug_manager;
 mod popup_menu;
 mod project;
 mod render;
+mod session;
 mod settings;
 mod shell;
 mod shell_dlg;
+mod smooth_scroll;
 mod subscriptions;
+mod tab_window;
 mod tabline;
 
 use gio::prelude::*;
+use gtk::prelude::*;
 use std::cell::RefCell;
 use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
 #[cfg(unix)]
 use unix_daemonize::{daemonize_redirect, ChdirMode};
 
 use crate::ui::Ui;
-use crate::shell::ShellOptions;
+use crate::shell::{OpenMode, Shell, ShellOptions};
 
 use clap::{App, Arg, ArgMatches};
 
 include!(concat!(env!("OUT_DIR"), "/version.rs"));
 
+/// Windows opened by this application, used to route files to the focused one
+type Windows = Rc<RefCell<Vec<(gtk::ApplicationWindow, Rc<RefCell<Shell>>)>>>;
+
 fn main() {
     env_logger::init();
 
-    let matches = App::new("NeovimGtk")
+    let about = misc::about_comments();
+    let matches = build_cli(&about).get_matches();
+
+    if let Some(mut paths) = matches.values_of("snapshot") {
+        let redraw_path = paths.next().unwrap();
+        let png_path = paths.next().unwrap();
+        if let Err(err) = headless::render_snapshot(Path::new(redraw_path), Path::new(png_path)) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let single_instance = matches.is_present("single-instance");
+    let input_data = RefCell::new(read_piped_input());
+
+    #[cfg(unix)]
+    {
+        // fork to background by default
+        if !matches.is_present("no-fork") && !matches.is_present("remote-wait") {
+            daemonize_redirect(
+                Some(format!("/tmp/nvim-gtk_stdout.{}.log", whoami::username())),
+                Some(format!("/tmp/nvim-gtk_stderr.{}.log", whoami::username())),
+                ChdirMode::NoChdir,
+            )
+            .unwrap();
+        }
+    }
+
+    let app_flags = if single_instance {
+        gio::ApplicationFlags::HANDLES_COMMAND_LINE
+    } else {
+        gio::ApplicationFlags::HANDLES_OPEN | gio::ApplicationFlags::NON_UNIQUE
+    };
+
+    glib::set_program_name(Some("NeovimGtk"));
+
+    let app = if cfg!(debug_assertions) {
+        gtk::Application::new(Some("org.daa.NeovimGtkDebug"), app_flags)
+    } else {
+        gtk::Application::new(Some("org.daa.NeovimGtk"), app_flags)
+    }
+    .expect("Failed to initialize GTK application");
+
+    let windows = Windows::default();
+
+    if single_instance {
+        let windows_ref = windows.clone();
+        app.connect_command_line(move |app, cmd_line| {
+            command_line(app, cmd_line, &windows_ref, input_data.replace(None))
+        });
+    } else {
+        let matches_copy = matches.clone();
+        let windows_ref = windows.clone();
+        app.connect_activate(move |app| {
+            let input_data = input_data
+                .replace(None)
+                .filter(|_input| !matches_copy.is_present("files"));
+
+            activate(app, &matches_copy, input_data, &windows_ref)
+        });
+
+        let matches_copy = matches.clone();
+        let windows_ref = windows.clone();
+        app.connect_open(move |app, files, _| open(app, files, &matches_copy, &windows_ref));
+    }
+
+    let app_ref = app.clone();
+    let matches_copy = matches.clone();
+    let windows_ref = windows.clone();
+    let new_window_action = gio::SimpleAction::new("new-window", None);
+    new_window_action
+        .connect_activate(move |_, _| activate(&app_ref, &matches_copy, None, &windows_ref));
+    app.add_action(&new_window_action);
+
+    let app_ref = app.clone();
+    let matches_copy = matches.clone();
+    let connect_action = gio::SimpleAction::new("connect", None);
+    connect_action.connect_activate(move |_, _| connect(&app_ref, &matches_copy, &windows));
+    app.add_action(&connect_action);
+
+    gtk::Window::set_default_icon_name("org.daa.NeovimGtk");
+
+    if single_instance {
+        // whole command line is passed to primary instance and parsed there
+        app.run(&std::env::args().collect::<Vec<String>>());
+    } else {
+        let app_exe = std::env::args().next().unwrap_or_else(|| "nvim-gtk".to_owned());
+
+        app.run(
+            &std::iter::once(app_exe)
+                .chain(
+                    matches
+                        .values_of("files")
+                        .unwrap_or_default()
+                        .map(str::to_owned),
+                )
+                .collect::<Vec<String>>(),
+        );
+    }
+}
+
+fn build_cli<'a, 'b>(about: &'b str) -> App<'a, 'b> {
+    App::new("NeovimGtk")
         .version(GIT_BUILD_VERSION.unwrap_or(env!("CARGO_PKG_VERSION")))
         .author(env!("CARGO_PKG_AUTHORS"))
-        .about(misc::about_comments().as_str())
+        .about(about)
         .arg(Arg::with_name("no-fork")
              .long("no-fork")
              .help("Prevent detach from console"))
@@ -71,6 +190,29 @@ fn main() {
         .arg(Arg::with_name("cterm-colors")
              .long("cterm-colors")
              .help("Use ctermfg/ctermbg instead of guifg/guibg"))
+        .arg(Arg::with_name("server")
+             .long("server")
+             .value_name("ADDR")
+             .help("Attach to running nvim instance, ADDR is a path to unix socket or host:port")
+             .conflicts_with("nvim-bin-path")
+             .takes_value(true))
+        .arg(Arg::with_name("single-instance")
+             .long("single-instance")
+             .help("Open files in already running NeovimGtk instead of starting new one"))
+        .arg(Arg::with_name("open-mode")
+             .long("open-mode")
+             .possible_values(&["edit", "tab", "split", "vsplit", "window"])
+             .default_value("tab")
+             .help("How files are opened in running instance")
+             .takes_value(true))
+        .arg(Arg::with_name("remote-wait")
+             .long("remote-wait")
+             .requires("single-instance")
+             .help("Wait until opened buffers are closed, useful for $EDITOR"))
+        .arg(Arg::with_name("snapshot")
+             .long("snapshot")
+             .value_names(&["REDRAW", "PNG"])
+             .help("Render redraw events recorded with NVIM_GTK_RECORD_REDRAW to png and exit"))
         .arg(Arg::with_name("files").help("Files to open").multiple(true))
         .arg(
             Arg::with_name("nvim-bin-path")
@@ -82,86 +224,158 @@ fn main() {
                 .help("Args will be passed to nvim")
                 .last(true)
                 .multiple(true),
-        ).get_matches();
-
-    let input_data = RefCell::new(read_piped_input());
+        )
+}
 
-    #[cfg(unix)]
-    {
-        // fork to background by default
-        if !matches.is_present("no-fork") {
-            daemonize_redirect(
-                Some(format!("/tmp/nvim-gtk_stdout.{}.log", whoami::username())),
-                Some(format!("/tmp/nvim-gtk_stderr.{}.log", whoami::username())),
-                ChdirMode::NoChdir,
-            )
-            .unwrap();
+/// Command line handler of single instance mode
+///
+/// Invoked in primary instance for own command line and for command lines of other instances
+fn command_line(
+    app: &gtk::Application,
+    cmd_line: &gio::ApplicationCommandLine,
+    windows: &Windows,
+    input_data: Option<String>,
+) -> i32 {
+    let about = misc::about_comments();
+    let matches = match build_cli(&about).get_matches_from_safe(cmd_line.get_arguments()) {
+        Ok(matches) => matches,
+        Err(err) => {
+            error!("Can't parse command line: {}", err);
+            return 1;
         }
-    }
+    };
 
-    let app_flags = gio::ApplicationFlags::HANDLES_OPEN | gio::ApplicationFlags::NON_UNIQUE;
+    let cwd = cmd_line.get_cwd();
+    let files: Vec<String> = matches
+        .values_of("files")
+        .unwrap_or_default()
+        .filter_map(|file| absolute_path(cwd.as_ref(), file))
+        .collect();
+    let wait = matches.is_present("remote-wait") && cmd_line.get_is_remote();
 
-    glib::set_program_name(Some("NeovimGtk"));
+    let focused = matches
+        .value_of("open-mode")
+        .and_then(OpenMode::parse)
+        .filter(|_| cmd_line.get_is_remote() && !files.is_empty())
+        .and_then(|mode| Some((mode, focused_window(app, windows)?)));
 
-    let app = if cfg!(debug_assertions) {
-        gtk::Application::new(Some("org.daa.NeovimGtkDebug"), app_flags)
+    if let Some((mode, (window, shell))) = focused {
+        let shell = shell.borrow();
+        let state = shell.state.borrow();
+        for file in &files {
+            state.open_file_in(file, mode, wait);
+            if wait {
+                // remote instance exits once all references to cmd_line are dropped
+                let cmd_line = cmd_line.clone();
+                state.wait_buffer_delete(file, move || drop(cmd_line));
+            }
+        }
+        window.present();
     } else {
-        gtk::Application::new(Some("org.daa.NeovimGtk"), app_flags)
-    }
-    .expect("Failed to initialize GTK application");
+        let input_data = input_data.filter(|_input| files.is_empty());
+        let window = new_window(app, &matches, files, input_data, windows);
 
-    let matches_copy = matches.clone();
-    app.connect_activate(move |app| {
-        let input_data = input_data
-            .replace(None)
-            .filter(|_input| !matches_copy.is_present("files"));
+        if wait {
+            let cmd_line = RefCell::new(Some(cmd_line.clone()));
+            window.connect_destroy(move |_| {
+                cmd_line.replace(None);
+            });
+        }
+    }
 
-        activate(app, &matches_copy, input_data)
-    });
+    0
+}
 
-    let matches_copy = matches.clone();
-    app.connect_open(move |app, files, _| open(app, files, &matches_copy));
+fn absolute_path(cwd: Option<&PathBuf>, file: &str) -> Option<String> {
+    let path = Path::new(file);
+    let path = match cwd {
+        Some(cwd) if path.is_relative() => cwd.join(path),
+        _ => path.to_path_buf(),
+    };
 
-    let app_ref = app.clone();
-    let matches_copy = matches.clone();
-    let new_window_action = gio::SimpleAction::new("new-window", None);
-    new_window_action.connect_activate(move |_, _| activate(&app_ref, &matches_copy, None));
-    app.add_action(&new_window_action);
+    path.components()
+        .filter(|c| *c != Component::CurDir)
+        .collect::<PathBuf>()
+        .to_str()
+        .map(str::to_owned)
+}
 
-    gtk::Window::set_default_icon_name("org.daa.NeovimGtk");
+fn focused_window(
+    app: &gtk::Application,
+    windows: &Windows,
+) -> Option<(gtk::ApplicationWindow, Rc<RefCell<Shell>>)> {
+    let active = app.get_active_window()?;
 
-    let app_exe = std::env::args().next().unwrap_or_else(|| "nvim-gtk".to_owned());
+    windows
+        .borrow()
+        .iter()
+        .find(|(window, _)| *window.upcast_ref::<gtk::Window>() == active)
+        .cloned()
+}
 
-    app.run(
-        &std::iter::once(app_exe)
-            .chain(
-                matches
-                    .values_of("files")
-                    .unwrap_or_default()
-                    .map(str::to_owned),
-            )
-            .collect::<Vec<String>>(),
+fn new_window(
+    app: &gtk::Application,
+    matches: &ArgMatches,
+    files: Vec<String>,
+    input_data: Option<String>,
+    windows: &Windows,
+) -> gtk::ApplicationWindow {
+    let ui = Ui::new(
+        ShellOptions::new(matches, input_data),
+        files.into_boxed_slice(),
     );
+
+    init_window(app, ui, matches, windows)
 }
 
-fn open(app: &gtk::Application, files: &[gio::File], matches: &ArgMatches) {
+fn init_window(
+    app: &gtk::Application,
+    mut ui: Ui,
+    matches: &ArgMatches,
+    windows: &Windows,
+) -> gtk::ApplicationWindow {
+    ui.init(app, !matches.is_present("disable-win-restore"));
+
+    let window = ui.window();
+    windows.borrow_mut().push((window.clone(), ui.shell()));
+
+    let windows = windows.clone();
+    window.connect_destroy(move |window| {
+        windows.borrow_mut().retain(|(w, _)| w != window);
+    });
+
+    window
+}
+
+fn open(app: &gtk::Application, files: &[gio::File], matches: &ArgMatches, windows: &Windows) {
     let files_list: Vec<String> = files
         .iter()
         .filter_map(|f| f.get_path()?.to_str().map(str::to_owned))
         .collect();
 
-    let mut ui = Ui::new(
-        ShellOptions::new(matches, None),
-        files_list.into_boxed_slice(),
-    );
+    new_window(app, matches, files_list, None, windows);
+}
 
-    ui.init(app, !matches.is_present("disable-win-restore"));
+fn activate(
+    app: &gtk::Application,
+    matches: &ArgMatches,
+    input_data: Option<String>,
+    windows: &Windows,
+) {
+    new_window(app, matches, vec![], input_data, windows);
 }
 
-fn activate(app: &gtk::Application, matches: &ArgMatches, input_data: Option<String>) {
-    let mut ui = Ui::new(ShellOptions::new(matches, input_data), Box::new([]));
+fn connect(app: &gtk::Application, matches: &ArgMatches, windows: &Windows) {
+    let address = shell_dlg::show_connect_dlg(app.get_active_window().as_ref());
 
-    ui.init(app, !matches.is_present("disable-win-restore"));
+    if let Some(address) = address {
+        let ui = Ui::new(
+            ShellOptions::new(matches, None).with_server(address),
+            Box::new([]),
+        );
+
+        init_window(app, ui, matches, windows);
+    }
 }
 
 fn read_piped_input() -> Option<String> {
@@ -0,0 +1,355 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use htmlescape::encode_minimal;
+
+use gtk::prelude::*;
+
+use crate::highlight::{HighlightMap, UnderlineStyle};
+
+const MAX_TOASTS: usize = 5;
+const TOAST_TIMEOUT_SEC: u32 = 4;
+const ERROR_TOAST_TIMEOUT_SEC: u32 = 8;
+const HISTORY_HEIGHT: i32 = 250;
+
+/// Widgets for externalized messages (ext_messages)
+///
+/// Transient messages are shown as toasts over drawing area,
+/// mode/showcmd/ruler go to status strip and `:messages` output to history pane
+pub struct Messages {
+    css_provider: gtk::CssProvider,
+
+    toasts: gtk::Box,
+    /// Toast updated by `replace_last` messages, cleared when it times out
+    last_toast: Rc<RefCell<Option<gtk::Label>>>,
+
+    status: gtk::Box,
+    mode: gtk::Label,
+    showcmd: gtk::Label,
+    ruler: gtk::Label,
+
+    history: gtk::Revealer,
+    history_list: gtk::Box,
+}
+
+impl Messages {
+    pub fn new() -> Self {
+        let css_provider = gtk::CssProvider::new();
+
+        let toasts = gtk::Box::new(gtk::Orientation::Vertical, 4);
+        toasts.set_halign(gtk::Align::End);
+        toasts.set_valign(gtk::Align::End);
+        toasts.set_margin_end(10);
+        toasts.set_margin_bottom(10);
+
+        let status = gtk::Box::new(gtk::Orientation::Horizontal, 10);
+        add_css(&status, &css_provider, "nvim-status");
+        let mode = status_label(&css_provider);
+        let showcmd = status_label(&css_provider);
+        let ruler = status_label(&css_provider);
+        status.pack_start(&mode, false, true, 0);
+        status.pack_end(&ruler, false, true, 0);
+        status.pack_end(&showcmd, false, true, 0);
+
+        let history_list = gtk::Box::new(gtk::Orientation::Vertical, 2);
+        let history = create_history_pane(&css_provider, &history_list);
+
+        Messages {
+            css_provider,
+            toasts,
+            last_toast: Rc::new(RefCell::new(None)),
+            status,
+            mode,
+            showcmd,
+            ruler,
+            history,
+            history_list,
+        }
+    }
+
+    /// Widget that must be placed as overlay above drawing area
+    pub fn toasts_widget(&self) -> &gtk::Box {
+        &self.toasts
+    }
+
+    /// Widget that must be placed as overlay above drawing area
+    pub fn history_widget(&self) -> &gtk::Revealer {
+        &self.history
+    }
+
+    /// Widget that must be placed below drawing area
+    pub fn status_widget(&self) -> &gtk::Box {
+        &self.status
+    }
+
+    pub fn update_css(&self, hl: &HighlightMap) {
+        let bg = hl.bg().to_hex();
+        let fg = hl.fg().to_hex();
+
+        if let Err(e) = self.css_provider.load_from_data(
+            format!(
+                ".nvim-message {{ color: {fg}; background-color: {bg}; \
+                 border: 1px solid {border}; border-radius: 4px; padding: 4px 8px; }}\n\
+                 .nvim-status, .nvim-history {{ color: {fg}; background-color: {bg}; }}\n\
+                 .nvim-status label {{ padding: 0px 6px; }}",
+                fg = fg,
+                bg = bg,
+                border = hl.pmenu_bg_sel().to_hex(),
+            )
+            .as_bytes(),
+        ) {
+            error!("Can't update css {}", e)
+        };
+    }
+
+    pub fn show(
+        &mut self,
+        kind: &str,
+        content: &[(u64, String)],
+        replace_last: bool,
+        hl: &HighlightMap,
+    ) {
+        let markup = content_to_markup(content, hl);
+
+        if replace_last {
+            if let Some(ref label) = *self.last_toast.borrow() {
+                label.set_markup(&markup);
+                return;
+            }
+        }
+
+        if markup.is_empty() {
+            return;
+        }
+
+        let children = self.toasts.get_children();
+        if children.len() >= MAX_TOASTS {
+            // prompts are kept until nvim gets the answer
+            let oldest = children
+                .iter()
+                .find(|child| !child.get_style_context().has_class("prompt"));
+            if let Some(oldest) = oldest {
+                oldest.destroy();
+            }
+        }
+
+        let label = gtk::Label::new(None);
+        label.set_markup(&markup);
+        label.set_line_wrap(true);
+        label.set_xalign(0.0);
+        add_css(&label, &self.css_provider, "nvim-message");
+        if is_error_kind(kind) {
+            label.get_style_context().add_class("error");
+        }
+        if is_prompt_kind(kind) {
+            label.get_style_context().add_class("prompt");
+        }
+
+        self.toasts.pack_start(&label, false, true, 0);
+        label.show();
+        *self.last_toast.borrow_mut() = Some(label.clone());
+
+        // Nvim waits for input while prompt is shown, it is removed by msg_clear.
+        if is_prompt_kind(kind) {
+            return;
+        }
+
+        let timeout = if is_error_kind(kind) {
+            ERROR_TOAST_TIMEOUT_SEC
+        } else {
+            TOAST_TIMEOUT_SEC
+        };
+        let last_toast = self.last_toast.clone();
+        gtk::timeout_add_seconds(
+            timeout,
+            clone!(label => move || {
+                let mut last_toast = last_toast.borrow_mut();
+                if last_toast.as_ref() == Some(&label) {
+                    *last_toast = None;
+                }
+                label.destroy();
+                glib::Continue(false)
+            }),
+        );
+    }
+
+    pub fn clear(&mut self) {
+        for toast in self.toasts.get_children() {
+            toast.destroy();
+        }
+        *self.last_toast.borrow_mut() = None;
+        self.history.set_reveal_child(false);
+    }
+
+    pub fn show_mode(&self, content: &[(u64, String)], hl: &HighlightMap) {
+        self.mode.set_markup(&content_to_markup(content, hl));
+    }
+
+    pub fn show_cmd(&self, content: &[(u64, String)], hl: &HighlightMap) {
+        self.showcmd.set_markup(&content_to_markup(content, hl));
+    }
+
+    pub fn ruler(&self, content: &[(u64, String)], hl: &HighlightMap) {
+        self.ruler.set_markup(&content_to_markup(content, hl));
+    }
+
+    pub fn show_history(&self, entries: &[(String, Vec<(u64, String)>)], hl: &HighlightMap) {
+        for child in self.history_list.get_children() {
+            self.history_list.remove(&child);
+        }
+
+        for (_, content) in entries {
+            let label = gtk::Label::new(None);
+            label.set_markup(&content_to_markup(content, hl));
+            label.set_line_wrap(true);
+            label.set_selectable(true);
+            label.set_xalign(0.0);
+            self.history_list.pack_start(&label, false, true, 0);
+        }
+
+        self.history_list.show_all();
+        self.history.set_reveal_child(true);
+    }
+}
+
+fn is_error_kind(kind: &str) -> bool {
+    match kind {
+        "emsg" | "echoerr" | "lua_error" | "rpc_error" => true,
+        _ => false,
+    }
+}
+
+/// Messages shown while nvim waits for an answer, e.g. swap file ATTENTION or `:s///c`
+fn is_prompt_kind(kind: &str) -> bool {
+    match kind {
+        "confirm" | "confirm_sub" | "return_prompt" => true,
+        _ => false,
+    }
+}
+
+fn add_css<W: IsA<gtk::Widget>>(widget: &W, css_provider: &gtk::CssProvider, class: &str) {
+    let style_context = widget.get_style_context();
+    style_context.add_provider(css_provider, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
+    style_context.add_class(class);
+}
+
+fn status_label(css_provider: &gtk::CssProvider) -> gtk::Label {
+    let label = gtk::Label::new(None);
+    label
+        .get_style_context()
+        .add_provider(css_provider, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
+    label.set_single_line_mode(true);
+    label
+}
+
+fn create_history_pane(css_provider: &gtk::CssProvider, history_list: &gtk::Box) -> gtk::Revealer {
+    let pane = gtk::Box::new(gtk::Orientation::Vertical, 0);
+    add_css(&pane, css_provider, "nvim-history");
+
+    let header = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+    let title = gtk::Label::new(Some("Messages"));
+    title.set_margin_start(6);
+    header.pack_start(&title, false, true, 0);
+
+    let close_btn = gtk::Button::new_from_icon_name(Some("window-close-symbolic"), gtk::IconSize::Menu);
+    close_btn.set_relief(gtk::ReliefStyle::None);
+    header.pack_end(&close_btn, false, false, 0);
+    pane.pack_start(&header, false, true, 0);
+
+    let scroll = gtk::ScrolledWindow::new(
+        Option::<&gtk::Adjustment>::None,
+        Option::<&gtk::Adjustment>::None,
+    );
+    scroll.set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
+    scroll.set_min_content_height(HISTORY_HEIGHT);
+    history_list.set_margin_start(6);
+    history_list.set_margin_end(6);
+    scroll.add(history_list);
+    pane.pack_start(&scroll, true, true, 0);
+
+    let history = gtk::Revealer::new();
+    history.set_transition_type(gtk::RevealerTransitionType::SlideUp);
+    history.set_valign(gtk::Align::End);
+    history.add(&pane);
+
+    close_btn.connect_clicked(clone!(history => move |_| history.set_reveal_child(false)));
+
+    history
+}
+
+/// Convert nvim message content chunks to pango markup
+fn content_to_markup(content: &[(u64, String)], hl: &HighlightMap) -> String {
+    content
+        .iter()
+        .map(|&(attr_id, ref text)| {
+            let attr = hl.get(Some(attr_id));
+            let mut span = String::new();
+
+            let (fg, bg) = if attr.reverse {
+                (
+                    Some(attr.background.as_ref().unwrap_or_else(|| hl.bg())),
+                    Some(attr.foreground.as_ref().unwrap_or_else(|| hl.fg())),
+                )
+            } else {
+                (attr.foreground.as_ref(), attr.background.as_ref())
+            };
+
+            if let Some(fg) = fg {
+                span.push_str(&format!(" foreground=\"{}\"", fg.to_hex()));
+            }
+            if let Some(bg) = bg {
+                span.push_str(&format!(" background=\"{}\"", bg.to_hex()));
+            }
+            if attr.bold {
+                span.push_str(" weight=\"bold\"");
+            }
+            if attr.italic {
+                span.push_str(" style=\"italic\"");
+            }
+            match attr.underline_style() {
+                Some(UnderlineStyle::Curl) => span.push_str(" underline=\"error\""),
+                Some(UnderlineStyle::Double) => span.push_str(" underline=\"double\""),
+                Some(_) => span.push_str(" underline=\"single\""),
+                None => (),
+            }
+            if let (Some(_), Some(sp)) = (attr.underline_style(), attr.special.as_ref()) {
+                span.push_str(&format!(" underline_color=\"{}\"", sp.to_hex()));
+            }
+            if attr.strikethrough {
+                span.push_str(" strikethrough=\"true\"");
+            }
+
+            if span.is_empty() {
+                encode_minimal(text)
+            } else {
+                format!("<span{}>{}</span>", span, encode_minimal(text))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use neovim_lib::Value;
+
+    #[test]
+    fn test_content_to_markup() {
+        let mut hl = HighlightMap::new();
+        let mut attrs = HashMap::new();
+        attrs.insert("foreground".to_owned(), Value::from(0xff0000));
+        attrs.insert("bold".to_owned(), Value::from(true));
+        hl.set(1, &attrs, &[]);
+
+        assert_eq!(
+            "E492: <span foreground=\"#FF0000\" weight=\"bold\">&lt;cmd&gt;</span>",
+            content_to_markup(
+                &[(0, "E492: ".to_owned()), (1, "<cmd>".to_owned())],
+                &hl
+            )
+        );
+    }
+}
@@ -82,6 +82,10 @@ pub struct ModeInfo {
     cursor_shape: Option<CursorShape>,
     cell_percentage: Option<u64>,
     pub blinkwait: Option<u32>,
+    pub blinkon: Option<u32>,
+    pub blinkoff: Option<u32>,
+    attr_id: Option<u64>,
+    attr_id_lm: Option<u64>,
 }
 
 impl ModeInfo {
@@ -96,6 +100,10 @@ impl ModeInfo {
             cursor_shape,
             cell_percentage: mode_info_map.get("cell_percentage").and_then(|cp| cp.as_u64()),
             blinkwait: mode_info_map.get("blinkwait").and_then(|cp| cp.as_u64()).map(|v| v as u32),
+            blinkon: mode_info_map.get("blinkon").and_then(|cp| cp.as_u64()).map(|v| v as u32),
+            blinkoff: mode_info_map.get("blinkoff").and_then(|cp| cp.as_u64()).map(|v| v as u32),
+            attr_id: mode_info_map.get("attr_id").and_then(Value::as_u64),
+            attr_id_lm: mode_info_map.get("attr_id_lm").and_then(Value::as_u64),
         })
     }
 
@@ -103,6 +111,20 @@ impl ModeInfo {
         self.cursor_shape.as_ref()
     }
 
+    /// Same as in 'guicursor', when one of blink times is zero there is no blinking
+    pub fn is_blink_disabled(&self) -> bool {
+        self.blinkwait == Some(0) || self.blinkon == Some(0) || self.blinkoff == Some(0)
+    }
+
+    /// Cursor highlight, `lang_mapping` selects highlight used when language mappings are active.
+    /// Zero means no highlight is given, so cursor swaps colors of cell.
+    pub fn attr_id(&self, lang_mapping: bool) -> Option<u64> {
+        match self.attr_id_lm {
+            Some(attr_id_lm) if lang_mapping && attr_id_lm != 0 => Some(attr_id_lm),
+            _ => self.attr_id,
+        }
+    }
+
     pub fn cell_percentage(&self) -> u64 {
         self.cell_percentage.unwrap_or(0)
     }
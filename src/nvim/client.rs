@@ -107,6 +107,7 @@ pub struct NeovimClient {
     state: Cell<NeovimClientState>,
     nvim: RefCell<Option<Neovim>>,
     nvim_async: NeovimClientAsync,
+    channel: Cell<u64>,
 }
 
 impl NeovimClient {
@@ -115,6 +116,7 @@ impl NeovimClient {
             state: Cell::new(NeovimClientState::Uninitialized),
             nvim: RefCell::new(None),
             nvim_async: NeovimClientAsync::new(),
+            channel: Cell::new(0),
         }
     }
 
@@ -138,10 +140,16 @@ impl NeovimClient {
         self.nvim_async.clone()
     }
 
-    pub fn set_initialized(&self) {
+    pub fn set_initialized(&self, channel: u64) {
+        self.channel.set(channel);
         self.state.set(NeovimClientState::Initialized);
     }
 
+    /// Rpc channel id of this client, valid only after initialization
+    pub fn channel(&self) -> u64 {
+        self.channel.get()
+    }
+
     pub fn set_error(&self) {
         self.state.set(NeovimClientState::Error);
     }
@@ -1,6 +1,7 @@
 use std::result;
 
-use neovim_lib::CallError;
+use neovim_lib::neovim::map_generic_error;
+use neovim_lib::{AsyncCall, CallError, Neovim, Value};
 
 pub trait ErrorReport<T> {
     fn report_err(&self);
@@ -20,3 +21,68 @@ impl<T> ErrorReport<T> for result::Result<T, CallError> {
         self.ok()
     }
 }
+
+/// Api calls that are not covered by neovim_lib
+pub trait NeovimApiExt {
+    /// Same as `ui_attach` but accept options that `UiAttachOptions` does not know about
+    fn ui_attach_ext(
+        &mut self,
+        width: i64,
+        height: i64,
+        options: &[(&str, Value)],
+    ) -> result::Result<(), CallError>;
+
+    fn input_mouse_async(
+        &mut self,
+        button: &str,
+        action: &str,
+        modifier: &str,
+        grid: u64,
+        row: u64,
+        col: u64,
+    ) -> AsyncCall<'_, ()>;
+}
+
+impl NeovimApiExt for Neovim {
+    fn ui_attach_ext(
+        &mut self,
+        width: i64,
+        height: i64,
+        options: &[(&str, Value)],
+    ) -> result::Result<(), CallError> {
+        let options = options
+            .iter()
+            .map(|&(name, ref val)| (name.into(), val.clone()))
+            .collect();
+
+        self.session
+            .call(
+                "nvim_ui_attach",
+                vec![width.into(), height.into(), Value::Map(options)],
+            )
+            .map_err(map_generic_error)
+            .map(|_| ())
+    }
+
+    fn input_mouse_async(
+        &mut self,
+        button: &str,
+        action: &str,
+        modifier: &str,
+        grid: u64,
+        row: u64,
+        col: u64,
+    ) -> AsyncCall<'_, ()> {
+        self.session.call_async(
+            "nvim_input_mouse",
+            vec![
+                button.into(),
+                action.into(),
+                modifier.into(),
+                grid.into(),
+                row.into(),
+                col.into(),
+            ],
+        )
+    }
+}
@@ -0,0 +1,290 @@
+//! Versioned api for plugins to control GUI widgets
+//!
+//! Calls are made with `rpcrequest(g:gtk_channel, 'Gui', 'Api', method, args...)`,
+//! or `rpcnotify` in case result is not needed. `rpcrequest(g:gtk_channel, 'Gui', 'ApiInfo')`
+//! returns api version and list of supported methods. See `:help nvim-gtk-api`.
+
+use std::fmt;
+use std::rc::Rc;
+
+use neovim_lib::Value;
+
+/// Incremented on incompatible changes, new methods does not change version
+pub const API_VERSION: u64 = 1;
+
+const METHODS: &[&str] = &[
+    "sidebar_get",
+    "sidebar_set",
+    "file_browser_reveal",
+    "file_browser_ignore_get",
+    "file_browser_ignore_set",
+    "title_set",
+    "fullscreen_get",
+    "fullscreen_set",
+    "notify",
+    "file_chooser",
+];
+
+/// Sends result of call, must be called exactly once
+///
+/// Handlers must not block main loop, calls that wait for user (e.g. file chooser) reply
+/// later from gtk callback.
+pub type GuiApiReply = Box<dyn FnOnce(Result<Value, GuiApiError>)>;
+
+pub type GuiApiCb = Rc<dyn Fn(GuiApiCall, GuiApiReply)>;
+
+#[derive(Debug, PartialEq)]
+pub enum FileChooserAction {
+    Open,
+    Save,
+    SelectFolder,
+}
+
+/// Arguments of file chooser: `[action, title, path, multiple]`, all optional
+#[derive(Debug, PartialEq)]
+pub struct FileChooserArgs {
+    pub action: FileChooserAction,
+    pub title: Option<String>,
+    /// Initial file or folder
+    pub path: Option<String>,
+    /// Allow to select multiple files
+    pub multiple: bool,
+}
+
+impl FileChooserArgs {
+    pub fn parse(args: &[Value]) -> Result<Self, GuiApiError> {
+        let action = match opt_arg_str(args, 0)?.as_ref().map(String::as_str) {
+            None | Some("open") => FileChooserAction::Open,
+            Some("save") => FileChooserAction::Save,
+            Some("select_folder") => FileChooserAction::SelectFolder,
+            Some(action) => {
+                return Err(GuiApiError::InvalidArgument(format!(
+                    "Unknown file chooser action {}",
+                    action
+                )))
+            }
+        };
+
+        Ok(FileChooserArgs {
+            action,
+            title: opt_arg_str(args, 1)?,
+            path: opt_arg_str(args, 2)?,
+            multiple: args.get(3).map_or(Ok(false), |_| arg_bool(args, 3))?,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum GuiApiCall {
+    SidebarGet,
+    SidebarSet(bool),
+    FileBrowserReveal(String),
+    FileBrowserIgnoreGet,
+    /// Patterns in gitignore format, in addition to ignore files
+    FileBrowserIgnoreSet(Vec<String>),
+    TitleSet(String),
+    FullscreenGet,
+    FullscreenSet(bool),
+    Notify {
+        summary: String,
+        body: Option<String>,
+    },
+    FileChooser(FileChooserArgs),
+}
+
+impl GuiApiCall {
+    /// Parse `[method, args...]` arguments of `Api` request or notification
+    pub fn from_args(args: &[Value], is_request: bool) -> Result<Self, GuiApiError> {
+        let (method, args) = args
+            .split_first()
+            .ok_or_else(|| GuiApiError::InvalidArgument("method is missing".to_owned()))?;
+        let method = method
+            .as_str()
+            .ok_or_else(|| GuiApiError::InvalidArgument("method must be a string".to_owned()))?;
+
+        let call = GuiApiCall::parse(method, args)?;
+        if !is_request && call.needs_result() {
+            return Err(GuiApiError::RequestRequired(method.to_owned()));
+        }
+
+        Ok(call)
+    }
+
+    fn parse(method: &str, args: &[Value]) -> Result<Self, GuiApiError> {
+        let call = match method {
+            "sidebar_get" => GuiApiCall::SidebarGet,
+            "sidebar_set" => GuiApiCall::SidebarSet(arg_bool(args, 0)?),
+            "file_browser_reveal" => GuiApiCall::FileBrowserReveal(arg_str(args, 0)?),
+            "file_browser_ignore_get" => GuiApiCall::FileBrowserIgnoreGet,
+            "file_browser_ignore_set" => GuiApiCall::FileBrowserIgnoreSet(arg_str_list(args, 0)?),
+            "title_set" => GuiApiCall::TitleSet(arg_str(args, 0)?),
+            "fullscreen_get" => GuiApiCall::FullscreenGet,
+            "fullscreen_set" => GuiApiCall::FullscreenSet(arg_bool(args, 0)?),
+            "notify" => GuiApiCall::Notify {
+                summary: arg_str(args, 0)?,
+                body: opt_arg_str(args, 1)?,
+            },
+            "file_chooser" => GuiApiCall::FileChooser(FileChooserArgs::parse(args)?),
+            _ => return Err(GuiApiError::UnknownMethod(method.to_owned())),
+        };
+
+        Ok(call)
+    }
+
+    /// Calls that must return result can't be made with notification
+    fn needs_result(&self) -> bool {
+        match *self {
+            GuiApiCall::SidebarGet
+            | GuiApiCall::FileBrowserIgnoreGet
+            | GuiApiCall::FullscreenGet
+            | GuiApiCall::FileChooser(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Result of `ApiInfo` request
+pub fn api_info() -> Value {
+    Value::Map(vec![
+        ("version".into(), API_VERSION.into()),
+        (
+            "methods".into(),
+            Value::Array(METHODS.iter().map(|&m| m.into()).collect()),
+        ),
+    ])
+}
+
+#[derive(Debug, PartialEq)]
+pub enum GuiApiError {
+    UnknownMethod(String),
+    InvalidArgument(String),
+    /// Call can't be executed in current GUI state, e.g. file browser does not contain path
+    Failed(String),
+    /// Call returns result, so must be done with `rpcrequest`
+    RequestRequired(String),
+}
+
+impl GuiApiError {
+    fn code(&self) -> u64 {
+        match *self {
+            GuiApiError::UnknownMethod(_) => 1,
+            GuiApiError::InvalidArgument(_) => 2,
+            GuiApiError::Failed(_) => 3,
+            GuiApiError::RequestRequired(_) => 4,
+        }
+    }
+
+    /// Error in form of `[code, message]`, same as nvim api errors
+    pub fn to_value(&self) -> Value {
+        Value::Array(vec![self.code().into(), self.to_string().into()])
+    }
+}
+
+impl fmt::Display for GuiApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GuiApiError::UnknownMethod(ref method) => write!(f, "Unknown method {}", method),
+            GuiApiError::InvalidArgument(ref msg) => write!(f, "Invalid argument: {}", msg),
+            GuiApiError::Failed(ref msg) => write!(f, "{}", msg),
+            GuiApiError::RequestRequired(ref method) => {
+                write!(f, "Method {} returns result, use rpcrequest", method)
+            }
+        }
+    }
+}
+
+fn arg_str(args: &[Value], idx: usize) -> Result<String, GuiApiError> {
+    opt_arg_str(args, idx)?
+        .ok_or_else(|| GuiApiError::InvalidArgument(format!("argument {} is missing", idx)))
+}
+
+fn opt_arg_str(args: &[Value], idx: usize) -> Result<Option<String>, GuiApiError> {
+    match args.get(idx) {
+        None | Some(Value::Nil) => Ok(None),
+        Some(val) => val.as_str().map(|s| Some(s.to_owned())).ok_or_else(|| {
+            GuiApiError::InvalidArgument(format!("argument {} must be a string", idx))
+        }),
+    }
+}
+
+fn arg_str_list(args: &[Value], idx: usize) -> Result<Vec<String>, GuiApiError> {
+    let err =
+        || GuiApiError::InvalidArgument(format!("argument {} must be a list of strings", idx));
+    match args.get(idx) {
+        Some(Value::Array(list)) => list
+            .iter()
+            .map(|val| val.as_str().map(str::to_owned).ok_or_else(err))
+            .collect(),
+        _ => Err(err()),
+    }
+}
+
+fn arg_bool(args: &[Value], idx: usize) -> Result<bool, GuiApiError> {
+    match args.get(idx) {
+        Some(&Value::Boolean(val)) => Ok(val),
+        // vimscript has no booleans, v:true/v:false are converted, but 0/1 are used often
+        Some(val) if val.as_u64().is_some() => Ok(val.as_u64() != Some(0)),
+        _ => Err(GuiApiError::InvalidArgument(format!(
+            "argument {} must be a boolean",
+            idx
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Ok(GuiApiCall::SidebarSet(true)),
+            GuiApiCall::parse("sidebar_set", &[Value::from(1)])
+        );
+        assert_eq!(
+            Ok(GuiApiCall::FileBrowserIgnoreSet(vec![
+                "*.o".to_owned(),
+                "build/".to_owned()
+            ])),
+            GuiApiCall::parse(
+                "file_browser_ignore_set",
+                &[Value::Array(vec!["*.o".into(), "build/".into()])]
+            )
+        );
+        assert!(GuiApiCall::parse("file_browser_ignore_set", &[Value::from("*.o")]).is_err());
+        assert_eq!(
+            Ok(GuiApiCall::FileChooser(FileChooserArgs {
+                action: FileChooserAction::SelectFolder,
+                title: None,
+                path: Some("/tmp".to_owned()),
+                multiple: false,
+            })),
+            GuiApiCall::parse(
+                "file_chooser",
+                &[Value::from("select_folder"), Value::Nil, Value::from("/tmp")]
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert_eq!(
+            Err(GuiApiError::UnknownMethod("unknown".to_owned())),
+            GuiApiCall::parse("unknown", &[])
+        );
+
+        assert_eq!(
+            Err(GuiApiError::RequestRequired("sidebar_get".to_owned())),
+            GuiApiCall::from_args(&[Value::from("sidebar_get")], false)
+        );
+
+        let err = GuiApiCall::parse("title_set", &[Value::from(1)]).unwrap_err();
+        assert_eq!(
+            Value::Array(vec![
+                Value::from(2),
+                Value::from("Invalid argument: argument 0 must be a string")
+            ]),
+            err.to_value()
+        );
+    }
+}
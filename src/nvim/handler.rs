@@ -1,8 +1,10 @@
+use std::mem;
 use std::result;
 use std::sync::{mpsc, Arc};
 
 use neovim_lib::{Handler, RequestHandler, Value};
 
+use crate::headless::RedrawRecorder;
 use crate::ui::UiMutex;
 use crate::shell;
 use glib;
@@ -12,6 +14,7 @@ use super::redraw_handler;
 
 pub struct NvimHandler {
     shell: Arc<UiMutex<shell::State>>,
+    recorder: Option<RedrawRecorder>,
 
     delayed_redraw_event_id: Arc<UiMutex<Option<glib::SourceId>>>,
 }
@@ -20,6 +23,7 @@ impl NvimHandler {
     pub fn new(shell: Arc<UiMutex<shell::State>>) -> NvimHandler {
         NvimHandler {
             shell,
+            recorder: RedrawRecorder::from_env(),
             delayed_redraw_event_id: Arc::new(UiMutex::new(None)),
         }
     }
@@ -79,7 +83,7 @@ impl NvimHandler {
                                     ui,
                                     ev_name
                                         .as_str()
-                                        .ok_or_else(|| "Event name does not exists")?,
+                                        .ok_or("Event name does not exists")?,
                                     args,
                                 )?;
                                 ui.on_redraw(&RepaintMode::All);
@@ -117,22 +121,26 @@ impl NvimHandler {
                             let args = params_iter.collect();
                             let (sender, receiver) = mpsc::channel();
                             self.safe_call(move |ui| {
-                                sender
-                                    .send(redraw_handler::call_gui_request(
-                                        &ui.clone(),
-                                        req_name
-                                            .as_str()
-                                            .ok_or_else(|| "Event name does not exists")?,
-                                        &args,
-                                    ))
-                                    .unwrap();
+                                redraw_handler::call_gui_request(
+                                    &ui.clone(),
+                                    req_name
+                                        .as_str()
+                                        .ok_or_else(|| "Event name does not exists")?,
+                                    &args,
+                                    Box::new(move |result| sender.send(result).unwrap()),
+                                );
                                 {
                                     let ui = &mut ui.borrow_mut();
                                     ui.on_redraw(&RepaintMode::All);
                                 }
                                 Ok(())
                             });
-                            Ok(receiver.recv().unwrap()?)
+                            // Response is sent by this dispatcher thread, so it waits for the
+                            // reply. Main loop is never blocked, handlers that wait for user
+                            // (e.g. file chooser) reply later from gtk callbacks.
+                            receiver
+                                .recv()
+                                .unwrap_or_else(|_| Err(Value::from("Request is not answered")))
                         } else {
                             error!("Unsupported request");
                             Err(Value::Nil)
@@ -168,33 +176,12 @@ fn call_redraw_handler(
     let ui = &mut ui.borrow_mut();
     let mut repaint_mode = RepaintMode::Nothing;
 
-    for ev in params {
-        if let Value::Array(ev_args) = ev {
-            let mut args_iter = ev_args.into_iter();
-            let ev_name = args_iter.next();
-            if let Some(ev_name) = ev_name {
-                if let Some(ev_name) = ev_name.as_str() {
-                    for local_args in args_iter {
-                        let args = match local_args {
-                            Value::Array(ar) => ar,
-                            _ => vec![],
-                        };
-                        let call_reapint_mode = match redraw_handler::call(ui, &ev_name, args) {
-                            Ok(mode) => mode,
-                            Err(desc) => return Err(format!("Event {}\n{}", ev_name, desc)),
-                        };
-                        repaint_mode = repaint_mode.join(call_reapint_mode);
-                    }
-                } else {
-                    error!("Unsupported event");
-                }
-            } else {
-                error!("Event name does not exists");
-            }
-        } else {
-            error!("Unsupported event type {:?}", ev);
-        }
-    }
+    redraw_handler::each_event(params, |ev_name, args| {
+        let call_reapint_mode = redraw_handler::call(ui, ev_name, args)?;
+        repaint_mode =
+            mem::replace(&mut repaint_mode, RepaintMode::Nothing).join(call_reapint_mode);
+        Ok(())
+    })?;
 
     ui.on_redraw(&repaint_mode);
     Ok(())
@@ -215,6 +202,9 @@ where
 
 impl Handler for NvimHandler {
     fn handle_notify(&mut self, name: &str, args: Vec<Value>) {
+        if let (Some(recorder), "redraw") = (self.recorder.as_mut(), name) {
+            recorder.record(&args);
+        }
         self.nvim_cb(name, args);
     }
 
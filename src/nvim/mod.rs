@@ -3,21 +3,27 @@ mod handler;
 mod redraw_handler;
 mod repaint_mode;
 mod ext;
+mod gui_api;
 
-pub use self::redraw_handler::{CompleteItem, NvimCommand};
+pub use self::redraw_handler::{call_grid, each_event, CompleteItem, GridRedraw, NvimCommand};
 pub use self::repaint_mode::RepaintMode;
 pub use self::client::{NeovimClient, NeovimClientAsync, NeovimRef};
-pub use self::ext::ErrorReport;
+pub use self::ext::{ErrorReport, NeovimApiExt};
 pub use self::handler::NvimHandler;
+pub use self::gui_api::{
+    FileChooserAction, FileChooserArgs, GuiApiCall, GuiApiCb, GuiApiError, GuiApiReply,
+};
 
 use std::error;
 use std::fmt;
 use std::env;
+use std::io;
+use std::path::Path;
 use std::process::{Command, Stdio};
 use std::result;
 use std::time::Duration;
 
-use neovim_lib::{Neovim, NeovimApi, Session, UiAttachOptions};
+use neovim_lib::{Neovim, NeovimApi, Session, Value};
 
 use crate::nvim_config::NvimConfig;
 
@@ -48,6 +54,16 @@ impl NvimInitError {
         }
     }
 
+    pub fn new_connect<E>(address: &str, error: E) -> NvimInitError
+    where
+        E: Into<Box<dyn error::Error>>,
+    {
+        NvimInitError {
+            cmd: Some(format!("connect to {}", address)),
+            source: error.into(),
+        }
+    }
+
     pub fn source(&self) -> String {
         format!("{}", self.source)
     }
@@ -101,15 +117,7 @@ pub fn start(
     #[cfg(target_os = "windows")]
     set_windows_creation_flags(&mut cmd);
 
-    if let Ok(runtime_path) = env::var("NVIM_GTK_RUNTIME_PATH") {
-        cmd.arg("--cmd")
-            .arg(format!("let &rtp.=',{}'", runtime_path));
-    } else if let Some(prefix) = option_env!("PREFIX") {
-        cmd.arg("--cmd")
-            .arg(format!("let &rtp.=',{}/share/nvim-gtk/runtime'", prefix));
-    } else {
-        cmd.arg("--cmd").arg("let &rtp.=',runtime'");
-    }
+    cmd.arg("--cmd").arg(runtime_path_cmd());
 
     if let Some(nvim_config) = NvimConfig::config_path() {
         if let Some(path) = nvim_config.to_str() {
@@ -137,22 +145,96 @@ pub fn start(
     Ok(nvim)
 }
 
+/// Connect to already running nvim instance
+///
+/// Address is path to unix socket or tcp `host:port`
+pub fn connect(
+    handler: NvimHandler,
+    address: &str,
+    timeout: Option<Duration>,
+) -> result::Result<Neovim, NvimInitError> {
+    let session = if is_socket_path(address) {
+        new_unix_socket_session(address)
+    } else {
+        Session::new_tcp(address)
+    };
+
+    let mut session = session.map_err(|e| NvimInitError::new_connect(address, e))?;
+
+    session.set_timeout(timeout.unwrap_or(Duration::from_millis(10_000)));
+
+    let mut nvim = Neovim::new(session);
+
+    nvim.session.start_event_loop_handler(handler);
+
+    // Server might be on another host and is shared with other clients, so its options and
+    // 'runtimepath' are left to user config, GUI shim must be installed there to use commands.
+    nvim.set_var("GtkGuiLoaded", Value::from(1))
+        .map_err(|e| NvimInitError::new_connect(address, e))?;
+
+    Ok(nvim)
+}
+
+/// Command to add GUI runtime files to 'runtimepath'
+fn runtime_path_cmd() -> String {
+    if let Ok(runtime_path) = env::var("NVIM_GTK_RUNTIME_PATH") {
+        format!("let &rtp.=',{}'", runtime_path)
+    } else if let Some(prefix) = option_env!("PREFIX") {
+        format!("let &rtp.=',{}/share/nvim-gtk/runtime'", prefix)
+    } else {
+        "let &rtp.=',runtime'".to_owned()
+    }
+}
+
+fn is_socket_path(address: &str) -> bool {
+    cfg!(unix) && (address.contains('/') || Path::new(address).exists())
+}
+
+#[cfg(unix)]
+fn new_unix_socket_session(address: &str) -> io::Result<Session> {
+    Session::new_unix_socket(address)
+}
+
+#[cfg(not(unix))]
+fn new_unix_socket_session(_: &str) -> io::Result<Session> {
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "Unix sockets are not supported on this platform",
+    ))
+}
+
+/// Attach ui, returns channel id of this client
 pub fn post_start_init(
     nvim: NeovimClientAsync,
     cols: i64,
     rows: i64,
     input_data: Option<String>,
-) -> result::Result<(), NvimInitError> {
+) -> result::Result<u64, NvimInitError> {
+    // publish channel before attach, so shim can reach us from startup scripts
+    let channel = get_channel(&mut nvim.borrow().unwrap())?;
+    nvim.borrow()
+        .unwrap()
+        .set_var("gtk_channel", Value::from(channel))
+        .map_err(NvimInitError::new_post_init)?;
+    // lets shim find another GUI when the one in g:gtk_channel is detached
+    nvim.borrow()
+        .unwrap()
+        .set_client_info("nvim-gtk", vec![], "ui", vec![], vec![])
+        .report_err();
+
     nvim.borrow()
         .unwrap()
-        .ui_attach(
+        .ui_attach_ext(
             cols,
             rows,
-            UiAttachOptions::new()
-                .set_popupmenu_external(true)
-                .set_tabline_external(true)
-                .set_linegrid_external(true)
-                .set_hlstate_external(true)
+            &[
+                ("ext_popupmenu", Value::from(true)),
+                ("ext_tabline", Value::from(true)),
+                ("ext_linegrid", Value::from(true)),
+                ("ext_hlstate", Value::from(true)),
+                ("ext_multigrid", Value::from(true)),
+                ("ext_messages", Value::from(true)),
+            ],
         )
         .map_err(NvimInitError::new_post_init)?;
 
@@ -176,5 +258,16 @@ pub fn post_start_init(
         }
     }
 
-    Ok(())
+    Ok(channel)
+}
+
+fn get_channel(nvim: &mut Neovim) -> result::Result<u64, NvimInitError> {
+    let api_info = nvim
+        .get_api_info()
+        .map_err(NvimInitError::new_post_init)?;
+
+    api_info
+        .first()
+        .and_then(Value::as_u64)
+        .ok_or_else(|| NvimInitError::new_post_init("Can't get channel id from api info"))
 }
@@ -1,18 +1,26 @@
-use std::num::ParseFloatError;
+use std::collections::HashMap;
+use std::num::{ParseFloatError, ParseIntError};
 use std::result;
 use std::sync::Arc;
 
+use gtk::prelude::*;
+
 use neovim_lib::neovim_api::Tabpage;
-use neovim_lib::{UiOption, Value};
+use neovim_lib::{NeovimApiAsync, UiOption, Value};
 
+use crate::cursor::Easing;
+use crate::file_chooser;
+use crate::grid::{FloatAnchor, FloatPos};
 use crate::shell;
 use crate::ui::UiMutex;
 
 use rmpv;
 use crate::value::ValueMapExt;
 
+use super::gui_api::{self, FileChooserArgs, GuiApiCall, GuiApiCb, GuiApiError, GuiApiReply};
 use super::handler::NvimHandler;
 use super::repaint_mode::RepaintMode;
+use super::ErrorReport;
 
 macro_rules! try_str {
     ($exp:expr) => {
@@ -43,6 +51,13 @@ macro_rules! try_bool {
     };
 }
 
+macro_rules! try_float {
+    ($exp:expr) => {
+        $exp.as_f64()
+            .ok_or_else(|| "Can't convert argument to float".to_owned())?
+    };
+}
+
 macro_rules! map_array {
     ($arg:expr, $err:expr, | $item:ident | $exp:expr) => {
         $arg.as_array().ok_or_else(|| $err).and_then(|items| {
@@ -115,8 +130,14 @@ macro_rules! call {
 pub enum NvimCommand {
     ToggleSidebar,
     ShowProjectView,
+    ShowFileFinder,
     Transparency(f64, f64),
     PreferDarkTheme(bool),
+    SessionSave,
+    /// Save session only if project already has one
+    SessionUpdate,
+    /// Restore session of given project or current dir
+    SessionLoad(Option<String>),
 }
 
 pub fn call_gui_event(
@@ -127,7 +148,9 @@ pub fn call_gui_event(
     match method {
         "Font" => call!(ui->set_font(args: str)),
         "FontFeatures" => call!(ui->set_font_features(args: str)),
+        "FontVariant" => call!(ui->set_font_variant(args: str, str)),
         "Linespace" => call!(ui->set_line_space(args: str)),
+        "LangMapping" => call!(ui->set_lang_mapping(args: bool)),
         "Clipboard" => match try_str!(args[0]) {
             "Set" => match try_str!(args[1]) {
                 "*" => ui.clipboard_primary_set(try_str!(args[2])),
@@ -165,6 +188,7 @@ pub fn call_gui_event(
             match try_str!(args[0]) {
                 "ToggleSidebar" => ui.on_command(NvimCommand::ToggleSidebar),
                 "ShowProjectView" => ui.on_command(NvimCommand::ShowProjectView),
+                "ShowFileFinder" => ui.on_command(NvimCommand::ShowFileFinder),
                 "Transparency" => ui.on_command(NvimCommand::Transparency(
                     try_str!(args.get(1).cloned().unwrap_or_else(|| "1.0".into()))
                         .parse()
@@ -182,6 +206,38 @@ pub fn call_gui_event(
 
                     ui.on_command(NvimCommand::PreferDarkTheme(prefer_dark_theme))
                 }
+                "FloatStyle" => {
+                    let shadow = try_str!(args.get(1).cloned().unwrap_or_else(|| "8".into()))
+                        .parse()
+                        .map_err(|e: ParseFloatError| e.to_string())?;
+                    let radius = try_str!(args.get(2).cloned().unwrap_or_else(|| "4".into()))
+                        .parse()
+                        .map_err(|e: ParseFloatError| e.to_string())?;
+                    let blend = try_str!(args.get(3).cloned().unwrap_or_else(|| "0".into()))
+                        .parse()
+                        .map_err(|e: ParseIntError| e.to_string())?;
+                    ui.set_float_style(shadow, radius, blend);
+                }
+                "SmoothScroll" => {
+                    let enabled = parse_toggle(args.get(1))?;
+                    let duration_ms = parse_duration(args.get(2))?;
+                    ui.set_smooth_scroll(enabled, duration_ms);
+                }
+                "BreakLigaturesAtCursor" => {
+                    let enabled = parse_toggle(args.get(1))?;
+                    ui.set_break_ligatures_at_cursor(enabled);
+                }
+                "CursorGlide" => {
+                    let enabled = parse_toggle(args.get(1))?;
+                    let duration_ms = parse_duration(args.get(2))?;
+                    let easing = match args.get(3) {
+                        Some(arg) => Some(Easing::parse(try_str!(arg))?),
+                        None => None,
+                    };
+                    ui.set_cursor_glide(enabled, duration_ms, easing);
+                }
+                "SessionSave" => ui.on_command(NvimCommand::SessionSave),
+                "SessionLoad" => ui.on_command(NvimCommand::SessionLoad(None)),
                 "SetCursorBlink" => {
                     let blink_count =
                         match try_str!(args.get(1).cloned().unwrap_or_else(|| Value::from(-1)))
@@ -195,15 +251,103 @@ pub fn call_gui_event(
                 _ => error!("Unknown command"),
             };
         }
+        "Api" => {
+            // there is no response to notification, so show error to user
+            let nvim = ui.nvim_clone();
+            let reply: GuiApiReply = Box::new(move |result| {
+                if let Err(err) = result {
+                    error!("Error call gui api: {}", err);
+                    if let Some(mut nvim) = nvim.nvim() {
+                        nvim.err_writeln_async(&format!("NeovimGtk: {}", err))
+                            .cb(|r| r.report_err())
+                            .call();
+                    }
+                }
+            });
+            call_gui_api(ui.gui_api_cb(), GuiApiCall::from_args(&args, false), reply);
+        }
         _ => return Err(format!("Unsupported event {}({:?})", method, args)),
     }
     Ok(())
 }
 
+/// Sends response of gui request, see `GuiApiReply`
+pub type GuiReply = Box<dyn FnOnce(result::Result<Value, Value>)>;
+
+/// Optional `on`/`off` argument of Gui command, other values leave option unchanged
+fn parse_toggle(arg: Option<&Value>) -> result::Result<Option<bool>, String> {
+    match arg {
+        Some(arg) => match try_str!(arg) {
+            "on" => Ok(Some(true)),
+            "off" => Ok(Some(false)),
+            _ => Ok(None),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Optional duration argument of Gui command in milliseconds
+fn parse_duration(arg: Option<&Value>) -> result::Result<Option<u64>, String> {
+    match arg {
+        Some(arg) => try_str!(arg)
+            .parse()
+            .map(Some)
+            .map_err(|e: ParseIntError| e.to_string()),
+        None => Ok(None),
+    }
+}
+
 pub fn call_gui_request(
     ui: &Arc<UiMutex<shell::State>>,
     method: &str,
     args: &Vec<Value>,
+    reply: GuiReply,
+) {
+    match method {
+        "Api" => {
+            // handler can borrow ui itself
+            let cb = ui.borrow().gui_api_cb();
+            call_gui_api(
+                cb,
+                GuiApiCall::from_args(args, true),
+                Box::new(move |result| reply(result.map_err(|e| e.to_value()))),
+            );
+        }
+        "FileChooser" => match FileChooserArgs::parse(args) {
+            Ok(args) => {
+                let parent = ui
+                    .borrow()
+                    .drawing_area
+                    .get_toplevel()
+                    .and_then(|w| w.downcast::<gtk::Window>().ok());
+                file_chooser::show(parent.as_ref(), args, move |paths| {
+                    reply(Ok(Value::Array(paths.into_iter().map(Value::from).collect())))
+                });
+            }
+            Err(err) => reply(Err(err.to_value())),
+        },
+        _ => reply(call_gui_sync_request(ui, method, args)),
+    }
+}
+
+fn call_gui_api(
+    cb: Option<GuiApiCb>,
+    call: result::Result<GuiApiCall, GuiApiError>,
+    reply: GuiApiReply,
+) {
+    match (call, cb) {
+        (Ok(call), Some(cb)) => cb(call, reply),
+        (Ok(_), None) => reply(Err(GuiApiError::Failed(
+            "GUI is not initialized".to_owned(),
+        ))),
+        (Err(err), _) => reply(Err(err)),
+    }
+}
+
+fn call_gui_sync_request(
+    ui: &Arc<UiMutex<shell::State>>,
+    method: &str,
+    args: &Vec<Value>,
 ) -> result::Result<Value, Value> {
     match method {
         "Clipboard" => {
@@ -230,27 +374,141 @@ pub fn call_gui_request(
                 }
             }
         }
+        "ApiInfo" => Ok(gui_api::api_info()),
         _ => Err(Value::String(
             format!("Unsupported request {}({:?})", method, args).into(),
         )),
     }
 }
 
+/// Events that change content of grids
+///
+/// Besides `shell::State` they are handled by headless renderer, see `crate::headless`.
+pub trait GridRedraw {
+    fn grid_line(
+        &mut self,
+        grid: u64,
+        row: u64,
+        col_start: u64,
+        cells: Vec<Vec<Value>>,
+    ) -> RepaintMode;
+
+    fn grid_clear(&mut self, grid: u64) -> RepaintMode;
+
+    fn grid_destroy(&mut self, grid: u64) -> RepaintMode;
+
+    fn grid_cursor_goto(&mut self, grid: u64, row: u64, column: u64) -> RepaintMode;
+
+    #[allow(clippy::too_many_arguments)]
+    fn grid_scroll(
+        &mut self,
+        grid: u64,
+        top: u64,
+        bot: u64,
+        left: u64,
+        right: u64,
+        rows: i64,
+        cols: i64,
+    ) -> RepaintMode;
+
+    fn grid_resize(&mut self, grid: u64, columns: u64, rows: u64) -> RepaintMode;
+
+    fn win_pos(
+        &mut self,
+        grid: u64,
+        win: Value,
+        start_row: u64,
+        start_col: u64,
+        width: u64,
+        height: u64,
+    ) -> RepaintMode;
+
+    fn win_float_pos(&mut self, grid: u64, pos: FloatPos) -> RepaintMode;
+
+    fn win_hide(&mut self, grid: u64) -> RepaintMode;
+
+    fn win_close(&mut self, grid: u64) -> RepaintMode;
+
+    fn win_viewport(
+        &mut self,
+        grid: u64,
+        win: Value,
+        topline: u64,
+        botline: u64,
+        curline: u64,
+        curcol: u64,
+    ) -> RepaintMode;
+
+    fn msg_set_pos(&mut self, grid: u64, row: u64) -> RepaintMode;
+
+    fn flush(&mut self) -> RepaintMode;
+
+    fn default_colors_set(
+        &mut self,
+        fg: i64,
+        bg: i64,
+        sp: i64,
+        cterm_fg: i64,
+        cterm_bg: i64,
+    ) -> RepaintMode;
+
+    fn hl_attr_define(
+        &mut self,
+        id: u64,
+        rgb_attr: HashMap<String, Value>,
+        cterm_attr: &Value,
+        info: Vec<HashMap<String, Value>>,
+    ) -> RepaintMode;
+
+    fn on_mode_change(&mut self, mode: String, idx: u64) -> RepaintMode;
+
+    fn mode_info_set(
+        &mut self,
+        cursor_style_enabled: bool,
+        mode_infos: Vec<HashMap<String, Value>>,
+    ) -> RepaintMode;
+}
+
+/// Call `cb` for each event of `redraw` notification,
+/// events with multiple argument lists are called once for each list
+pub fn each_event<F>(params: Vec<Value>, mut cb: F) -> result::Result<(), String>
+where
+    F: FnMut(&str, Vec<Value>) -> result::Result<(), String>,
+{
+    for ev in params {
+        if let Value::Array(ev_args) = ev {
+            let mut args_iter = ev_args.into_iter();
+            let ev_name = args_iter.next();
+            if let Some(ev_name) = ev_name {
+                if let Some(ev_name) = ev_name.as_str() {
+                    for local_args in args_iter {
+                        let args = match local_args {
+                            Value::Array(ar) => ar,
+                            _ => vec![],
+                        };
+                        cb(ev_name, args)
+                            .map_err(|desc| format!("Event {}\n{}", ev_name, desc))?;
+                    }
+                } else {
+                    error!("Unsupported event");
+                }
+            } else {
+                error!("Event name does not exists");
+            }
+        } else {
+            error!("Unsupported event type {:?}", ev);
+        }
+    }
+
+    Ok(())
+}
+
 pub fn call(
     ui: &mut shell::State,
     method: &str,
     args: Vec<Value>,
 ) -> result::Result<RepaintMode, String> {
     let repaint_mode = match method {
-        "grid_line" => call!(ui->grid_line(args: uint, uint, uint, ext)),
-        "grid_clear" => call!(ui->grid_clear(args: uint)),
-        "grid_destroy" => call!(ui->grid_destroy(args: uint)),
-        "grid_cursor_goto" => call!(ui->grid_cursor_goto(args: uint, uint, uint)),
-        "grid_scroll" => call!(ui->grid_scroll(args: uint, uint, uint, uint, uint, int, int)),
-        "grid_resize" => call!(ui->grid_resize(args: uint, uint, uint)),
-        "default_colors_set" => call!(ui->default_colors_set(args: int, int, int, int, int)),
-        "hl_attr_define" => call!(ui->hl_attr_define(args: uint, ext, val_ref, ext)),
-        "mode_change" => call!(ui->on_mode_change(args: str, uint)),
         "mouse_on" => ui.on_mouse(true),
         "mouse_off" => ui.on_mouse(false),
         "busy_start" => ui.on_busy(true),
@@ -267,6 +525,13 @@ pub fn call(
                 try_int!(args[1]),
                 try_uint!(args[2]),
                 try_uint!(args[3]),
+                args.get(4).and_then(Value::as_i64).and_then(|grid| {
+                    if grid > 0 {
+                        Some(grid as u64)
+                    } else {
+                        None
+                    }
+                }),
             )
         }
         "popupmenu_hide" => ui.popupmenu_hide(),
@@ -289,7 +554,6 @@ pub fn call(
                 }))?;
             ui.tabline_update(Tabpage::new(args[0].clone()), tabs_out)
         }
-        "mode_info_set" => call!(ui->mode_info_set(args: bool, ext)),
         "option_set" => call!(ui->option_set(args: str, val)),
         "cmdline_show" => call!(ui->cmdline_show(args: ext, uint, str, str, uint, uint)),
         "cmdline_block_show" => call!(ui->cmdline_block_show(args: ext)),
@@ -301,6 +565,52 @@ pub fn call(
         "wildmenu_show" => call!(ui->wildmenu_show(args: ext)),
         "wildmenu_hide" => ui.wildmenu_hide(),
         "wildmenu_select" => call!(ui->wildmenu_select(args: int)),
+        "msg_show" => call!(ui->msg_show(args: str, ext, bool)),
+        "msg_clear" => ui.msg_clear(),
+        "msg_showmode" => call!(ui->msg_showmode(args: ext)),
+        "msg_showcmd" => call!(ui->msg_showcmd(args: ext)),
+        "msg_ruler" => call!(ui->msg_ruler(args: ext)),
+        "msg_history_show" => call!(ui->msg_history_show(args: ext)),
+        _ => return call_grid(ui, method, args),
+    };
+
+    Ok(repaint_mode)
+}
+
+/// Handle events of `GridRedraw`, rest are ignored
+pub fn call_grid<U: GridRedraw>(
+    ui: &mut U,
+    method: &str,
+    args: Vec<Value>,
+) -> result::Result<RepaintMode, String> {
+    let repaint_mode = match method {
+        "grid_line" => call!(ui->grid_line(args: uint, uint, uint, ext)),
+        "grid_clear" => call!(ui->grid_clear(args: uint)),
+        "grid_destroy" => call!(ui->grid_destroy(args: uint)),
+        "grid_cursor_goto" => call!(ui->grid_cursor_goto(args: uint, uint, uint)),
+        "grid_scroll" => call!(ui->grid_scroll(args: uint, uint, uint, uint, uint, int, int)),
+        "grid_resize" => call!(ui->grid_resize(args: uint, uint, uint)),
+        "win_pos" => call!(ui->win_pos(args: uint, val, uint, uint, uint, uint)),
+        "win_float_pos" => ui.win_float_pos(
+            try_uint!(args[0]),
+            FloatPos {
+                anchor: FloatAnchor::parse(try_str!(args[2]))?,
+                anchor_grid: try_uint!(args[3]),
+                anchor_row: try_float!(args[4]),
+                anchor_col: try_float!(args[5]),
+                focusable: args.get(6).and_then(Value::as_bool).unwrap_or(true),
+                zindex: args.get(7).and_then(Value::as_u64).unwrap_or(50),
+            },
+        ),
+        "win_hide" => call!(ui->win_hide(args: uint)),
+        "win_close" => call!(ui->win_close(args: uint)),
+        "win_viewport" => call!(ui->win_viewport(args: uint, val, uint, uint, uint, uint)),
+        "msg_set_pos" => call!(ui->msg_set_pos(args: uint, uint)),
+        "flush" => ui.flush(),
+        "default_colors_set" => call!(ui->default_colors_set(args: int, int, int, int, int)),
+        "hl_attr_define" => call!(ui->hl_attr_define(args: uint, ext, val_ref, ext)),
+        "mode_change" => call!(ui->on_mode_change(args: str, uint)),
+        "mode_info_set" => call!(ui->mode_info_set(args: bool, ext)),
         _ => {
             warn!("Event {}({:?})", method, args);
             RepaintMode::Nothing
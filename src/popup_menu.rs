@@ -29,6 +29,8 @@ struct State {
     kind_column: gtk::TreeViewColumn,
     menu_column: gtk::TreeViewColumn,
     preview: bool,
+    /// Opacity of item backgrounds, see 'pumblend'
+    bg_alpha: f64,
 }
 
 impl State {
@@ -84,6 +86,7 @@ impl State {
             kind_column,
             menu_column,
             preview: true,
+            bg_alpha: 1.0,
         }
     }
 
@@ -155,7 +158,7 @@ impl State {
         self.renderer
             .set_property_foreground_rgba(Some(&hl.pmenu_fg().into()));
 
-        update_css(&self.css_provider, hl);
+        update_css(&self.css_provider, hl, self.bg_alpha);
 
         let list_store = gtk::ListStore::new(&[gtk::Type::String; 4]);
         let all_column_ids: Vec<u32> = (0..4).map(|i| i as u32).collect();
@@ -213,6 +216,7 @@ impl State {
 
 pub struct PopupMenu {
     popover: gtk::Popover,
+    popover_css_provider: gtk::CssProvider,
     open: bool,
 
     state: Rc<RefCell<State>>,
@@ -223,6 +227,10 @@ impl PopupMenu {
         let state = State::new();
         let popover = gtk::Popover::new(Some(drawing));
         popover.set_modal(false);
+        let popover_css_provider = gtk::CssProvider::new();
+        popover
+            .get_style_context()
+            .add_provider(&popover_css_provider, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
 
         let content = gtk::Box::new(gtk::Orientation::Vertical, 0);
 
@@ -268,6 +276,7 @@ impl PopupMenu {
 
         PopupMenu {
             popover,
+            popover_css_provider,
             state,
             open: false,
         }
@@ -298,6 +307,26 @@ impl PopupMenu {
         self.popover.hide();
     }
 
+    /// Same as 'pumblend', 0 is opaque and 100 is fully transparent
+    ///
+    /// Only item backgrounds are blended, text stays opaque. Applied on next show.
+    pub fn set_blend(&self, blend: u64) {
+        let bg_alpha = 1.0 - blend.min(100) as f64 / 100.0;
+        self.state.borrow_mut().bg_alpha = bg_alpha;
+
+        // themed popover background would be visible through items otherwise
+        let css = if blend > 0 {
+            "popover { background-color: transparent; }"
+        } else {
+            ""
+        };
+        if let Err(e) =
+            gtk::CssProviderExt::load_from_data(&self.popover_css_provider, css.as_bytes())
+        {
+            error!("Can't update css {}", e)
+        };
+    }
+
     pub fn select(&self, selected: i64) {
         self.state.borrow().select(selected);
     }
@@ -388,7 +417,8 @@ fn on_treeview_allocate(
     }));
 }
 
-pub fn update_css(css_provider: &gtk::CssProvider, hl: &HighlightMap) {
+/// `bg_alpha` is opacity of item backgrounds
+pub fn update_css(css_provider: &gtk::CssProvider, hl: &HighlightMap, bg_alpha: f64) {
     let bg = hl.pmenu_bg_sel();
     let fg = hl.pmenu_fg_sel();
 
@@ -398,8 +428,8 @@ pub fn update_css(css_provider: &gtk::CssProvider, hl: &HighlightMap) {
             ".view :selected {{ color: {}; background-color: {};}}\n
                 .view {{ background-color: {}; }}",
             fg.to_hex(),
-            bg.to_hex(),
-            hl.pmenu_bg().to_hex(),
+            bg.to_rgba_css(bg_alpha),
+            hl.pmenu_bg().to_rgba_css(bg_alpha),
         )
         .as_bytes(),
     ) {
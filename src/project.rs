@@ -12,7 +12,8 @@ use gtk::{
 use pango;
 
 use neovim_lib::{Neovim, NeovimApi, Value};
-use crate::nvim::ErrorReport;
+use crate::nvim::{ErrorReport, NvimCommand};
+use crate::session;
 use crate::shell::Shell;
 use crate::ui::UiMutex;
 
@@ -226,6 +227,12 @@ impl Projects {
             .unwrap();
 
         let shell = self.shell.borrow();
+        if project && session::find(&uri).is_some() {
+            let mut state = shell.state.borrow_mut();
+            state.on_command(NvimCommand::SessionLoad(Some(uri)));
+            return;
+        }
+
         if project {
             shell.cd(&uri);
         }
@@ -1,16 +1,23 @@
+use std::cell::RefCell;
 use std::collections::HashSet;
 
 use pango;
 
+use crate::color::Color;
+use crate::misc::split_at_comma;
 use crate::sys::pango as sys_pango;
 
-use super::itemize::ItemizeIterator;
+use super::glyph_cache::GlyphCache;
+use super::itemize::{self, ItemizeIterator};
 use crate::ui_model::StyledLine;
 
 pub struct Context {
     font_metrics: FontMetrix,
     font_features: FontFeatures,
+    font_variants: FontVariants,
+    glyph_cache: RefCell<GlyphCache>,
     line_space: i32,
+    break_ligatures_at_cursor: bool,
 }
 
 impl Context {
@@ -19,17 +26,25 @@ impl Context {
             line_space: 0,
             font_metrics: FontMetrix::new(pango_context, 0),
             font_features: FontFeatures::new(),
+            font_variants: FontVariants::new(),
+            glyph_cache: RefCell::new(GlyphCache::new()),
+            break_ligatures_at_cursor: false,
         }
     }
 
     pub fn update(&mut self, pango_context: pango::Context) {
         self.font_metrics = FontMetrix::new(pango_context, self.line_space);
+        self.glyph_cache.borrow_mut().clear();
     }
 
     pub fn update_font_features(&mut self, font_features: FontFeatures) {
         self.font_features = font_features;
     }
 
+    pub fn update_font_variant(&mut self, variant: FontVariant, desc: Option<pango::FontDescription>) {
+        self.font_variants.set(variant, desc);
+    }
+
     pub fn update_line_space(&mut self, line_space: i32) {
         self.line_space = line_space;
         let pango_context = self.font_metrics.pango_context.clone();
@@ -40,6 +55,7 @@ impl Context {
         let attr_iter = line.attr_list.get_iterator();
 
         ItemizeIterator::new(&line.line_str)
+            .flat_map(|range| itemize::split_range(range, line.break_range()))
             .flat_map(|(offset, len)| {
                 pango::itemize(
                     &self.font_metrics.pango_context,
@@ -53,6 +69,24 @@ impl Context {
             .collect()
     }
 
+    /// Draw glyphs at current point, pre rendered glyphs are used if possible
+    pub fn show_glyph_string(
+        &self,
+        ctx: &cairo::Context,
+        font: &pango::Font,
+        glyphs: &pango::GlyphString,
+        color: &Color,
+    ) {
+        self.glyph_cache
+            .borrow_mut()
+            .show_glyph_string(ctx, font, glyphs, color);
+    }
+
+    #[cfg(test)]
+    pub fn glyph_cache(&self) -> &RefCell<GlyphCache> {
+        &self.glyph_cache
+    }
+
     pub fn create_layout(&self) -> pango::Layout {
         pango::Layout::new(&self.font_metrics.pango_context)
     }
@@ -65,10 +99,23 @@ impl Context {
         &self.font_metrics.cell_metrics
     }
 
+    /// Ligatures are not formed over cursor cell, so text under cursor can be edited by char
+    pub fn set_break_ligatures_at_cursor(&mut self, break_ligatures_at_cursor: bool) {
+        self.break_ligatures_at_cursor = break_ligatures_at_cursor;
+    }
+
+    pub fn break_ligatures_at_cursor(&self) -> bool {
+        self.break_ligatures_at_cursor
+    }
+
     pub fn font_features(&self) -> &FontFeatures {
         &self.font_features
     }
 
+    pub fn font_variants(&self) -> &FontVariants {
+        &self.font_variants
+    }
+
     pub fn font_families(&self) -> HashSet<glib::GString> {
         self.font_metrics
             .pango_context
@@ -87,8 +134,11 @@ struct FontMetrix {
 
 impl FontMetrix {
     pub fn new(pango_context: pango::Context, line_space: i32) -> Self {
-        let font_metrics = pango_context.get_metrics(None, None).unwrap();
         let font_desc = pango_context.get_font_description().unwrap();
+        // rest of families are fallback fonts, they must not change cell size
+        let font_metrics = pango_context
+            .get_metrics(Some(&primary_font(&font_desc)), None)
+            .unwrap();
 
         FontMetrix {
             pango_context,
@@ -98,6 +148,54 @@ impl FontMetrix {
     }
 }
 
+/// Font description with only first family of families list
+fn primary_font(font_desc: &pango::FontDescription) -> pango::FontDescription {
+    let mut primary_desc = font_desc.clone();
+    if let Some(family) = font_desc.get_family() {
+        if let Some(primary_family) = family.split(',').next() {
+            primary_desc.set_family(primary_family.trim());
+        }
+    }
+    primary_desc
+}
+
+/// Combines `guifont` entries into single font description
+///
+/// Families are joined to list, so pango uses them as per glyph fallback chain,
+/// not installed families are skipped. Size is taken from first entry that has it,
+/// in case no entry has size `default_size` is used. Returns `None` for empty list.
+pub fn fallback_font_desc(
+    fonts: &[String],
+    installed_families: &HashSet<glib::GString>,
+    default_size: i32,
+) -> Option<pango::FontDescription> {
+    let descs: Vec<pango::FontDescription> = fonts
+        .iter()
+        .map(|font| pango::FontDescription::from_string(font))
+        .collect();
+    let first_desc = descs.first()?;
+
+    let size = descs
+        .iter()
+        .map(|desc| desc.get_size())
+        .find(|&size| size > 0)
+        .unwrap_or(default_size);
+
+    let families = descs
+        .iter()
+        .filter_map(|desc| desc.get_family())
+        .filter(|family| installed_families.contains(family))
+        .collect::<Vec<_>>();
+
+    // nothing installed? pango will find something similar to first one
+    let mut font_desc = first_desc.clone();
+    if !families.is_empty() {
+        font_desc.set_family(&families.join(","));
+    }
+    font_desc.set_size(size);
+    Some(font_desc)
+}
+
 pub struct CellMetrics {
     pub line_height: f64,
     pub char_width: f64,
@@ -189,3 +287,145 @@ impl FontFeatures {
         }
     }
 }
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FontVariant {
+    /// Double width cells, same as 'guifontwide'
+    Wide,
+    Bold,
+    Italic,
+    BoldItalic,
+}
+
+impl FontVariant {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "wide" => Some(FontVariant::Wide),
+            "bold" => Some(FontVariant::Bold),
+            "italic" => Some(FontVariant::Italic),
+            "bold_italic" => Some(FontVariant::BoldItalic),
+            _ => None,
+        }
+    }
+}
+
+/// Fonts that replace primary font for some cells
+///
+/// Size is always taken from primary font, so variant font descriptions must have no size.
+pub struct FontVariants {
+    wide: Option<pango::FontDescription>,
+    bold: Option<pango::FontDescription>,
+    italic: Option<pango::FontDescription>,
+    bold_italic: Option<pango::FontDescription>,
+}
+
+impl FontVariants {
+    pub fn new() -> Self {
+        FontVariants {
+            wide: None,
+            bold: None,
+            italic: None,
+            bold_italic: None,
+        }
+    }
+
+    /// Parse comma separated list of fonts, empty string means no font
+    pub fn parse_desc(fonts: &str) -> Option<pango::FontDescription> {
+        let descs: Vec<pango::FontDescription> = split_at_comma(fonts)
+            .iter()
+            .map(|font| pango::FontDescription::from_string(font))
+            .collect();
+        let families = descs
+            .iter()
+            .filter_map(|desc| desc.get_family())
+            .collect::<Vec<_>>();
+
+        if families.is_empty() {
+            return None;
+        }
+
+        let mut font_desc = descs[0].clone();
+        font_desc.set_family(&families.join(","));
+        font_desc.unset_fields(pango::FontMask::SIZE);
+        Some(font_desc)
+    }
+
+    fn set(&mut self, variant: FontVariant, desc: Option<pango::FontDescription>) {
+        match variant {
+            FontVariant::Wide => self.wide = desc,
+            FontVariant::Bold => self.bold = desc,
+            FontVariant::Italic => self.italic = desc,
+            FontVariant::BoldItalic => self.bold_italic = desc,
+        }
+    }
+
+    /// Font for cell with given attributes, None if primary font is used
+    pub fn get(&self, wide: bool, bold: bool, italic: bool) -> Option<&pango::FontDescription> {
+        let styled = match (bold, italic) {
+            (true, true) => self.bold_italic.as_ref(),
+            (true, false) => self.bold.as_ref(),
+            (false, true) => self.italic.as_ref(),
+            (false, false) => None,
+        };
+
+        if wide {
+            self.wide.as_ref().or(styled)
+        } else {
+            styled
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fallback_font_desc() {
+        let installed: HashSet<glib::GString> =
+            vec!["Fira Code".into(), "Noto Color Emoji".into()].into_iter().collect();
+
+        let desc = fallback_font_desc(
+            &[
+                "Fira Code 12".to_owned(),
+                "Not Installed".to_owned(),
+                "Noto Color Emoji".to_owned(),
+            ],
+            &installed,
+            10 * pango::SCALE,
+        )
+        .unwrap();
+        assert_eq!(Some("Fira Code,Noto Color Emoji".into()), desc.get_family());
+        assert_eq!(12 * pango::SCALE, desc.get_size());
+
+        // no size, fallbacks are kept with default size
+        let desc = fallback_font_desc(
+            &["Fira Code".to_owned(), "Noto Color Emoji".to_owned()],
+            &installed,
+            10 * pango::SCALE,
+        )
+        .unwrap();
+        assert_eq!(Some("Fira Code,Noto Color Emoji".into()), desc.get_family());
+        assert_eq!(10 * pango::SCALE, desc.get_size());
+
+        assert!(fallback_font_desc(&[], &installed, 10 * pango::SCALE).is_none());
+    }
+
+    #[test]
+    fn test_font_variants() {
+        let mut variants = FontVariants::new();
+        variants.set(FontVariant::Bold, FontVariants::parse_desc("Fira Code Bold 14"));
+        variants.set(FontVariant::Wide, FontVariants::parse_desc("Noto Sans CJK JP"));
+
+        let bold = variants.get(false, true, false).unwrap();
+        assert_eq!(Some("Fira Code".into()), bold.get_family());
+        assert_eq!(pango::Weight::Bold, bold.get_weight());
+        assert_eq!(0, bold.get_size());
+
+        assert!(variants.get(false, false, true).is_none());
+        assert_eq!(
+            Some("Noto Sans CJK JP".into()),
+            variants.get(true, true, false).unwrap().get_family()
+        );
+    }
+}
@@ -0,0 +1,388 @@
+use std::collections::HashMap;
+use std::env;
+
+use glib::object::{ObjectExt, ObjectType};
+
+use crate::color::Color;
+use crate::sys::cairo as sys_cairo;
+use crate::sys::pango::glyph_string::glyph_infos;
+use crate::sys::pangocairo::{font_get_scaled_font, show_glyph_string};
+
+/// Least recently used glyphs are evicted when cache grows over this number of glyphs
+const MAX_ENTRIES: usize = 8192;
+/// or when surfaces of glyphs take more then this number of bytes
+const MAX_BYTES: usize = 32 * 1024 * 1024;
+/// Cairo positions glyphs with subpixel precision since this version, older ones round
+/// glyph position to pixels
+const CAIRO_SUBPIXEL_VERSION: i32 = 11704;
+/// Horizontal glyph position is rounded to 1/SUBPIXEL_STEPS of pixel, same as cairo does
+const SUBPIXEL_STEPS: f64 = 4.0;
+/// Space around glyph ink for antialiasing, in pixels
+const PADDING: f64 = 2.0;
+
+const PANGO_GLYPH_EMPTY: u32 = 0x0FFF_FFFF;
+const PANGO_GLYPH_UNKNOWN_FLAG: u32 = 0x1000_0000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    /// Pointer to pango font, font is kept alive by entry, so pointer can't be reused
+    font: usize,
+    glyph: u32,
+    subpixel: u8,
+    /// Device scale (HiDPI) multiplied by 100
+    scale: u32,
+}
+
+struct GlyphEntry {
+    surface: cairo::ImageSurface,
+    /// Glyph origin inside surface, in device pixels
+    origin_x: f64,
+    origin_y: f64,
+    /// Glyph of color font (emoji) keeps own colors,
+    /// rest are rendered as mask and painted with text color
+    is_color: bool,
+    _font: pango::Font,
+}
+
+struct CacheSlot {
+    /// None in case glyph can't be rendered to surface
+    entry: Option<GlyphEntry>,
+    /// Size of entry surface
+    bytes: usize,
+    /// Generation of last use, see `GlyphCache::generation`
+    last_used: u64,
+}
+
+/// Pre rendered glyphs, so redraw only blits image surfaces
+/// instead of rasterizing glyphs through cairo each time
+///
+/// Used only for plain translated or uniformly scaled contexts,
+/// rest are drawn directly. Disabled with NVIM_GTK_NO_GLYPH_CACHE=1.
+pub struct GlyphCache {
+    entries: HashMap<GlyphKey, CacheSlot>,
+    /// Sum of sizes of all entries
+    bytes: usize,
+    /// Incremented on each drawn glyph string, so least recently used entries can be evicted
+    generation: u64,
+    enabled: bool,
+    /// Steps per pixel of horizontal glyph position, must match cairo, otherwise
+    /// position would be rounded twice
+    subpixel_steps: f64,
+}
+
+impl GlyphCache {
+    pub fn new() -> Self {
+        let enabled = env::var("NVIM_GTK_NO_GLYPH_CACHE")
+            .map(|opt| opt.trim() != "1")
+            .unwrap_or(true);
+
+        let subpixel_steps = if sys_cairo::version() >= CAIRO_SUBPIXEL_VERSION {
+            SUBPIXEL_STEPS
+        } else {
+            1.0
+        };
+
+        GlyphCache {
+            entries: HashMap::new(),
+            bytes: 0,
+            generation: 0,
+            enabled,
+            subpixel_steps,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.clear();
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.bytes = 0;
+    }
+
+    /// Remove least recently used entries until cache fits into given limits
+    fn evict(&mut self, max_entries: usize, max_bytes: usize) {
+        let mut slots: Vec<_> = self
+            .entries
+            .iter()
+            .map(|(key, slot)| (slot.last_used, *key))
+            .collect();
+        slots.sort_by_key(|&(last_used, _)| last_used);
+
+        for (_, key) in slots {
+            if self.entries.len() <= max_entries && self.bytes <= max_bytes {
+                break;
+            }
+            if let Some(slot) = self.entries.remove(&key) {
+                self.bytes -= slot.bytes;
+            }
+        }
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Same as `pango_cairo_show_glyph_string` at current point
+    pub fn show_glyph_string(
+        &mut self,
+        ctx: &cairo::Context,
+        font: &pango::Font,
+        glyphs: &pango::GlyphString,
+        color: &Color,
+    ) {
+        if !self.enabled || !self.try_show_glyph_string(ctx, font, glyphs, color) {
+            ctx.set_source_rgb(color.0, color.1, color.2);
+            show_glyph_string(ctx, font, glyphs);
+        }
+    }
+
+    fn try_show_glyph_string(
+        &mut self,
+        ctx: &cairo::Context,
+        font: &pango::Font,
+        glyphs: &pango::GlyphString,
+        color: &Color,
+    ) -> bool {
+        let matrix = ctx.get_matrix();
+        if matrix.xy != 0.0 || matrix.yx != 0.0 || matrix.xx != matrix.yy || matrix.xx <= 0.0 {
+            return false;
+        }
+        let scale = matrix.xx;
+
+        let infos = glyph_infos(glyphs);
+        // pango draws hex boxes for unknown glyphs itself
+        if infos
+            .iter()
+            .any(|info| info.glyph & PANGO_GLYPH_UNKNOWN_FLAG != 0)
+        {
+            return false;
+        }
+
+        if !font.is::<pangocairo::Font>() {
+            return false;
+        }
+        let scaled_font = match font_get_scaled_font(font) {
+            Some(scaled_font) => scaled_font,
+            None => return false,
+        };
+        // rasterize with font options of target, same as direct drawing does
+        let mut font_options = scaled_font.get_font_options();
+        font_options.merge(&ctx.get_font_options());
+
+        // evict more then needed, so eviction does not run on each glyph string
+        if self.entries.len() > MAX_ENTRIES || self.bytes > MAX_BYTES {
+            self.evict(MAX_ENTRIES * 3 / 4, MAX_BYTES * 3 / 4);
+        }
+        self.generation += 1;
+        let generation = self.generation;
+        let mut added_bytes = 0;
+
+        let (start_x, start_y) = ctx.get_current_point();
+
+        // glyphs are blitted in device pixels
+        ctx.save();
+        ctx.identity_matrix();
+
+        let mut x_position = 0;
+        for info in infos {
+            let glyph = info.glyph;
+            let geometry = &info.geometry;
+
+            if glyph != PANGO_GLYPH_EMPTY {
+                let x =
+                    start_x + f64::from(x_position + geometry.x_offset) / f64::from(pango::SCALE);
+                let y = start_y + f64::from(geometry.y_offset) / f64::from(pango::SCALE);
+
+                let device_x = x * scale + matrix.x0;
+                let device_y = (y * scale + matrix.y0).round();
+                let mut pixel_x = device_x.floor();
+                let mut subpixel = ((device_x - pixel_x) * self.subpixel_steps).round();
+                if subpixel >= self.subpixel_steps {
+                    pixel_x += 1.0;
+                    subpixel = 0.0;
+                }
+
+                let key = GlyphKey {
+                    font: font.as_ptr() as usize,
+                    glyph,
+                    subpixel: subpixel as u8,
+                    scale: (scale * 100.0).round() as u32,
+                };
+
+                let subpixel_x = subpixel / self.subpixel_steps;
+                let slot = self.entries.entry(key).or_insert_with(|| {
+                    let entry =
+                        render_glyph(font, &scaled_font, &font_options, glyph, subpixel_x, scale);
+                    let bytes = entry.as_ref().map_or(0, |entry| {
+                        entry.surface.get_stride() as usize * entry.surface.get_height() as usize
+                    });
+                    added_bytes += bytes;
+                    CacheSlot {
+                        entry,
+                        bytes,
+                        last_used: generation,
+                    }
+                });
+                slot.last_used = generation;
+
+                if let Some(ref entry) = slot.entry {
+                    let surface_x = pixel_x - entry.origin_x.floor();
+                    let surface_y = device_y - entry.origin_y;
+
+                    if entry.is_color {
+                        ctx.set_source_surface(&entry.surface, surface_x, surface_y);
+                        ctx.rectangle(
+                            surface_x,
+                            surface_y,
+                            f64::from(entry.surface.get_width()),
+                            f64::from(entry.surface.get_height()),
+                        );
+                        ctx.fill();
+                    } else {
+                        ctx.set_source_rgb(color.0, color.1, color.2);
+                        ctx.mask_surface(&entry.surface, surface_x, surface_y);
+                    }
+                }
+            }
+
+            x_position += geometry.width;
+        }
+
+        ctx.restore();
+        self.bytes += added_bytes;
+        true
+    }
+}
+
+fn render_glyph(
+    font: &pango::Font,
+    scaled_font: &cairo::ScaledFont,
+    font_options: &cairo::FontOptions,
+    glyph: u32,
+    subpixel_x: f64,
+    scale: f64,
+) -> Option<GlyphEntry> {
+    let scaled_font = cairo::ScaledFont::new(
+        &scaled_font.get_font_face(),
+        &scaled_font.get_font_matrix(),
+        &scaled_font.get_ctm(),
+        font_options,
+    );
+    let extents = scaled_font.glyph_extents(&[cairo::Glyph {
+        index: u64::from(glyph),
+        x: 0.0,
+        y: 0.0,
+    }]);
+
+    let origin_x = (PADDING - extents.x_bearing * scale).ceil() + subpixel_x;
+    let origin_y = (PADDING - extents.y_bearing * scale).ceil();
+    let width = (origin_x + (extents.x_bearing + extents.width) * scale + PADDING).ceil() as i32;
+    let height = (origin_y + (extents.y_bearing + extents.height) * scale + PADDING).ceil() as i32;
+
+    let mut surface =
+        cairo::ImageSurface::create(cairo::Format::ARgb32, width.max(1), height.max(1))
+            .map_err(|e| error!("Can't create glyph surface {:?}", e))
+            .ok()?;
+
+    {
+        let ctx = cairo::Context::new(&surface);
+        ctx.scale(scale, scale);
+        ctx.set_scaled_font(&scaled_font);
+        // color glyphs ignore source, so anything except alpha is left from own colors
+        ctx.set_source_rgb(0.0, 0.0, 0.0);
+        ctx.show_glyphs(&[cairo::Glyph {
+            index: u64::from(glyph),
+            x: origin_x / scale,
+            y: origin_y / scale,
+        }]);
+    }
+    surface.flush();
+    let is_color = has_color(&mut surface);
+
+    Some(GlyphEntry {
+        surface,
+        origin_x,
+        origin_y,
+        is_color,
+        _font: font.clone(),
+    })
+}
+
+/// Check if surface rendered with black source has any colored pixel
+fn has_color(surface: &mut cairo::ImageSurface) -> bool {
+    let stride = surface.get_stride() as usize;
+    let width = surface.get_width() as usize;
+
+    match surface.get_data() {
+        Ok(data) => data.chunks(stride).any(|row| {
+            // pixels are native endian u32 with alpha in highest byte
+            row[..width * 4].chunks(4).any(|pixel| {
+                u32::from_ne_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]) & 0x00FF_FFFF != 0
+            })
+        }),
+        Err(err) => {
+            error!("Can't read glyph surface {:?}", err);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_color() {
+        let mut surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 3, 3).unwrap();
+        {
+            let ctx = cairo::Context::new(&surface);
+            ctx.set_source_rgba(0.0, 0.0, 0.0, 0.5);
+            ctx.paint();
+        }
+        surface.flush();
+        assert!(!has_color(&mut surface));
+
+        {
+            let ctx = cairo::Context::new(&surface);
+            ctx.set_source_rgb(1.0, 0.0, 0.0);
+            ctx.rectangle(2.0, 2.0, 1.0, 1.0);
+            ctx.fill();
+        }
+        surface.flush();
+        assert!(has_color(&mut surface));
+    }
+
+    #[test]
+    fn test_evict() {
+        let mut cache = GlyphCache::new();
+        for glyph in 0..4 {
+            let key = GlyphKey {
+                font: 0,
+                glyph,
+                subpixel: 0,
+                scale: 100,
+            };
+            let slot = CacheSlot {
+                entry: None,
+                bytes: 10,
+                last_used: u64::from(4 - glyph),
+            };
+            cache.entries.insert(key, slot);
+            cache.bytes += 10;
+        }
+
+        cache.evict(3, 100);
+        assert_eq!(3, cache.len());
+        assert_eq!(30, cache.bytes);
+        assert!(cache.entries.keys().all(|key| key.glyph != 3));
+
+        cache.evict(3, 15);
+        assert_eq!(1, cache.len());
+        assert_eq!(10, cache.bytes);
+        assert!(cache.entries.keys().all(|key| key.glyph == 0));
+    }
+}
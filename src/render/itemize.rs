@@ -43,6 +43,30 @@ impl<'a> Iterator for ItemizeIterator<'a> {
     }
 }
 
+/// Split `(offset, len)` range by `break_range` of byte indexes
+pub fn split_range(
+    range: (usize, usize),
+    break_range: Option<(usize, usize)>,
+) -> impl Iterator<Item = (usize, usize)> {
+    let (offset, len) = range;
+    let end = offset + len;
+
+    let ranges = match break_range {
+        Some((break_start, break_end)) if break_start < end && break_end > offset => {
+            let break_start = break_start.max(offset);
+            let break_end = break_end.min(end);
+            vec![
+                (offset, break_start - offset),
+                (break_start, break_end - break_start),
+                (break_end, end - break_end),
+            ]
+        }
+        _ => vec![range],
+    };
+
+    ranges.into_iter().filter(|&(_, len)| len > 0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -55,4 +79,19 @@ mod tests {
         assert_eq!(Some((6, 4)), iter.next());
         assert_eq!(None, iter.next());
     }
+
+    #[test]
+    fn test_split_range() {
+        let split: Vec<_> = split_range((2, 4), Some((3, 4))).collect();
+        assert_eq!(vec![(2, 1), (3, 1), (4, 2)], split);
+
+        let split: Vec<_> = split_range((2, 4), Some((2, 3))).collect();
+        assert_eq!(vec![(2, 1), (3, 3)], split);
+
+        let split: Vec<_> = split_range((2, 4), Some((7, 8))).collect();
+        assert_eq!(vec![(2, 4)], split);
+
+        let split: Vec<_> = split_range((2, 4), None).collect();
+        assert_eq!(vec![(2, 4)], split);
+    }
 }
@@ -1,21 +1,25 @@
 mod context;
+mod glyph_cache;
 mod itemize;
 mod model_clip_iterator;
 
 pub use self::context::CellMetrics;
-pub use self::context::{Context, FontFeatures};
+pub use self::context::{fallback_font_desc, Context, FontFeatures, FontVariant, FontVariants};
 use self::model_clip_iterator::{ModelClipIteratorFactory, RowView};
 
+use std::f64::consts::{FRAC_PI_2, PI};
+
 use crate::color;
-use crate::sys::pangocairo::*;
 use cairo;
 use pango;
 use pangocairo;
 
 use crate::cursor::{cursor_rect, Cursor};
-use crate::highlight::HighlightMap;
+use crate::highlight::{HighlightMap, UnderlineStyle};
 use crate::ui_model;
 
+const SHADOW_ALPHA: f64 = 0.4;
+
 trait ContextAlpha {
     fn set_source_rgbo(&self, _: &color::Color, _: Option<f64>);
 }
@@ -35,6 +39,53 @@ pub fn fill_background(ctx: &cairo::Context, hl: &HighlightMap, alpha: Option<f6
     ctx.paint();
 }
 
+/// Add rectangle with rounded corners to current path
+pub fn rounded_rectangle(ctx: &cairo::Context, x: f64, y: f64, width: f64, height: f64, radius: f64) {
+    let radius = radius.min(width / 2.0).min(height / 2.0);
+
+    if radius <= 0.0 {
+        ctx.rectangle(x, y, width, height);
+        return;
+    }
+
+    ctx.new_sub_path();
+    ctx.arc(x + width - radius, y + radius, radius, -FRAC_PI_2, 0.0);
+    ctx.arc(x + width - radius, y + height - radius, radius, 0.0, FRAC_PI_2);
+    ctx.arc(x + radius, y + height - radius, radius, FRAC_PI_2, PI);
+    ctx.arc(x + radius, y + radius, radius, PI, PI + FRAC_PI_2);
+    ctx.close_path();
+}
+
+/// Draw soft drop shadow around area
+///
+/// cairo has no blur, so shadow is approximated by stacking semi transparent
+/// rectangles each one pixel bigger then previous
+pub fn draw_shadow(ctx: &cairo::Context, width: f64, height: f64, radius: f64, size: f64) {
+    if size <= 0.0 {
+        return;
+    }
+
+    ctx.save();
+    let steps = size.ceil() as i32;
+    let step_alpha = SHADOW_ALPHA / steps as f64;
+    let offset = (size / 3.0).floor();
+
+    ctx.set_source_rgba(0.0, 0.0, 0.0, step_alpha);
+    for i in 1..=steps {
+        let spread = f64::from(i);
+        rounded_rectangle(
+            ctx,
+            offset - spread,
+            offset - spread,
+            width + spread * 2.0,
+            height + spread * 2.0,
+            radius + spread,
+        );
+        ctx.fill();
+    }
+    ctx.restore();
+}
+
 pub fn render<C: Cursor>(
     ctx: &cairo::Context,
     cursor: &C,
@@ -64,8 +115,8 @@ pub fn render<C: Cursor>(
         let mut line_x = 0.0;
 
         for (col, cell) in row_view.line.line.iter().enumerate() {
-            draw_cell(&row_view, hl, cell, col, line_x, 0.0);
-            draw_underline_strikethrough(&row_view, hl, cell, line_x, 0.0);
+            draw_cell(&row_view, font_ctx, hl, cell, col, line_x, None);
+            draw_underline_strikethrough(&row_view, hl, cell, line_x, None);
 
             line_x += char_width;
         }
@@ -102,6 +153,8 @@ fn draw_cursor<C: Cursor>(
             .line
             .get(cursor_col + 1)
             .map_or(false, |c| c.double_width);
+        let (cursor_bg, cursor_fg) =
+            hl.cursor_colors(cursor.attr_id(), cursor_line.line.get(cursor_col));
 
         if cell_start_col >= 0 {
             let cell = &cursor_line[cursor_col];
@@ -123,26 +176,28 @@ fn draw_cursor<C: Cursor>(
             // reapint cursor and text
             ctx.set_operator(cairo::Operator::Over);
             ctx.move_to(line_x, line_y);
-            let cursor_alpha = cursor.draw(ctx, font_ctx, line_y, double_width, &hl);
+            let cursor_alpha = cursor.draw(ctx, font_ctx, line_y, double_width, &cursor_bg);
 
             let cell_start_line_x =
                 line_x - (cursor_col as i32 - cell_start_col) as f64 * cell_metrics.char_width;
 
             debug_assert!(cell_start_line_x >= 0.0);
 
-            ctx.set_operator(cairo::Operator::Xor);
+            // text under cursor is drawn in cursor highlight foreground, it fades with blinking
+            let fg = hl.actual_cell_fg(cell).mix(&cursor_fg, cursor_alpha);
             draw_cell(
                 &row_view,
+                font_ctx,
                 hl,
                 cell,
                 cell_start_col as usize,
                 cell_start_line_x,
-                cursor_alpha,
+                Some(&fg),
             );
-            draw_underline_strikethrough(&row_view, hl, cell, line_x, cursor_alpha);
+            draw_underline_strikethrough(&row_view, hl, cell, line_x, Some(&fg));
         } else {
             ctx.move_to(line_x, line_y);
-            cursor.draw(ctx, font_ctx, line_y, double_width, &hl);
+            cursor.draw(ctx, font_ctx, line_y, double_width, &cursor_bg);
         }
     }
 }
@@ -152,9 +207,11 @@ fn draw_underline_strikethrough(
     hl: &HighlightMap,
     cell: &ui_model::Cell,
     line_x: f64,
-    inverse_level: f64,
+    fg: Option<&color::Color>,
 ) {
-    if cell.hl.underline || cell.hl.undercurl || cell.hl.strikethrough {
+    let underline_style = cell.hl.underline_style();
+
+    if underline_style.is_some() || cell.hl.strikethrough {
         let &RowView {
             ctx,
             line_y,
@@ -172,7 +229,7 @@ fn draw_underline_strikethrough(
         } = cell_view;
 
         if cell.hl.strikethrough {
-            let fg = hl.actual_cell_fg(cell).inverse(inverse_level);
+            let fg = fg.unwrap_or_else(|| hl.actual_cell_fg(cell));
             ctx.set_source_rgb(fg.0, fg.1, fg.2);
             ctx.set_line_width(strikethrough_thickness);
             ctx.move_to(line_x, line_y + strikethrough_position);
@@ -180,28 +237,68 @@ fn draw_underline_strikethrough(
             ctx.stroke();
         }
 
-        if cell.hl.undercurl {
-            let sp = hl.actual_cell_sp(cell).inverse(inverse_level);
-            ctx.set_source_rgba(sp.0, sp.1, sp.2, 0.7);
+        match underline_style {
+            Some(UnderlineStyle::Curl) => {
+                let sp = fg.unwrap_or_else(|| hl.actual_cell_sp(cell));
+                ctx.set_source_rgba(sp.0, sp.1, sp.2, 0.7);
 
-            let max_undercurl_height = (line_height - underline_position) * 2.0;
-            let undercurl_height = (underline_thickness * 4.0).min(max_undercurl_height);
-            let undercurl_y = line_y + underline_position - undercurl_height / 2.0;
+                let max_undercurl_height = (line_height - underline_position) * 2.0;
+                let undercurl_height = (underline_thickness * 4.0).min(max_undercurl_height);
+                let undercurl_y = line_y + underline_position - undercurl_height / 2.0;
 
-            pangocairo::functions::show_error_underline(
-                ctx,
-                line_x,
-                undercurl_y,
-                char_width,
-                undercurl_height,
-            );
-        } else if cell.hl.underline {
-            let fg = hl.actual_cell_fg(cell).inverse(inverse_level);
-            ctx.set_source_rgb(fg.0, fg.1, fg.2);
-            ctx.set_line_width(underline_thickness);
-            ctx.move_to(line_x, line_y + underline_position);
-            ctx.line_to(line_x + char_width, line_y + underline_position);
-            ctx.stroke();
+                pangocairo::functions::show_error_underline(
+                    ctx,
+                    line_x,
+                    undercurl_y,
+                    char_width,
+                    undercurl_height,
+                );
+            }
+            Some(UnderlineStyle::Single) => {
+                // same as TUI, special color is used if set
+                let fg = fg
+                    .or_else(|| cell.hl.special.as_ref())
+                    .unwrap_or_else(|| hl.actual_cell_fg(cell));
+                ctx.set_source_rgb(fg.0, fg.1, fg.2);
+                ctx.set_line_width(underline_thickness);
+                ctx.move_to(line_x, line_y + underline_position);
+                ctx.line_to(line_x + char_width, line_y + underline_position);
+                ctx.stroke();
+            }
+            Some(UnderlineStyle::Double) => {
+                let sp = fg.unwrap_or_else(|| hl.actual_cell_sp(cell));
+                ctx.set_source_rgb(sp.0, sp.1, sp.2);
+                ctx.set_line_width(underline_thickness);
+
+                // second line goes below the first one, if there is no space both are moved up
+                let gap = underline_thickness * 2.0;
+                let max_position = line_height - underline_thickness / 2.0;
+                let position = underline_position.min(max_position - gap);
+                for y in &[position, position + gap] {
+                    ctx.move_to(line_x, line_y + y);
+                    ctx.line_to(line_x + char_width, line_y + y);
+                }
+                ctx.stroke();
+            }
+            Some(style @ UnderlineStyle::Dotted) | Some(style @ UnderlineStyle::Dashed) => {
+                let sp = fg.unwrap_or_else(|| hl.actual_cell_sp(cell));
+                ctx.set_source_rgb(sp.0, sp.1, sp.2);
+                ctx.set_line_width(underline_thickness);
+
+                let unit = underline_thickness.max(1.0);
+                let dashes = if style == UnderlineStyle::Dotted {
+                    [unit, unit]
+                } else {
+                    [unit * 3.0, unit * 2.0]
+                };
+                // offset by x, so pattern continues over cell borders
+                ctx.set_dash(&dashes, line_x);
+                ctx.move_to(line_x, line_y + underline_position);
+                ctx.line_to(line_x + char_width, line_y + underline_position);
+                ctx.stroke();
+                ctx.set_dash(&[], 0.0);
+            }
+            None => (),
         }
     }
 }
@@ -229,21 +326,24 @@ fn draw_cell_bg(
 
     let bg = hl.cell_bg(cell);
 
+    // blended cell ('winblend', 'pumblend') replaces grid background,
+    // so content below grid layer is visible through it
+    if let Some(blend_alpha) = cell.hl.blend_alpha() {
+        let bg = bg.unwrap_or_else(|| hl.bg());
+        let operator = ctx.get_operator();
+        ctx.set_operator(cairo::Operator::Source);
+        ctx.set_source_rgba(bg.0, bg.1, bg.2, blend_alpha * bg_alpha.unwrap_or(1.0));
+        ctx.rectangle(line_x, line_y, char_width, line_height);
+        ctx.fill();
+        ctx.set_operator(operator);
+        return;
+    }
+
+    // item can span cells with different backgrounds, so each cell is filled by own color
     if let Some(bg) = bg {
-        if !line.is_binded_to_item(col) {
-            if bg != hl.bg() {
-                ctx.set_source_rgbo(bg, bg_alpha);
-                ctx.rectangle(line_x, line_y, char_width, line_height);
-                ctx.fill();
-            }
-        } else {
+        if line.is_binded_to_item(col) || bg != hl.bg() {
             ctx.set_source_rgbo(bg, bg_alpha);
-            ctx.rectangle(
-                line_x,
-                line_y,
-                char_width * line.item_len_from_idx(col) as f64,
-                line_height,
-            );
+            ctx.rectangle(line_x, line_y, char_width, line_height);
             ctx.fill();
         }
     }
@@ -251,64 +351,138 @@ fn draw_cell_bg(
 
 fn draw_cell(
     row_view: &RowView,
+    font_ctx: &context::Context,
     hl: &HighlightMap,
     cell: &ui_model::Cell,
     col: usize,
     line_x: f64,
-    inverse_level: f64,
+    fg: Option<&color::Color>,
 ) {
     let &RowView {
         ctx,
         line,
         line_y,
-        cell_metrics: &CellMetrics { ascent, .. },
+        cell_metrics: &CellMetrics {
+            ascent, char_width, ..
+        },
         ..
     } = row_view;
 
     if let Some(item) = line.item_line[col].as_ref() {
         if let Some(ref glyphs) = item.glyphs {
-            let fg = hl.actual_cell_fg(cell).inverse(inverse_level);
-
-            ctx.move_to(line_x, line_y + ascent);
-            ctx.set_source_rgb(fg.0, fg.1, fg.2);
-
-            show_glyph_string(ctx, item.font(), glyphs);
+            let fg = fg.unwrap_or_else(|| hl.actual_cell_fg(cell));
+            let cells = &line.line[col..col + item.cells_count];
+
+            // item can span cells with different colors, e.g. ligature,
+            // in this case each part is drawn clipped with own color
+            if cells.iter().all(|c| hl.actual_cell_fg(c) == fg) {
+                show_item_glyphs(ctx, font_ctx, item, glyphs, line_x, line_y + ascent, fg);
+            } else {
+                let (clip_x1, clip_y1, clip_x2, clip_y2) = ctx.clip_extents();
+                let mut run_start = 0;
+                while run_start < cells.len() {
+                    let run_fg = hl.actual_cell_fg(&cells[run_start]);
+                    let run_end = cells[run_start..]
+                        .iter()
+                        .position(|c| hl.actual_cell_fg(c) != run_fg)
+                        .map_or(cells.len(), |len| run_start + len);
+
+                    // glyph ink can overflow item, so first and last parts are not clipped outside
+                    let x1 = if run_start == 0 {
+                        clip_x1
+                    } else {
+                        line_x + run_start as f64 * char_width
+                    };
+                    let x2 = if run_end == cells.len() {
+                        clip_x2
+                    } else {
+                        line_x + run_end as f64 * char_width
+                    };
+
+                    ctx.save();
+                    ctx.rectangle(x1, clip_y1, x2 - x1, clip_y2 - clip_y1);
+                    ctx.clip();
+                    show_item_glyphs(ctx, font_ctx, item, glyphs, line_x, line_y + ascent, run_fg);
+                    ctx.restore();
+
+                    run_start = run_end;
+                }
+            }
         }
     }
 }
 
-pub fn shape_dirty(ctx: &context::Context, ui_model: &mut ui_model::UiModel, hl: &HighlightMap) {
-    for line in ui_model.model_mut() {
+fn show_item_glyphs(
+    ctx: &cairo::Context,
+    font_ctx: &context::Context,
+    item: &ui_model::Item,
+    glyphs: &pango::GlyphString,
+    x: f64,
+    baseline: f64,
+    fg: &color::Color,
+) {
+    if let Some(ref glyph_scale) = item.glyph_scale {
+        ctx.save();
+        glyph_scale.apply(ctx, font_ctx, x, baseline);
+        font_ctx.show_glyph_string(ctx, item.font(), glyphs, fg);
+        ctx.restore();
+    } else {
+        ctx.move_to(x, baseline);
+        font_ctx.show_glyph_string(ctx, item.font(), glyphs, fg);
+    }
+}
+
+/// Shape changed lines, `has_cursor` is set for model with visible cursor
+pub fn shape_dirty(ctx: &context::Context, ui_model: &mut ui_model::UiModel, has_cursor: bool) {
+    let (cursor_row, cursor_col) = ui_model.get_cursor();
+    let break_at_cursor = has_cursor && ctx.break_ligatures_at_cursor();
+
+    for (row, line) in ui_model.model_mut().iter_mut().enumerate() {
         if !line.dirty_line {
             continue;
         }
 
-        let styled_line = ui_model::StyledLine::from(line, hl, ctx.font_features());
+        let mut styled_line =
+            ui_model::StyledLine::from(line, ctx.font_features(), ctx.font_variants());
+        if break_at_cursor && row == cursor_row {
+            styled_line.break_at(cursor_col);
+        }
         let items = ctx.itemize(&styled_line);
         line.merge(&styled_line, &items);
 
-        for (col, cell) in line.line.iter_mut().enumerate() {
-            if cell.dirty {
+        for col in 0..line.line.len() {
+            if line.line[col].dirty {
                 if let Some(item) = line.item_line[col].as_mut() {
                     let mut glyphs = pango::GlyphString::new();
+                    let mut is_emoji = false;
                     {
                         let analysis = item.analysis();
                         let offset = item.item.offset() as usize;
                         let length = item.item.length() as usize;
                         if let Some(line_str) = styled_line.line_str.get(offset..offset + length) {
                             pango::shape(&line_str, analysis, &mut glyphs);
+                            is_emoji = ui_model::is_emoji(line_str);
                         } else {
                             warn!("Wrong itemize split");
                         }
                     }
 
-                    item.set_glyphs(ctx, glyphs);
+                    // emoji is fitted to cells, including second half of last double width char
+                    let fit_cells = if is_emoji {
+                        let end = col + item.cells_count;
+                        let wide = line.line.get(end).map_or(false, |c| c.double_width);
+                        Some(item.cells_count + wide as usize)
+                    } else {
+                        None
+                    };
+                    item.set_glyphs(ctx, glyphs, fit_cells);
                 }
             }
 
-            cell.dirty = false;
+            line.line[col].dirty = false;
         }
 
         line.dirty_line = false;
     }
 }
+
@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+use crate::dirs;
+use crate::settings::SettingsLoader;
+
+const SESSIONS_DIR: &str = "sessions";
+
+/// GUI state saved together with nvim `:mksession` file
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    pub project: String,
+    pub width: i32,
+    pub height: i32,
+    pub is_maximized: bool,
+    pub show_sidebar: bool,
+    pub sidebar_width: i32,
+    /// Expanded directories of file browser, relative to project dir
+    pub expanded_dirs: Vec<String>,
+    pub font: Option<String>,
+}
+
+impl Session {
+    /// Path to nvim session file of this project
+    pub fn session_file(&self) -> Result<PathBuf, String> {
+        let mut path = dirs::get_app_config_dir_create()?;
+        path.push(SESSIONS_DIR);
+        std::fs::create_dir_all(&path).map_err(|e| format!("{}", e))?;
+
+        path.push(session_file_name(&self.project));
+        Ok(path)
+    }
+}
+
+/// Find saved session of project
+pub fn find(project: &str) -> Option<Session> {
+    SessionSettings::load()
+        .sessions
+        .into_iter()
+        .find(|s| s.project == project)
+}
+
+/// Store session, replacing previous session of the same project
+pub fn store(session: Session) {
+    let mut settings = SessionSettings::load();
+    settings.sessions.retain(|s| s.project != session.project);
+    settings.sessions.push(session);
+    settings.save();
+}
+
+/// Same as vim does for 'undodir': path separators are replaced with '%'
+fn session_file_name(project: &str) -> String {
+    let name: String = project
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' => '%',
+            c => c,
+        })
+        .collect();
+    format!("{}.vim", name)
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct SessionSettings {
+    sessions: Vec<Session>,
+}
+
+impl SettingsLoader for SessionSettings {
+    const SETTINGS_FILE: &'static str = "sessions.toml";
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        toml::from_str(s).map_err(|e| format!("{}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_file_name() {
+        assert_eq!("%home%user%project.vim", session_file_name("/home/user/project"));
+        assert_eq!("C%%src%project.vim", session_file_name("C:\\src\\project"));
+    }
+
+    #[test]
+    fn test_session_settings() {
+        let settings: SessionSettings = SessionSettings::from_str(
+            "[[sessions]]\n\
+             project = \"/home/user/project\"\n\
+             width = 800\n\
+             height = 600\n\
+             is_maximized = false\n\
+             show_sidebar = true\n\
+             sidebar_width = 200\n\
+             expanded_dirs = [\"src\", \"src/nvim\"]\n\
+             font = \"Monospace 12\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(1, settings.sessions.len());
+        assert_eq!(vec!["src", "src/nvim"], settings.sessions[0].expanded_dirs);
+        assert_eq!(Some("Monospace 12".to_owned()), settings.sessions[0].font);
+    }
+}
@@ -18,35 +18,39 @@ use pango;
 use pango::FontDescription;
 use pangocairo;
 
-use neovim_lib::neovim_api::Tabpage;
+use neovim_lib::neovim_api::{Tabpage, Window};
 use neovim_lib::{Neovim, NeovimApi, NeovimApiAsync, Value};
 
-use crate::color::{Color, COLOR_BLACK, COLOR_WHITE};
-use crate::grid::GridMap;
+use crate::grid::{FloatPos, GridMap, Viewport};
 use crate::highlight::HighlightMap;
 use crate::misc::{decode_uri, escape_filename, split_at_comma};
 use crate::nvim::{
-    self, CompleteItem, ErrorReport, NeovimClient, NeovimClientAsync, NeovimRef, NvimHandler,
-    RepaintMode,
+    self, CompleteItem, ErrorReport, GridRedraw, NeovimApiExt, NeovimClient, NeovimClientAsync,
+    NeovimRef, NvimHandler, RepaintMode,
 };
 use crate::settings::{FontSource, Settings};
 use crate::ui_model::ModelRect;
 
 use crate::cmd_line::{CmdLine, CmdLineContext};
-use crate::cursor::{BlinkCursor, Cursor, CursorRedrawCb};
+use crate::cursor::{BlinkCursor, Cursor, CursorRedrawCb, Easing, EmptyCursor};
 use crate::error;
 use crate::input;
-use crate::input::keyval_to_input_string;
+use crate::messages::Messages;
 use crate::mode;
 use crate::popup_menu::{self, PopupMenu};
 use crate::render;
 use crate::render::CellMetrics;
+use crate::smooth_scroll::SmoothScroll;
 use crate::subscriptions::{SubscriptionHandle, SubscriptionKey, Subscriptions};
+use crate::tab_window::TabWindow;
 use crate::tabline::Tabline;
 use crate::ui::UiMutex;
 
 const DEFAULT_FONT_NAME: &str = "DejaVu Sans Mono 12";
-pub const MINIMUM_SUPPORTED_NVIM_VERSION: &str = "0.3.2";
+pub const MINIMUM_SUPPORTED_NVIM_VERSION: &str = "0.4.0";
+
+/// Called once buffer is deleted, see `State::wait_buffer_delete`
+type BufferWaiterCb = Box<dyn FnOnce()>;
 
 macro_rules! idle_cb_call {
     ($state:ident.$cb:ident($( $x:expr ),*)) => (
@@ -108,14 +112,42 @@ impl TransparencySettigns {
     }
 }
 
+pub struct FloatSettings {
+    shadow: f64,
+    radius: f64,
+    blend: u8,
+}
+
+impl FloatSettings {
+    pub fn new() -> Self {
+        FloatSettings {
+            shadow: 8.0,
+            radius: 4.0,
+            blend: 0,
+        }
+    }
+
+    /// Float layer opacity, None in case float is opaque
+    fn alpha(&self) -> Option<f64> {
+        if self.blend > 0 {
+            Some(1.0 - f64::from(self.blend) / 100.0)
+        } else {
+            None
+        }
+    }
+}
+
 pub struct State {
     pub grids: GridMap,
 
     mouse_enabled: bool,
+    /// Grid where mouse button was pressed, it gets drag and release events
+    mouse_grid: Option<u64>,
     nvim: Rc<NeovimClient>,
     cursor: Option<BlinkCursor<State>>,
     popup_menu: PopupMenu,
     cmd_line: CmdLine,
+    messages: Messages,
     settings: Rc<RefCell<Settings>>,
     render_state: Rc<RefCell<RenderState>>,
 
@@ -128,17 +160,23 @@ pub struct State {
     stack: gtk::Stack,
     pub drawing_area: gtk::DrawingArea,
     tabs: Tabline,
+    tab_windows: Rc<RefCell<Vec<TabWindow>>>,
     im_context: gtk::IMMulticontext,
     error_area: error::ErrorArea,
 
     options: ShellOptions,
+    remote: bool,
     transparency_settings: TransparencySettigns,
+    float_settings: FloatSettings,
+    smooth_scroll: SmoothScroll,
 
     detach_cb: Option<Box<RefCell<dyn FnMut() + Send + 'static>>>,
     nvim_started_cb: Option<Box<RefCell<dyn FnMut() + Send + 'static>>>,
     command_cb: Option<Box<dyn FnMut(&mut State, nvim::NvimCommand) + Send + 'static>>,
+    gui_api_cb: Option<nvim::GuiApiCb>,
 
     subscriptions: RefCell<Subscriptions>,
+    buffer_waiters: RefCell<Vec<(String, BufferWaiterCb)>>,
 }
 
 impl State {
@@ -159,9 +197,11 @@ impl State {
             grids: GridMap::new(),
             nvim: Rc::new(NeovimClient::new()),
             mouse_enabled: true,
+            mouse_grid: None,
             cursor: None,
             popup_menu,
             cmd_line,
+            messages: Messages::new(),
             settings,
             render_state,
 
@@ -175,17 +215,23 @@ impl State {
             stack: gtk::Stack::new(),
             drawing_area,
             tabs: Tabline::new(),
+            tab_windows: Rc::new(RefCell::new(Vec::new())),
             im_context: gtk::IMMulticontext::new(),
             error_area: error::ErrorArea::new(),
 
+            remote: options.server.is_some(),
             options,
             transparency_settings: TransparencySettigns::new(),
+            float_settings: FloatSettings::new(),
+            smooth_scroll: SmoothScroll::new(),
 
             detach_cb: None,
             nvim_started_cb: None,
             command_cb: None,
+            gui_api_cb: None,
 
             subscriptions: RefCell::new(Subscriptions::new()),
+            buffer_waiters: RefCell::new(Vec::new()),
         }
     }
 
@@ -251,6 +297,18 @@ impl State {
         }
     }
 
+    pub fn set_gui_api_cb<F>(&mut self, cb: Option<F>)
+    where
+        F: Fn(nvim::GuiApiCall, nvim::GuiApiReply) + 'static,
+    {
+        self.gui_api_cb = cb.map(|c| Rc::new(c) as nvim::GuiApiCb);
+    }
+
+    /// Callback is cloned, so state borrow can be released before call
+    pub fn gui_api_cb(&self) -> Option<nvim::GuiApiCb> {
+        self.gui_api_cb.clone()
+    }
+
     pub fn set_font_desc(&mut self, desc: &str) {
         let font_description = FontDescription::from_string(desc);
 
@@ -271,6 +329,18 @@ impl State {
         self.on_redraw(&RepaintMode::All);
     }
 
+    pub fn font_desc(&self) -> String {
+        self.render_state
+            .borrow()
+            .font_ctx
+            .font_description()
+            .to_string()
+    }
+
+    pub fn is_remote(&self) -> bool {
+        self.remote
+    }
+
     pub fn set_font_features(&mut self, font_features: String) {
         let font_features = render::FontFeatures::from(font_features);
 
@@ -317,12 +387,58 @@ impl State {
         self.transparency_settings.enabled
     }
 
+    /// shadow and radius are in pixels, blend has same meaning as 'winblend'
+    pub fn set_float_style(&mut self, shadow: f64, radius: f64, blend: u8) {
+        self.float_settings.shadow = shadow.max(0.0);
+        self.float_settings.radius = radius.max(0.0);
+        self.float_settings.blend = blend.min(100);
+
+        self.on_redraw(&RepaintMode::All);
+    }
+
+    /// Toggle smooth scroll in case `enabled` is not given
+    pub fn set_smooth_scroll(&mut self, enabled: Option<bool>, duration_ms: Option<u64>) {
+        self.smooth_scroll.set(enabled, duration_ms);
+        self.on_redraw(&RepaintMode::All);
+    }
+
     pub fn set_cursor_blink(&mut self, val: i32) {
         if let Some(cursor) = &mut self.cursor {
             cursor.set_cursor_blink(val);
         }
     }
 
+    /// Language mappings are active, so cursor uses `attr_id_lm` highlight of mode
+    pub fn set_lang_mapping(&mut self, lang_mapping: bool) {
+        if let Some(cursor) = &mut self.cursor {
+            cursor.set_lang_mapping(lang_mapping);
+        }
+        self.queue_redraw_cursor();
+    }
+
+    /// Toggle breaking of ligatures at cursor in case `enabled` is not given
+    pub fn set_break_ligatures_at_cursor(&mut self, enabled: Option<bool>) {
+        {
+            let mut render_state = self.render_state.borrow_mut();
+            let enabled = enabled.unwrap_or(!render_state.font_ctx.break_ligatures_at_cursor());
+            render_state.font_ctx.set_break_ligatures_at_cursor(enabled);
+        }
+        self.grids.mark_cur_line_dirty();
+        self.on_redraw(&RepaintMode::All);
+    }
+
+    /// Toggle cursor glide in case `enabled` is not given
+    pub fn set_cursor_glide(
+        &mut self,
+        enabled: Option<bool>,
+        duration_ms: Option<u64>,
+        easing: Option<Easing>,
+    ) {
+        if let Some(cursor) = &mut self.cursor {
+            cursor.set_glide(enabled, duration_ms, easing);
+        }
+    }
+
     pub fn open_file(&self, path: &str) {
         if let Some(mut nvim) = self.nvim() {
             nvim.command_async(&format!("e {}", path))
@@ -331,6 +447,49 @@ impl State {
         }
     }
 
+    /// Open file with given command, `wait` makes buffer deleted when it's hidden
+    /// so `wait_buffer_delete` callbacks are called once user done with it
+    pub fn open_file_in(&self, path: &str, mode: OpenMode, wait: bool) {
+        if let Some(mut nvim) = self.nvim() {
+            let mut command = format!("{} {}", mode.command(), escape_filename(path));
+            if wait {
+                command.push_str(" | setlocal bufhidden=delete");
+            }
+
+            nvim.command_async(&command).cb(|r| r.report_err()).call();
+        }
+    }
+
+    /// Call `cb` once buffer with given full path deleted
+    pub fn wait_buffer_delete<F: FnOnce() + 'static>(&self, path: &str, cb: F) {
+        self.buffer_waiters
+            .borrow_mut()
+            .push((canonical_path(path), Box::new(cb)));
+    }
+
+    pub fn on_buffer_delete(&self, path: &str) {
+        let path = canonical_path(path);
+        let waiters: Vec<_> = {
+            let mut buffer_waiters = self.buffer_waiters.borrow_mut();
+            let (deleted, rest) = buffer_waiters.drain(..).partition(|(p, _)| *p == path);
+            *buffer_waiters = rest;
+            deleted
+        };
+
+        for (_, cb) in waiters {
+            cb();
+        }
+    }
+
+    /// Call all pending `wait_buffer_delete` callbacks, used when nvim or window goes away
+    pub fn release_buffer_waiters(&self) {
+        let waiters: Vec<_> = self.buffer_waiters.borrow_mut().drain(..).collect();
+
+        for (_, cb) in waiters {
+            cb();
+        }
+    }
+
     pub fn cd(&self, path: &str) {
         if let Some(mut nvim) = self.nvim() {
             nvim.command_async(&format!("cd {}", path))
@@ -358,12 +517,17 @@ impl State {
     fn queue_draw_area<M: AsRef<ModelRect>>(&mut self, rect_list: &[M]) {
         // extends by items before, then after changes
 
+        let visible = self.grids.visible();
         let rects: Vec<_> = rect_list
             .iter()
-            .map(|rect| rect.as_ref().clone())
-            .map(|mut rect| {
-                rect.extend_by_items(self.grids.current_model());
-                rect
+            .flat_map(|rect| {
+                let grids = &self.grids;
+                visible.iter().filter_map(move |&idx| {
+                    grids.to_grid(idx, rect.as_ref()).map(|mut grid_rect| {
+                        grid_rect.extend_by_items(Some(grids[idx].model()));
+                        (idx, grid_rect)
+                    })
+                })
             })
             .collect();
 
@@ -372,19 +536,25 @@ impl State {
         let render_state = self.render_state.borrow();
         let cell_metrics = render_state.font_ctx.cell_metrics();
 
-        for mut rect in rects {
-            rect.extend_by_items(self.grids.current_model());
-
-            let (x, y, width, height) =
-                rect.to_area_extend_ink(self.grids.current_model(), cell_metrics);
-            self.drawing_area.queue_draw_area(x, y, width, height);
+        for (idx, mut rect) in rects {
+            let model = self.grids[idx].model();
+            rect.extend_by_items(Some(model));
+
+            let (x, y, width, height) = rect.to_area_extend_ink(Some(model), cell_metrics);
+            let (x_offset, y_offset) = grid_offset(&self.grids, idx, cell_metrics);
+            let x1 = (x as f64 + x_offset).floor() as i32;
+            let y1 = (y as f64 + y_offset).floor() as i32;
+            let x2 = ((x + width) as f64 + x_offset).ceil() as i32;
+            let y2 = ((y + height) as f64 + y_offset).ceil() as i32;
+            self.drawing_area.queue_draw_area(x1, y1, x2 - x1, y2 - y1);
         }
     }
 
     fn update_dirty_glyphs(&mut self) {
         let render_state = self.render_state.borrow();
-        if let Some(model) = self.grids.current_model_mut() {
-            render::shape_dirty(&render_state.font_ctx, model, &render_state.hl);
+        let cursor_grid = self.grids.current_idx();
+        for (idx, model) in self.grids.models_mut() {
+            render::shape_dirty(&render_state.font_ctx, model, idx == cursor_grid);
         }
     }
 
@@ -416,9 +586,9 @@ impl State {
     }
 
     fn set_im_location(&self) {
-        if let Some((row, col)) = self.grids.current().map(|g| g.get_cursor()) {
-            let (x, y, width, height) = ModelRect::point(col, row)
-                .to_area(self.render_state.borrow().font_ctx.cell_metrics());
+        if let Some(cur_point) = self.grids.cur_point() {
+            let (x, y, width, height) =
+                cur_point.to_area(self.render_state.borrow().font_ctx.cell_metrics());
 
             self.im_context.set_cursor_location(&gdk::Rectangle {
                 x,
@@ -510,7 +680,7 @@ impl State {
     pub fn set_autocmds(&self) {
         self.subscriptions
             .borrow()
-            .set_autocmds(&mut self.nvim().unwrap());
+            .set_autocmds(&mut self.nvim().unwrap(), self.nvim.channel());
     }
 
     pub fn notify(&self, params: Vec<Value>) -> Result<(), String> {
@@ -524,7 +694,7 @@ impl State {
     }
 
     pub fn set_font(&mut self, font_desc: String) {
-        self.set_font_rpc(&font_desc);
+        self.set_font_list(&font_desc);
     }
 
     pub fn set_font_rpc(&mut self, font_desc: &str) {
@@ -594,6 +764,36 @@ impl UiState {
     }
 }
 
+/// How file opened from outside (e.g. second application instance) is shown
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum OpenMode {
+    Edit,
+    Tab,
+    Split,
+    VSplit,
+}
+
+impl OpenMode {
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode {
+            "edit" => Some(OpenMode::Edit),
+            "tab" => Some(OpenMode::Tab),
+            "split" => Some(OpenMode::Split),
+            "vsplit" => Some(OpenMode::VSplit),
+            _ => None,
+        }
+    }
+
+    fn command(self) -> &'static str {
+        match self {
+            OpenMode::Edit => "e",
+            OpenMode::Tab => "tabe",
+            OpenMode::Split => "sp",
+            OpenMode::VSplit => "vsp",
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct ShellOptions {
     nvim_bin_path: Option<String>,
@@ -601,6 +801,7 @@ pub struct ShellOptions {
     args_for_neovim: Vec<String>,
     input_data: Option<String>,
     cterm_colors: bool,
+    server: Option<String>,
 }
 
 impl ShellOptions {
@@ -616,9 +817,16 @@ impl ShellOptions {
                 .values_of("nvim-args")
                 .map(|args| args.map(str::to_owned).collect())
                 .unwrap_or_else(|| vec![]),
+            server: matches.value_of("server").map(str::to_owned),
         }
     }
 
+    /// Attach to running nvim instance instead of spawning new one
+    pub fn with_server(mut self, address: String) -> Self {
+        self.server = Some(address);
+        self
+    }
+
     // remove input data from original
     // shell option, as it need to be used only once
     pub fn take(&mut self) -> Self {
@@ -668,8 +876,15 @@ impl Shell {
 
         let nvim_box = gtk::Box::new(gtk::Orientation::Vertical, 0);
 
+        let overlay = gtk::Overlay::new();
+        overlay.add(&state.drawing_area);
+        overlay.add_overlay(state.messages.toasts_widget());
+        overlay.set_overlay_pass_through(state.messages.toasts_widget(), true);
+        overlay.add_overlay(state.messages.history_widget());
+
         nvim_box.pack_start(&*state.tabs, false, true, 0);
-        nvim_box.pack_start(&state.drawing_area, true, true, 0);
+        nvim_box.pack_start(&overlay, true, true, 0);
+        nvim_box.pack_start(state.messages.status_widget(), false, true, 0);
 
         state.stack.add_named(&nvim_box, "Nvim");
         state.stack.add_named(&*state.error_area, "Error");
@@ -723,6 +938,25 @@ impl Shell {
             .drawing_area
             .connect_draw(move |_, ctx| gtk_draw(&ref_state, ctx));
 
+        let ref_state = self.state.clone();
+        state
+            .tabs
+            .set_mirror_cb(Some(move |tabpage: &Tabpage, name: Option<&str>| {
+                mirror_tab(&ref_state, tabpage, name)
+            }));
+
+        let tab_windows = state.tab_windows.clone();
+        state.drawing_area.connect_destroy(move |_| {
+            let windows: Vec<_> = tab_windows
+                .borrow()
+                .iter()
+                .map(|tab_window| tab_window.window().clone())
+                .collect();
+            for window in windows {
+                window.destroy();
+            }
+        });
+
         let ref_ui_state = self.ui_state.clone();
         let ref_state = self.state.clone();
         state.drawing_area.connect_key_press_event(move |_, ev| {
@@ -883,6 +1117,14 @@ impl Shell {
         self.state.borrow().cd(path);
     }
 
+    /// True in case shell attached to nvim instance that is not owned by this shell
+    pub fn is_remote(&self) -> bool {
+        self.state.borrow().remote
+    }
+
+    /// Detach ui from nvim
+    ///
+    /// Embedded nvim exits after this call, remote one keeps running
     pub fn detach_ui(&mut self) {
         let state = self.state.borrow();
 
@@ -938,6 +1180,14 @@ impl Shell {
         state.set_nvim_command_cb(cb);
     }
 
+    pub fn set_gui_api_cb<F>(&self, cb: Option<F>)
+    where
+        F: Fn(nvim::GuiApiCall, nvim::GuiApiReply) + 'static,
+    {
+        let mut state = self.state.borrow_mut();
+        state.set_gui_api_cb(cb);
+    }
+
     pub fn set_completeopts(&self, options: &str) {
         self.state
             .borrow()
@@ -954,6 +1204,15 @@ impl Deref for Shell {
     }
 }
 
+/// Path with symlinks resolved, so buffer opened through a link matches its target.
+/// Given path is kept if it does not exist, e.g. on remote nvim host.
+fn canonical_path(path: &str) -> String {
+    std::fs::canonicalize(path)
+        .ok()
+        .and_then(|path| path.to_str().map(str::to_owned))
+        .unwrap_or_else(|| path.to_owned())
+}
+
 fn gtk_focus_in(state: &mut State) -> Inhibit {
     if let Some(mut nvim) = state.try_nvim() {
         nvim.command_async("if exists('#FocusGained') | doautocmd FocusGained | endif")
@@ -1056,12 +1315,55 @@ fn gtk_button_press(
 }
 
 fn mouse_input(shell: &mut State, input: &str, state: ModifierType, position: (f64, f64)) {
-    if let Some(mut nvim) = shell.try_nvim() {
-        let (col, row) = mouse_coordinates_to_nvim(shell, position);
-        let input_str = format!("{}<{},{}>", keyval_to_input_string(input, state), col, row);
+    let (button, action) = match input {
+        "LeftMouse" => ("left", "press"),
+        "LeftDrag" => ("left", "drag"),
+        "LeftRelease" => ("left", "release"),
+        "MiddleMouse" => ("middle", "press"),
+        "MiddleRelease" => ("middle", "release"),
+        "RightMouse" => ("right", "press"),
+        "RightRelease" => ("right", "release"),
+        "ScrollWheelUp" => ("wheel", "up"),
+        "ScrollWheelDown" => ("wheel", "down"),
+        "ScrollWheelLeft" => ("wheel", "left"),
+        "ScrollWheelRight" => ("wheel", "right"),
+        _ => {
+            error!("Unknown mouse input {}", input);
+            return;
+        }
+    };
+
+    let (col, row) = mouse_coordinates_to_nvim(shell, position);
+    let (row, col) = (row as usize, col as usize);
+    let grabbed = match action {
+        "drag" | "release" => shell.mouse_grid.and_then(|grid| {
+            shell
+                .grids
+                .grid_pos_clamped(grid, row, col)
+                .map(|(grid_row, grid_col)| (grid, grid_row, grid_col))
+        }),
+        _ => None,
+    };
+    let target = grabbed.or_else(|| shell.grids.grid_at(row, col));
+    match action {
+        "press" => shell.mouse_grid = target.map(|(grid, _, _)| grid),
+        "release" => shell.mouse_grid = None,
+        _ => (),
+    }
 
-        nvim.input(&input_str)
-            .expect("Can't send mouse input event");
+    if let Some((grid, grid_row, grid_col)) = target {
+        if let Some(mut nvim) = shell.try_nvim() {
+            nvim.input_mouse_async(
+                button,
+                action,
+                &input::modifier_to_input_string(state),
+                grid,
+                grid_row as u64,
+                grid_col as u64,
+            )
+            .cb(|r| r.report_err())
+            .call();
+        }
     }
 }
 
@@ -1121,17 +1423,157 @@ fn draw_content(state: &State, ctx: &cairo::Context) {
         &render_state.hl,
         state.transparency_settings.background_alpha(),
     );
-    render::render(
+
+    let cursor_grid = state.grids.current_idx();
+    for idx in state.grids.visible() {
+        draw_grid(state, ctx, &render_state, idx, idx == cursor_grid);
+    }
+
+    state
+        .cursor
+        .as_ref()
+        .unwrap()
+        .draw_glide(ctx, &render_state.font_ctx, &render_state.hl);
+
+    ctx.pop_group_to_source();
+    ctx.paint();
+}
+
+fn draw_grid(
+    state: &State,
+    ctx: &cairo::Context,
+    render_state: &RenderState,
+    idx: u64,
+    show_cursor: bool,
+) {
+    let cell_metrics = render_state.font_ctx.cell_metrics();
+    let grid = &state.grids[idx];
+    let model = grid.model();
+    let (x, y) = grid_offset(&state.grids, idx, cell_metrics);
+    let width = model.columns as f64 * cell_metrics.char_width;
+    let height = model.rows as f64 * cell_metrics.line_height;
+
+    ctx.save();
+    ctx.translate(x, y);
+
+    let float_alpha = if grid.is_float() {
+        let float_settings = &state.float_settings;
+
+        render::draw_shadow(
+            ctx,
+            width,
+            height,
+            float_settings.radius,
+            float_settings.shadow,
+        );
+        render::rounded_rectangle(ctx, 0.0, 0.0, width, height, float_settings.radius);
+        float_settings.alpha()
+    } else {
+        ctx.rectangle(0.0, 0.0, width, height);
+        None
+    };
+    ctx.clip();
+
+    // floats are drawn as separate layer, so they can be blended with content below
+    // as whole or by cells with highlight blend
+    let is_layer = grid.is_float();
+    if is_layer {
+        ctx.push_group();
+    }
+
+    // grids can overlap, so each one must hide content below it
+    ctx.set_operator(cairo::Operator::Source);
+    render::fill_background(
         ctx,
-        state.cursor.as_ref().unwrap(),
-        &render_state.font_ctx,
-        state.grids.current_model().unwrap(),
         &render_state.hl,
-        state.transparency_settings.filled_alpha(),
+        state.transparency_settings.background_alpha(),
+    );
+    ctx.set_operator(cairo::Operator::Over);
+
+    let draw_content = |ctx: &cairo::Context| {
+        if show_cursor {
+            render::render(
+                ctx,
+                state.cursor.as_ref().unwrap(),
+                &render_state.font_ctx,
+                model,
+                &render_state.hl,
+                state.transparency_settings.filled_alpha(),
+            );
+        } else {
+            render::render(
+                ctx,
+                &EmptyCursor::new(),
+                &render_state.font_ctx,
+                model,
+                &render_state.hl,
+                state.transparency_settings.filled_alpha(),
+            );
+        }
+    };
+    draw_content(ctx);
+    state.smooth_scroll.draw(
+        idx,
+        ctx,
+        cell_metrics,
+        &render_state.hl,
+        state.transparency_settings.background_alpha(),
+        draw_content,
     );
 
-    ctx.pop_group_to_source();
-    ctx.paint();
+    if is_layer {
+        ctx.pop_group_to_source();
+        ctx.paint_with_alpha(float_alpha.unwrap_or(1.0));
+    }
+
+    ctx.restore();
+}
+
+/// Draw copy of window grids of mirrored tabpage, see `TabWindow`
+fn draw_tab_window(state: &State, ctx: &cairo::Context, tab_window: &TabWindow) {
+    let render_state = state.render_state.borrow();
+    let cell_metrics = render_state.font_ctx.cell_metrics();
+    render::fill_background(
+        ctx,
+        &render_state.hl,
+        state.transparency_settings.background_alpha(),
+    );
+
+    for &(row, col, ref model) in tab_window.grids() {
+        ctx.save();
+        ctx.translate(
+            col as f64 * cell_metrics.char_width,
+            row as f64 * cell_metrics.line_height,
+        );
+        ctx.rectangle(
+            0.0,
+            0.0,
+            model.columns as f64 * cell_metrics.char_width,
+            model.rows as f64 * cell_metrics.line_height,
+        );
+        ctx.clip();
+
+        render::render(
+            ctx,
+            &EmptyCursor::new(),
+            &render_state.font_ctx,
+            model,
+            &render_state.hl,
+            state.transparency_settings.filled_alpha(),
+        );
+        ctx.restore();
+    }
+}
+
+/**
+ * Pixel offset of grid top left corner on drawing area
+ */
+fn grid_offset(grids: &GridMap, idx: u64, cell_metrics: &CellMetrics) -> (f64, f64) {
+    let (row, col) = grids.position(idx).unwrap_or((0, 0));
+    (
+        col as f64 * cell_metrics.char_width,
+        row as f64 * cell_metrics.line_height,
+    )
 }
 
 fn gtk_draw(state_arc: &Arc<UiMutex<State>>, ctx: &cairo::Context) -> Inhibit {
@@ -1145,6 +1587,62 @@ fn gtk_draw(state_arc: &Arc<UiMutex<State>>, ctx: &cairo::Context) -> Inhibit {
     Inhibit(false)
 }
 
+/// Mirror tabpage in separate top level window
+fn mirror_tab(state_arc: &Arc<UiMutex<State>>, tabpage: &Tabpage, name: Option<&str>) {
+    let state = state_arc.borrow();
+
+    if let Some(tab_window) = state
+        .tab_windows
+        .borrow()
+        .iter()
+        .find(|tab_window| tab_window.tabpage() == tabpage)
+    {
+        tab_window.window().present();
+        return;
+    }
+
+    let windows = match state.nvim() {
+        Some(mut nvim) => tabpage.list_wins(&mut nvim).ok_and_report(),
+        None => None,
+    };
+    let windows = if let Some(windows) = windows {
+        windows
+    } else {
+        return;
+    };
+
+    let alloc = state.drawing_area.get_allocation();
+    let mut tab_window = TabWindow::new(tabpage.clone(), windows, alloc.width, alloc.height);
+    // Dragged tab is the selected one, tabline mirrors current tabpage.
+    tab_window.set_title(name, true);
+    tab_window.set_grids(state.grids.window_models(tab_window.windows()));
+
+    let ref_state = state_arc.clone();
+    let ref_tabpage = tabpage.clone();
+    tab_window.drawing_area().connect_draw(move |_, ctx| {
+        let state = ref_state.borrow();
+        let tab_windows = state.tab_windows.borrow();
+        if let Some(tab_window) = tab_windows
+            .iter()
+            .find(|tab_window| *tab_window.tabpage() == ref_tabpage)
+        {
+            draw_tab_window(&state, ctx, tab_window);
+        }
+        Inhibit(false)
+    });
+
+    let tab_windows = state.tab_windows.clone();
+    let ref_tabpage = tabpage.clone();
+    tab_window.window().connect_destroy(move |_| {
+        tab_windows
+            .borrow_mut()
+            .retain(|tab_window| *tab_window.tabpage() != ref_tabpage);
+    });
+
+    tab_window.show();
+    state.tab_windows.borrow_mut().push(tab_window);
+}
+
 fn show_nvim_start_error(err: &nvim::NvimInitError, state_arc: Arc<UiMutex<State>>) {
     let source = err.source();
     let cmd = err.cmd().unwrap().to_owned();
@@ -1179,13 +1677,19 @@ fn init_nvim_async(
     cols: usize,
     rows: usize,
 ) {
-    // execute nvim
-    let nvim = match nvim::start(
-        nvim_handler,
-        options.nvim_bin_path.as_ref(),
-        options.timeout,
-        options.args_for_neovim,
-    ) {
+    // execute nvim or connect to running one
+    let nvim = if let Some(ref address) = options.server {
+        nvim::connect(nvim_handler, address, options.timeout)
+    } else {
+        nvim::start(
+            nvim_handler,
+            options.nvim_bin_path.as_ref(),
+            options.timeout,
+            options.args_for_neovim,
+        )
+    };
+
+    let nvim = match nvim {
         Ok(nvim) => nvim,
         Err(err) => {
             show_nvim_start_error(&err, state_arc);
@@ -1212,10 +1716,9 @@ fn init_nvim_async(
     });
 
     // attach ui
-    if let Err(err) = nvim::post_start_init(nvim, cols as i64, rows as i64, options.input_data) {
-        show_nvim_init_error(&err, state_arc.clone());
-    } else {
-        set_nvim_initialized(state_arc);
+    match nvim::post_start_init(nvim, cols as i64, rows as i64, options.input_data) {
+        Ok(channel) => set_nvim_initialized(state_arc, channel),
+        Err(err) => show_nvim_init_error(&err, state_arc.clone()),
     }
 }
 
@@ -1245,11 +1748,11 @@ fn set_nvim_to_state(state_arc: Arc<UiMutex<State>>, nvim: Neovim) -> NeovimClie
     started.take().unwrap()
 }
 
-fn set_nvim_initialized(state_arc: Arc<UiMutex<State>>) {
+fn set_nvim_initialized(state_arc: Arc<UiMutex<State>>, channel: u64) {
     glib::idle_add(clone!(state_arc => move || {
         let mut state = state_arc.borrow_mut();
         state.nvim.async_to_sync();
-        state.nvim.set_initialized();
+        state.nvim.set_initialized(channel);
         // in some case resize can happens while initilization in progress
         // so force resize here
         state.try_nvim_resize();
@@ -1284,11 +1787,9 @@ fn draw_initializing(state: &State, ctx: &cairo::Context) {
     pangocairo::functions::show_layout(ctx, &layout);
 
     ctx.move_to(x + width as f64, y);
-    state
-        .cursor
-        .as_ref()
-        .unwrap()
-        .draw(ctx, &render_state.font_ctx, y, false, &hl);
+    let cursor = state.cursor.as_ref().unwrap();
+    let (cursor_bg, _) = hl.cursor_colors(cursor.attr_id(), None);
+    cursor.draw(ctx, &render_state.font_ctx, y, false, &cursor_bg);
 }
 
 fn init_nvim(state_ref: &Arc<UiMutex<State>>) {
@@ -1316,7 +1817,7 @@ impl State {
     ) -> RepaintMode {
         let hl = &self.render_state.borrow().hl;
         let repaint_area = self.grids[grid].line(row as usize, col_start as usize, cells, hl);
-        RepaintMode::Area(repaint_area)
+        self.grid_area(grid, &repaint_area)
     }
 
     pub fn grid_clear(&mut self, grid: u64) -> RepaintMode {
@@ -1331,8 +1832,44 @@ impl State {
     }
 
     pub fn grid_cursor_goto(&mut self, grid: u64, row: u64, column: u64) -> RepaintMode {
-        let repaint_area = self.grids[grid].cursor_goto(row as usize, column as usize);
+        let break_ligatures = self
+            .render_state
+            .borrow()
+            .font_ctx
+            .break_ligatures_at_cursor();
+
+        // ligatures of previous cursor line are restored
+        let prev_line = if break_ligatures {
+            self.grids.mark_cur_line_dirty();
+            self.grids.cur_line()
+        } else {
+            None
+        };
+
+        let mut repaint_area = self
+            .grids
+            .cursor_goto(grid, row as usize, column as usize);
         self.set_im_location();
+
+        if break_ligatures {
+            self.grids.mark_cur_line_dirty();
+            for line in prev_line.iter().chain(self.grids.cur_line().iter()) {
+                repaint_area.join(line);
+            }
+        }
+
+        if let Some(cur_point) = self.grids.cur_point() {
+            let &CellMetrics {
+                line_height,
+                char_width,
+                ..
+            } = self.render_state.borrow().font_ctx.cell_metrics();
+            self.cursor.as_mut().unwrap().move_to(
+                &self.drawing_area,
+                cur_point.left as f64 * char_width,
+                cur_point.top as f64 * line_height,
+            );
+        }
         RepaintMode::AreaList(repaint_area)
     }
 
@@ -1351,7 +1888,14 @@ impl State {
             }
             RepaintMode::Area(ref rect) => self.queue_draw_area(&[rect]),
             RepaintMode::AreaList(ref list) => self.queue_draw_area(&list.list),
-            RepaintMode::Nothing => (),
+            RepaintMode::Nothing => return,
+        }
+
+        for tab_window in self.tab_windows.borrow_mut().iter_mut() {
+            if tab_window.is_current() {
+                tab_window.set_grids(self.grids.window_models(tab_window.windows()));
+                tab_window.queue_draw();
+            }
         }
     }
 
@@ -1365,16 +1909,132 @@ impl State {
         rows: i64,
         cols: i64,
     ) -> RepaintMode {
-        let hl = &self.render_state.borrow().hl;
-        RepaintMode::Area(self.grids[grid].scroll(
-            top,
-            bot,
-            left,
-            right,
+        if self.smooth_scroll.is_enabled() && cols == 0 && !self.grids[grid].is_float() {
+            self.start_smooth_scroll(grid, top, bot, left, right, rows);
+        }
+
+        let repaint_area = {
+            let hl = &self.render_state.borrow().hl;
+            self.grids[grid].scroll(top, bot, left, right, rows, cols, &hl.default_hl())
+        };
+        self.grid_area(grid, &repaint_area)
+    }
+
+    /// Save content of region before scroll, so it can be animated
+    fn start_smooth_scroll(
+        &mut self,
+        grid: u64,
+        top: u64,
+        bot: u64,
+        left: u64,
+        right: u64,
+        rows: i64,
+    ) {
+        if top >= bot || left >= right {
+            return;
+        }
+
+        let render_state = self.render_state.borrow();
+        let has_cursor = grid == self.grids.current_idx();
+        let model = self.grids[grid].model_mut();
+        render::shape_dirty(&render_state.font_ctx, model, has_cursor);
+
+        let rect = ModelRect::new(
+            top as usize,
+            bot as usize - 1,
+            left as usize,
+            right as usize - 1,
+        );
+        let bg_alpha = self.transparency_settings.filled_alpha();
+        self.smooth_scroll.grid_scroll(
+            grid,
+            rect,
             rows,
-            cols,
-            &hl.default_hl(),
-        ))
+            render_state.font_ctx.cell_metrics(),
+            f64::from(self.drawing_area.get_scale_factor()),
+            |ctx| {
+                render::fill_background(ctx, &render_state.hl, bg_alpha);
+                render::render(
+                    ctx,
+                    &EmptyCursor::new(),
+                    &render_state.font_ctx,
+                    model,
+                    &render_state.hl,
+                    bg_alpha,
+                );
+            },
+        );
+    }
+
+    pub fn win_pos(
+        &mut self,
+        grid: u64,
+        win: Value,
+        start_row: u64,
+        start_col: u64,
+        width: u64,
+        height: u64,
+    ) -> RepaintMode {
+        let grid_ref = self.grids.get_or_create(grid);
+        grid_ref.resize(width, height);
+        grid_ref.set_window(Window::new(win));
+        self.grids.win_pos(grid, start_row, start_col);
+        RepaintMode::All
+    }
+
+    pub fn win_float_pos(&mut self, grid: u64, pos: FloatPos) -> RepaintMode {
+        self.grids.win_float_pos(grid, pos);
+        RepaintMode::All
+    }
+
+    pub fn win_hide(&mut self, grid: u64) -> RepaintMode {
+        self.grids.hide(grid);
+        RepaintMode::All
+    }
+
+    pub fn win_close(&mut self, grid: u64) -> RepaintMode {
+        self.grids.destroy(grid);
+        RepaintMode::All
+    }
+
+    pub fn win_viewport(
+        &mut self,
+        grid: u64,
+        _: Value,
+        topline: u64,
+        _: u64,
+        _: u64,
+        _: u64,
+    ) -> RepaintMode {
+        let grid_ref = self.grids.get_or_create(grid);
+        if let Some(viewport) = grid_ref.viewport() {
+            if viewport.topline != topline {
+                let delta = topline as i64 - viewport.topline as i64;
+                self.smooth_scroll.viewport_changed(grid, delta);
+            }
+        }
+
+        grid_ref.set_viewport(Viewport { topline });
+        RepaintMode::Nothing
+    }
+
+    pub fn flush(&mut self) -> RepaintMode {
+        self.smooth_scroll.flush(&self.drawing_area);
+        RepaintMode::Nothing
+    }
+
+    pub fn msg_set_pos(&mut self, grid: u64, row: u64) -> RepaintMode {
+        self.grids.msg_set_pos(grid, row);
+        RepaintMode::All
+    }
+
+    /// Repaint area of grid local rect
+    fn grid_area(&self, grid: u64, rect: &ModelRect) -> RepaintMode {
+        if let Some(rect) = self.grids.to_screen(grid, rect) {
+            RepaintMode::Area(rect)
+        } else {
+            RepaintMode::Nothing
+        }
     }
 
     pub fn hl_attr_define(
@@ -1396,26 +2056,16 @@ impl State {
         cterm_fg: i64,
         cterm_bg: i64,
     ) -> RepaintMode {
-        self.render_state.borrow_mut().hl.set_defaults(
-            Color::from_indexed_color(fg as u64),
-            Color::from_indexed_color(bg as u64),
-            Color::from_indexed_color(sp as u64),
-            if cterm_fg > 0 {
-                Color::from_cterm((cterm_fg - 1) as u8)
-            } else {
-                COLOR_WHITE
-            },
-            if cterm_bg > 0 {
-                Color::from_cterm((cterm_bg - 1) as u8)
-            } else {
-                COLOR_BLACK
-            },
-        );
+        self.render_state
+            .borrow_mut()
+            .hl
+            .set_defaults(fg, bg, sp, cterm_fg, cterm_bg);
+        self.messages.update_css(&self.render_state.borrow().hl);
         RepaintMode::All
     }
 
     fn cur_point_area(&self) -> RepaintMode {
-        if let Some(cur_point) = self.grids.current().map(|g| g.cur_point()) {
+        if let Some(cur_point) = self.grids.cur_point() {
             RepaintMode::Area(cur_point)
         } else {
             RepaintMode::Nothing
@@ -1456,8 +2106,12 @@ impl State {
         selected: i64,
         row: u64,
         col: u64,
+        grid: Option<u64>,
     ) -> RepaintMode {
         let point = ModelRect::point(col as usize, row as usize);
+        let point = grid
+            .and_then(|grid| self.grids.to_screen(grid, &point))
+            .unwrap_or(point);
         let render_state = self.render_state.borrow();
         let (x, y, width, height) = point.to_area(render_state.font_ctx.cell_metrics());
 
@@ -1495,12 +2149,62 @@ impl State {
         tabs: Vec<(Tabpage, Option<String>)>,
     ) -> RepaintMode {
         self.tabs.update_tabs(&self.nvim, &selected, &tabs);
+        self.update_tab_windows(&selected, &tabs);
 
         RepaintMode::Nothing
     }
 
+    /// Close windows of closed tabpages, tabpage windows list can be changed too
+    fn update_tab_windows(&mut self, selected: &Tabpage, tabs: &[(Tabpage, Option<String>)]) {
+        if self.tab_windows.borrow().is_empty() {
+            return;
+        }
+
+        let closed: Vec<_> = {
+            let mut tab_windows = self.tab_windows.borrow_mut();
+            let (opened, closed): (Vec<_>, Vec<_>) = tab_windows
+                .drain(..)
+                .partition(|tab_window| tabs.iter().any(|(t, _)| t == tab_window.tabpage()));
+            *tab_windows = opened;
+            closed
+        };
+
+        // destroy handler borrows tab_windows, so it must be released here
+        for tab_window in closed {
+            tab_window.window().destroy();
+        }
+
+        if let Some(mut nvim) = self.nvim() {
+            for tab_window in self.tab_windows.borrow_mut().iter_mut() {
+                if let Some(windows) = tab_window.tabpage().list_wins(&mut nvim).ok_and_report() {
+                    tab_window.set_windows(windows);
+                }
+
+                let title = tabs
+                    .iter()
+                    .find(|(t, _)| t == tab_window.tabpage())
+                    .and_then(|(_, name)| name.as_ref());
+                let is_current = tab_window.tabpage() == selected;
+                tab_window.set_title(title.map(String::as_str), is_current);
+            }
+        }
+    }
+
     pub fn option_set(&mut self, name: String, val: Value) -> RepaintMode {
-        if let "guifont" = name.as_str() { self.set_font_from_value(val) };
+        match name.as_str() {
+            "guifont" => self.set_font_from_value(val),
+            "guifontwide" => {
+                if let Some(fonts) = val.as_str() {
+                    self.set_font_variant("wide".to_owned(), fonts.to_owned());
+                }
+            }
+            "pumblend" => {
+                if let Some(blend) = val.as_u64() {
+                    self.popup_menu.set_blend(blend);
+                }
+            }
+            _ => (),
+        }
         RepaintMode::Nothing
     }
 
@@ -1508,27 +2212,52 @@ impl State {
         if let Value::String(val) = val {
             if let Some(val) = val.into_str() {
                 if !val.is_empty() {
-                    let exists_fonts = self.render_state.borrow().font_ctx.font_families();
-                    let fonts = split_at_comma(&val);
-                    for font in &fonts {
-                        let desc = FontDescription::from_string(&font);
-                        if desc.get_size() > 0
-                            && exists_fonts.contains(&desc.get_family().unwrap_or_else(|| "".into()))
-                        {
-                            self.set_font_rpc(font);
-                            return;
-                        }
-                    }
-
-                    // font does not exists? set first one
-                    if !fonts.is_empty() {
-                        self.set_font_rpc(&fonts[0]);
-                    }
+                    self.set_font_list(&val);
                 }
             }
         }
     }
 
+    /// All installed fonts of comma separated list are used as fallback chain,
+    /// first one is primary. Current font size is kept if list has no size.
+    fn set_font_list(&mut self, fonts: &str) {
+        let (exists_fonts, current_size) = {
+            let font_ctx = &self.render_state.borrow().font_ctx;
+            (
+                font_ctx.font_families(),
+                font_ctx.font_description().get_size(),
+            )
+        };
+        let size = if current_size > 0 {
+            current_size
+        } else {
+            FontDescription::from_string(DEFAULT_FONT_NAME).get_size()
+        };
+
+        match render::fallback_font_desc(&split_at_comma(fonts), &exists_fonts, size) {
+            Some(desc) => self.set_font_rpc(&desc.to_string()),
+            None => error!("Font list is empty"),
+        }
+    }
+
+    /// 'guifontwide' and fonts for bold and italic highlights
+    pub fn set_font_variant(&mut self, variant: String, fonts: String) {
+        let variant = match render::FontVariant::parse(&variant) {
+            Some(variant) => variant,
+            None => {
+                error!("Unknown font variant {}", variant);
+                return;
+            }
+        };
+
+        self.render_state
+            .borrow_mut()
+            .font_ctx
+            .update_font_variant(variant, render::FontVariants::parse_desc(&fonts));
+        self.grids.clear_glyphs();
+        self.on_redraw(&RepaintMode::All);
+    }
+
     pub fn mode_info_set(
         &mut self,
         cursor_style_enabled: bool,
@@ -1564,7 +2293,7 @@ impl State {
         level: u64,
     ) -> RepaintMode {
         {
-            let cursor = self.grids.current().unwrap().cur_point();
+            let cursor = self.grids.cur_point().unwrap();
             let render_state = self.render_state.borrow();
             let (x, y, width, height) = cursor.to_area(render_state.font_ctx.cell_metrics());
             let ctx = CmdLineContext {
@@ -1636,11 +2365,170 @@ impl State {
         self.cmd_line.wildmenu_select(selected);
         RepaintMode::Nothing
     }
+
+    pub fn msg_show(
+        &mut self,
+        kind: String,
+        content: Vec<(u64, String)>,
+        replace_last: bool,
+    ) -> RepaintMode {
+        let render_state = self.render_state.borrow();
+        self.messages
+            .show(&kind, &content, replace_last, &render_state.hl);
+        RepaintMode::Nothing
+    }
+
+    pub fn msg_clear(&mut self) -> RepaintMode {
+        self.messages.clear();
+        RepaintMode::Nothing
+    }
+
+    pub fn msg_showmode(&mut self, content: Vec<(u64, String)>) -> RepaintMode {
+        self.messages
+            .show_mode(&content, &self.render_state.borrow().hl);
+        RepaintMode::Nothing
+    }
+
+    pub fn msg_showcmd(&mut self, content: Vec<(u64, String)>) -> RepaintMode {
+        self.messages
+            .show_cmd(&content, &self.render_state.borrow().hl);
+        RepaintMode::Nothing
+    }
+
+    pub fn msg_ruler(&mut self, content: Vec<(u64, String)>) -> RepaintMode {
+        self.messages
+            .ruler(&content, &self.render_state.borrow().hl);
+        RepaintMode::Nothing
+    }
+
+    pub fn msg_history_show(&mut self, entries: Vec<(String, Vec<(u64, String)>)>) -> RepaintMode {
+        self.messages
+            .show_history(&entries, &self.render_state.borrow().hl);
+        RepaintMode::Nothing
+    }
+}
+
+impl GridRedraw for State {
+    fn grid_line(
+        &mut self,
+        grid: u64,
+        row: u64,
+        col_start: u64,
+        cells: Vec<Vec<Value>>,
+    ) -> RepaintMode {
+        State::grid_line(self, grid, row, col_start, cells)
+    }
+
+    fn grid_clear(&mut self, grid: u64) -> RepaintMode {
+        State::grid_clear(self, grid)
+    }
+
+    fn grid_destroy(&mut self, grid: u64) -> RepaintMode {
+        State::grid_destroy(self, grid)
+    }
+
+    fn grid_cursor_goto(&mut self, grid: u64, row: u64, column: u64) -> RepaintMode {
+        State::grid_cursor_goto(self, grid, row, column)
+    }
+
+    fn grid_scroll(
+        &mut self,
+        grid: u64,
+        top: u64,
+        bot: u64,
+        left: u64,
+        right: u64,
+        rows: i64,
+        cols: i64,
+    ) -> RepaintMode {
+        State::grid_scroll(self, grid, top, bot, left, right, rows, cols)
+    }
+
+    fn grid_resize(&mut self, grid: u64, columns: u64, rows: u64) -> RepaintMode {
+        State::grid_resize(self, grid, columns, rows)
+    }
+
+    fn win_pos(
+        &mut self,
+        grid: u64,
+        win: Value,
+        start_row: u64,
+        start_col: u64,
+        width: u64,
+        height: u64,
+    ) -> RepaintMode {
+        State::win_pos(self, grid, win, start_row, start_col, width, height)
+    }
+
+    fn win_float_pos(&mut self, grid: u64, pos: FloatPos) -> RepaintMode {
+        State::win_float_pos(self, grid, pos)
+    }
+
+    fn win_hide(&mut self, grid: u64) -> RepaintMode {
+        State::win_hide(self, grid)
+    }
+
+    fn win_close(&mut self, grid: u64) -> RepaintMode {
+        State::win_close(self, grid)
+    }
+
+    fn win_viewport(
+        &mut self,
+        grid: u64,
+        win: Value,
+        topline: u64,
+        botline: u64,
+        curline: u64,
+        curcol: u64,
+    ) -> RepaintMode {
+        State::win_viewport(self, grid, win, topline, botline, curline, curcol)
+    }
+
+    fn msg_set_pos(&mut self, grid: u64, row: u64) -> RepaintMode {
+        State::msg_set_pos(self, grid, row)
+    }
+
+    fn flush(&mut self) -> RepaintMode {
+        State::flush(self)
+    }
+
+    fn default_colors_set(
+        &mut self,
+        fg: i64,
+        bg: i64,
+        sp: i64,
+        cterm_fg: i64,
+        cterm_bg: i64,
+    ) -> RepaintMode {
+        State::default_colors_set(self, fg, bg, sp, cterm_fg, cterm_bg)
+    }
+
+    fn hl_attr_define(
+        &mut self,
+        id: u64,
+        rgb_attr: HashMap<String, Value>,
+        cterm_attr: &Value,
+        info: Vec<HashMap<String, Value>>,
+    ) -> RepaintMode {
+        State::hl_attr_define(self, id, rgb_attr, cterm_attr, info)
+    }
+
+    fn on_mode_change(&mut self, mode: String, idx: u64) -> RepaintMode {
+        State::on_mode_change(self, mode, idx)
+    }
+
+    fn mode_info_set(
+        &mut self,
+        cursor_style_enabled: bool,
+        mode_infos: Vec<HashMap<String, Value>>,
+    ) -> RepaintMode {
+        State::mode_info_set(self, cursor_style_enabled, mode_infos)
+    }
 }
 
 impl CursorRedrawCb for State {
     fn queue_redraw_cursor(&mut self) {
-        if let Some(cur_point) = self.grids.current().map(|g| g.cur_point()) {
+        if let Some(cur_point) = self.grids.cur_point() {
             self.on_redraw(&RepaintMode::Area(cur_point));
         }
     }
@@ -105,3 +105,45 @@ fn get_changed_buffers(shell: &Shell) -> Result<Vec<String>, CallError> {
         Ok(vec![])
     }
 }
+
+/// Ask address of running nvim instance
+pub fn show_connect_dlg(parent: Option<&gtk::Window>) -> Option<String> {
+    let flags = gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT;
+    let dlg = gtk::Dialog::new_with_buttons(
+        Some("Connect to nvim"),
+        parent,
+        flags,
+        &[
+            ("_Cancel", gtk::ResponseType::Cancel),
+            ("_Connect", gtk::ResponseType::Ok),
+        ],
+    );
+    dlg.set_default_response(gtk::ResponseType::Ok);
+
+    let content = dlg.get_content_area();
+    content.set_spacing(6);
+    content.set_border_width(10);
+
+    let label = gtk::Label::new(Some("Unix socket path or host:port"));
+    label.set_halign(gtk::Align::Start);
+    content.pack_start(&label, false, true, 0);
+
+    let entry = gtk::Entry::new();
+    entry.set_activates_default(true);
+    entry.set_width_chars(40);
+    content.pack_start(&entry, false, true, 0);
+
+    content.show_all();
+
+    let address = match dlg.run() {
+        gtk::ResponseType::Ok => {
+            let address = entry.get_text().map(|t| t.trim().to_owned());
+            address.filter(|a| !a.is_empty())
+        }
+        _ => None,
+    };
+
+    dlg.destroy();
+
+    address
+}
@@ -0,0 +1,318 @@
+//! Smooth scrolling of window grids
+//!
+//! Before `grid_scroll` is applied to model, content of scrolled region is saved to surface.
+//! When `win_viewport` confirms that window was scrolled, saved content is moved out of region
+//! while new content moves in. Animation is driven by frame clock of drawing area.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use gtk::prelude::*;
+
+use crate::highlight::HighlightMap;
+use crate::render;
+use crate::render::CellMetrics;
+use crate::ui_model::ModelRect;
+
+const DEFAULT_DURATION_MS: u64 = 150;
+
+pub struct ScrollAnimation {
+    /// Content of region before scroll, in device pixels
+    snapshot: cairo::ImageSurface,
+    scale: f64,
+    rect: ModelRect,
+    rows: i64,
+    /// In microseconds, same as frame time
+    duration: i64,
+    start_time: Option<i64>,
+    progress: f64,
+}
+
+impl ScrollAnimation {
+    fn new<F: Fn(&cairo::Context)>(
+        rect: ModelRect,
+        rows: i64,
+        duration: i64,
+        cell_metrics: &CellMetrics,
+        scale: f64,
+        draw_content: F,
+    ) -> Option<Self> {
+        let (x, y, width, height) = rect.to_area(cell_metrics);
+        let snapshot = cairo::ImageSurface::create(
+            cairo::Format::ARgb32,
+            (f64::from(width) * scale).ceil() as i32,
+            (f64::from(height) * scale).ceil() as i32,
+        )
+        .ok()?;
+
+        {
+            let ctx = cairo::Context::new(&snapshot);
+            ctx.scale(scale, scale);
+            ctx.translate(-f64::from(x), -f64::from(y));
+            draw_content(&ctx);
+        }
+
+        Some(ScrollAnimation {
+            snapshot,
+            scale,
+            rect,
+            rows,
+            duration,
+            start_time: None,
+            progress: 0.0,
+        })
+    }
+
+    /// Returns false when animation is finished
+    fn tick(&mut self, frame_time: i64) -> bool {
+        let start_time = *self.start_time.get_or_insert(frame_time);
+        self.progress = if self.duration > 0 {
+            (frame_time - start_time) as f64 / self.duration as f64
+        } else {
+            1.0
+        };
+
+        self.progress < 1.0
+    }
+
+    /// Vertical offset of new content from its final position
+    fn offset(&self, line_height: f64) -> f64 {
+        let t = self.progress.max(0.0).min(1.0);
+        // ease out cubic, so animation starts fast and does not lag behind input
+        let eased = 1.0 - (1.0 - t).powi(3);
+        self.rows as f64 * line_height * (1.0 - eased)
+    }
+
+    fn draw<F: Fn(&cairo::Context)>(
+        &self,
+        ctx: &cairo::Context,
+        cell_metrics: &CellMetrics,
+        hl: &HighlightMap,
+        bg_alpha: Option<f64>,
+        draw_content: F,
+    ) {
+        let (x, y, width, height) = self.rect.to_area(cell_metrics);
+        let (x, y, width, height) = (
+            f64::from(x),
+            f64::from(y),
+            f64::from(width),
+            f64::from(height),
+        );
+        let line_height = cell_metrics.line_height;
+        let offset = self.offset(line_height);
+
+        ctx.save();
+        ctx.rectangle(x, y, width, height);
+        ctx.clip();
+
+        // previous content moves out of region in scroll direction
+        ctx.save();
+        ctx.translate(x, y + offset - self.rows as f64 * line_height);
+        ctx.scale(1.0 / self.scale, 1.0 / self.scale);
+        ctx.set_source_surface(&self.snapshot, 0.0, 0.0);
+        ctx.set_operator(cairo::Operator::Source);
+        ctx.paint();
+        ctx.restore();
+
+        // and new content follows it
+        if offset > 0.0 {
+            ctx.rectangle(x, y + offset, width, height - offset);
+        } else {
+            ctx.rectangle(x, y, width, height + offset);
+        }
+        ctx.clip();
+
+        ctx.set_operator(cairo::Operator::Source);
+        render::fill_background(ctx, hl, bg_alpha);
+        ctx.set_operator(cairo::Operator::Over);
+
+        ctx.translate(0.0, offset);
+        draw_content(ctx);
+
+        ctx.restore();
+    }
+}
+
+pub struct SmoothScroll {
+    enabled: bool,
+    duration_ms: u64,
+    /// Scrolls received in current redraw batch
+    pending: HashMap<u64, Option<ScrollAnimation>>,
+    /// Change of topline of grids in current redraw batch
+    scrolled_grids: HashMap<u64, i64>,
+    animations: Rc<RefCell<HashMap<u64, ScrollAnimation>>>,
+}
+
+impl SmoothScroll {
+    pub fn new() -> Self {
+        SmoothScroll {
+            enabled: false,
+            duration_ms: DEFAULT_DURATION_MS,
+            pending: HashMap::new(),
+            scrolled_grids: HashMap::new(),
+            animations: Rc::new(RefCell::new(HashMap::new())),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Toggle smooth scroll in case `enabled` is not given
+    pub fn set(&mut self, enabled: Option<bool>, duration_ms: Option<u64>) {
+        self.enabled = enabled.unwrap_or(!self.enabled);
+        if let Some(duration_ms) = duration_ms {
+            self.duration_ms = duration_ms;
+        }
+
+        if !self.enabled {
+            self.pending.clear();
+            self.scrolled_grids.clear();
+            self.animations.borrow_mut().clear();
+        }
+    }
+
+    /// Called before scroll is applied to grid model,
+    /// `draw_content` must draw grid content in current state
+    pub fn grid_scroll<F: Fn(&cairo::Context)>(
+        &mut self,
+        grid: u64,
+        rect: ModelRect,
+        rows: i64,
+        cell_metrics: &CellMetrics,
+        scale: f64,
+        draw_content: F,
+    ) {
+        if !is_animated(&rect, rows) {
+            self.pending.insert(grid, None);
+            return;
+        }
+
+        let duration = self.duration_ms as i64 * 1000;
+        match self.pending.get_mut(&grid) {
+            // snapshot was taken before first scroll of this batch,
+            // so it is still valid if same region is scrolled again
+            Some(Some(animation)) if animation.rect == rect => {
+                animation.rows += rows;
+                if !is_animated(&rect, animation.rows) {
+                    self.pending.insert(grid, None);
+                }
+            }
+            Some(_) => {
+                self.pending.insert(grid, None);
+            }
+            None => {
+                let animation =
+                    ScrollAnimation::new(rect, rows, duration, cell_metrics, scale, draw_content);
+                self.pending.insert(grid, animation);
+            }
+        }
+    }
+
+    /// `delta` is change of first buffer line shown in window
+    pub fn viewport_changed(&mut self, grid: u64, delta: i64) {
+        *self.scrolled_grids.entry(grid).or_insert(0) += delta;
+    }
+
+    /// End of redraw batch, starts animation of scrolls confirmed by viewport change in the
+    /// same direction. Other scrolls (e.g. line insert) are applied without animation.
+    pub fn flush(&mut self, drawing_area: &gtk::DrawingArea) {
+        let mut animations = self.animations.borrow_mut();
+        let was_running = !animations.is_empty();
+
+        for (grid, animation) in self.pending.drain() {
+            match animation {
+                Some(animation) if is_confirmed(&self.scrolled_grids, grid, animation.rows) => {
+                    animations.insert(grid, animation);
+                }
+                _ => {
+                    animations.remove(&grid);
+                }
+            }
+        }
+        self.scrolled_grids.clear();
+
+        if !was_running && !animations.is_empty() {
+            let animations = self.animations.clone();
+            drawing_area.add_tick_callback(move |drawing_area, frame_clock| {
+                let frame_time = frame_clock.get_frame_time();
+                let mut animations = animations.borrow_mut();
+                animations.retain(|_, animation| animation.tick(frame_time));
+                drawing_area.queue_draw();
+                Continue(!animations.is_empty())
+            });
+        }
+    }
+
+    /// Draw scrolled region of grid over already drawn content,
+    /// `draw_content` must draw grid content in current state
+    pub fn draw<F: Fn(&cairo::Context)>(
+        &self,
+        grid: u64,
+        ctx: &cairo::Context,
+        cell_metrics: &CellMetrics,
+        hl: &HighlightMap,
+        bg_alpha: Option<f64>,
+        draw_content: F,
+    ) {
+        if let Some(animation) = self.animations.borrow().get(&grid) {
+            animation.draw(ctx, cell_metrics, hl, bg_alpha, draw_content);
+        }
+    }
+}
+
+/// Content scrolls up (positive rows) when first line of window moves down the buffer
+fn is_confirmed(scrolled_grids: &HashMap<u64, i64>, grid: u64, rows: i64) -> bool {
+    scrolled_grids
+        .get(&grid)
+        .map_or(false, |&delta| delta.signum() == rows.signum())
+}
+
+/// Scroll of whole region content can't be animated, it is redrawn anyway
+fn is_animated(rect: &ModelRect, rows: i64) -> bool {
+    rows != 0
+        && rows
+            .checked_abs()
+            .map_or(false, |rows| rows as u64 <= (rect.bot - rect.top) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_animation_offset() {
+        let metrics = CellMetrics::new_hw(10.0, 5.0);
+        let mut animation = ScrollAnimation::new(
+            ModelRect::new(0, 9, 0, 9),
+            3,
+            100_000,
+            &metrics,
+            1.0,
+            |_| {},
+        )
+        .unwrap();
+
+        assert!(animation.tick(1_000_000));
+        assert_eq!(30.0, animation.offset(metrics.line_height));
+
+        assert!(animation.tick(1_050_000));
+        let offset = animation.offset(metrics.line_height);
+        assert!(offset > 0.0 && offset < 15.0);
+
+        assert!(!animation.tick(1_100_000));
+        assert_eq!(0.0, animation.offset(metrics.line_height));
+    }
+
+    #[test]
+    fn test_is_confirmed() {
+        let mut scrolled_grids = HashMap::new();
+        scrolled_grids.insert(2, 3);
+
+        assert!(is_confirmed(&scrolled_grids, 2, 1));
+        // viewport moved down, but content scrolled down, e.g. lines inserted
+        assert!(!is_confirmed(&scrolled_grids, 2, -1));
+        assert!(!is_confirmed(&scrolled_grids, 3, 1));
+    }
+}
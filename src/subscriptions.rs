@@ -109,7 +109,7 @@ impl Subscriptions {
     /// Register all subscriptions with Neovim.
     ///
     /// This function is wrapped by `shell::State`.
-    pub fn set_autocmds(&self, nvim: &mut NeovimRef) {
+    pub fn set_autocmds(&self, nvim: &mut NeovimRef, channel: u64) {
         for (key, subscriptions) in &self.0 {
             let SubscriptionKey {
                 event_name,
@@ -121,8 +121,8 @@ impl Subscriptions {
                     .iter()
                     .fold("".to_owned(), |acc, arg| acc + ", " + &arg);
                 let autocmd = format!(
-                    "autocmd {} {} call rpcnotify(1, 'subscription', '{}', '{}', {} {})",
-                    event_name, pattern, event_name, pattern, i, args,
+                    "autocmd {} {} call rpcnotify({}, 'subscription', '{}', '{}', {} {})",
+                    event_name, pattern, channel, event_name, pattern, i, args,
                 );
                 nvim.command_async(&autocmd).cb(|r| r.report_err()).call();
             }
@@ -0,0 +1,6 @@
+use cairo_sys as ffi;
+
+/// Version of cairo library in use, encoded as `major * 10000 + minor * 100 + micro`
+pub fn version() -> i32 {
+    unsafe { ffi::cairo_version() }
+}
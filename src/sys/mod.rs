@@ -1,3 +1,3 @@
-
+pub mod cairo;
 pub mod pango;
 pub mod pangocairo;
@@ -10,3 +10,7 @@ pub fn new_features(features: &str) -> Option<pango::Attribute> {
         ))
     }
 }
+
+pub fn new_font_desc(font_desc: &pango::FontDescription) -> Option<pango::Attribute> {
+    unsafe { from_glib_full(pango_sys::pango_attr_font_desc_new(font_desc.to_glib_none().0)) }
+}
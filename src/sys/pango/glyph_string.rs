@@ -0,0 +1,17 @@
+use std::slice;
+
+use pango;
+use pango_sys;
+
+use glib::translate::*;
+
+/// Glyphs of glyph string, pango-rs has no accessor for them
+pub fn glyph_infos(glyphs: &pango::GlyphString) -> &[pango_sys::PangoGlyphInfo] {
+    unsafe {
+        let glyph_string: *const pango_sys::PangoGlyphString = glyphs.to_glib_none().0;
+        if (*glyph_string).num_glyphs <= 0 || (*glyph_string).glyphs.is_null() {
+            return &[];
+        }
+        slice::from_raw_parts((*glyph_string).glyphs, (*glyph_string).num_glyphs as usize)
+    }
+}
@@ -1,2 +1,2 @@
 pub mod attribute;
-
+pub mod glyph_string;
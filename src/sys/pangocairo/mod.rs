@@ -14,3 +14,14 @@ pub fn show_glyph_string(cr: &cairo::Context, font: &pango::Font, glyphs: &pango
         );
     }
 }
+
+/// Same as `pangocairo::FontExt::get_scaled_font`, but does not take ownership
+/// of returned font, it is owned by pango font
+pub fn font_get_scaled_font(font: &pango::Font) -> Option<cairo::ScaledFont> {
+    unsafe {
+        from_glib_none(ffi::pango_cairo_font_get_scaled_font(
+            ToGlibPtr::<*mut pango_sys::PangoFont>::to_glib_none(font).0
+                as *mut ffi::PangoCairoFont,
+        ))
+    }
+}
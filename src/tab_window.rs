@@ -0,0 +1,93 @@
+use gtk::prelude::*;
+
+use neovim_lib::neovim_api::{Tabpage, Window};
+
+use crate::ui_model::UiModel;
+
+/// Top level read only window that mirrors tabpage dragged out of tabline
+///
+/// Nvim draws only windows of current tabpage, so the mirror keeps own copy of grids of its
+/// tabpage, that is refreshed only while the tabpage is current in the main window. Otherwise
+/// it shows the last copied content, which is marked in window title. Input goes to the main
+/// window.
+pub struct TabWindow {
+    tabpage: Tabpage,
+    windows: Vec<Window>,
+    grids: Vec<(usize, usize, UiModel)>,
+    is_current: bool,
+    window: gtk::Window,
+    drawing_area: gtk::DrawingArea,
+}
+
+impl TabWindow {
+    pub fn new(tabpage: Tabpage, windows: Vec<Window>, width: i32, height: i32) -> Self {
+        let window = gtk::Window::new(gtk::WindowType::Toplevel);
+        window.set_default_size(width, height);
+
+        let drawing_area = gtk::DrawingArea::new();
+        window.add(&drawing_area);
+
+        TabWindow {
+            tabpage,
+            windows,
+            grids: Vec::new(),
+            is_current: true,
+            window,
+            drawing_area,
+        }
+    }
+
+    pub fn show(&self) {
+        self.window.show_all();
+    }
+
+    pub fn tabpage(&self) -> &Tabpage {
+        &self.tabpage
+    }
+
+    /// Nvim windows of tabpage
+    pub fn windows(&self) -> &[Window] {
+        &self.windows
+    }
+
+    pub fn set_windows(&mut self, windows: Vec<Window>) {
+        self.windows = windows;
+    }
+
+    /// Copy of window grids with their (row, col) position
+    pub fn grids(&self) -> &[(usize, usize, UiModel)] {
+        &self.grids
+    }
+
+    pub fn set_grids(&mut self, grids: Vec<(usize, usize, UiModel)>) {
+        self.grids = grids;
+    }
+
+    /// `true` if tabpage is current in the main window, so grids can be refreshed
+    pub fn is_current(&self) -> bool {
+        self.is_current
+    }
+
+    /// Title of tabpage, `is_current` is `false` if window content is not updated
+    pub fn set_title(&mut self, title: Option<&str>, is_current: bool) {
+        self.is_current = is_current;
+        let title = title.unwrap_or("??");
+        if is_current {
+            self.window.set_title(title);
+        } else {
+            self.window.set_title(&format!("{} (not current)", title));
+        }
+    }
+
+    pub fn window(&self) -> &gtk::Window {
+        &self.window
+    }
+
+    pub fn drawing_area(&self) -> &gtk::DrawingArea {
+        &self.drawing_area
+    }
+
+    pub fn queue_draw(&self) {
+        self.drawing_area.queue_draw();
+    }
+}
@@ -16,10 +16,13 @@ use neovim_lib::neovim_api::Tabpage;
 use crate::nvim;
 use crate::nvim::ErrorReport;
 
+type MirrorCb = Box<dyn Fn(&Tabpage, Option<&str>)>;
+
 struct State {
-    data: Vec<Tabpage>,
+    data: Vec<(Tabpage, Option<String>)>,
     selected: Option<Tabpage>,
     nvim: Option<Rc<nvim::NeovimClient>>,
+    mirror_cb: Option<MirrorCb>,
 }
 
 impl State {
@@ -28,11 +31,12 @@ impl State {
             data: Vec::new(),
             selected: None,
             nvim: None,
+            mirror_cb: None,
         }
     }
 
     fn switch_page(&self, idx: u32) {
-        let target = &self.data[idx as usize];
+        let target = &self.data[idx as usize].0;
         if Some(target) != self.selected.as_ref() {
             if let Some(mut nvim) = self.nvim.as_ref().unwrap().nvim() {
                 nvim.set_current_tabpage(target).report_err();
@@ -40,6 +44,13 @@ impl State {
         }
     }
 
+    fn mirror_tab(&self, idx: u32) {
+        if let Some(ref cb) = self.mirror_cb {
+            let (ref tabpage, ref name) = self.data[idx as usize];
+            cb(tabpage, name.as_ref().map(String::as_str));
+        }
+    }
+
     fn close_tab(&self, idx: u32) {
         if let Some(mut nvim) = self.nvim.as_ref().unwrap().nvim() {
             nvim.command_async(&format!(":tabc {}", idx + 1))
@@ -72,6 +83,20 @@ impl Tabline {
         let switch_handler_id =
             tabs.connect_switch_page(move |_, _, idx| state_ref.borrow().switch_page(idx));
 
+        // tab dropped outside of notebook, notebook itself must not move page
+        // as it only mirrors nvim tabpages
+        let state_ref = state.clone();
+        tabs.connect_drag_failed(move |tabs, _, result| {
+            if result != gtk::DragResult::NoTarget {
+                return Inhibit(false);
+            }
+
+            if let Some(idx) = tabs.get_current_page() {
+                state_ref.borrow().mirror_tab(idx);
+            }
+            Inhibit(true)
+        });
+
         Tabline {
             tabs,
             state,
@@ -93,7 +118,12 @@ impl Tabline {
 
         state.selected = Some(selected.clone());
 
-        state.data = tabs.iter().map(|item| item.0.clone()).collect();
+        state.data = tabs.to_vec();
+    }
+
+    /// Called with tabpage and its name when tab is dragged out of tabline
+    pub fn set_mirror_cb<F: Fn(&Tabpage, Option<&str>) + 'static>(&self, cb: Option<F>) {
+        self.state.borrow_mut().mirror_cb = cb.map(|cb| Box::new(cb) as MirrorCb);
     }
 
     pub fn update_tabs(
@@ -136,6 +166,7 @@ impl Tabline {
                 close_btn.show();
                 self.tabs.append_page(&empty, Some(&label_box));
                 self.tabs.set_child_tab_expand(&empty, true);
+                self.tabs.set_tab_detachable(&empty, true);
 
                 let tabs = self.tabs.clone();
                 let state_ref = Rc::clone(&self.state);
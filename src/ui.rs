@@ -14,13 +14,16 @@ use gtk::{AboutDialog, ApplicationWindow, Button, HeaderBar, Orientation, Paned,
 
 use toml;
 
-use neovim_lib::NeovimApi;
+use neovim_lib::{NeovimApi, NeovimApiAsync, Value};
 
 use crate::file_browser::FileBrowserWidget;
+use crate::file_chooser;
+use crate::file_finder::FileFinder;
 use crate::misc;
-use crate::nvim::{ErrorReport, NvimCommand};
+use crate::nvim::{ErrorReport, GuiApiCall, GuiApiError, GuiApiReply, NvimCommand};
 use crate::plug_manager;
 use crate::project::Projects;
+use crate::session::{self, Session};
 use crate::settings::{Settings, SettingsLoader};
 use crate::shell::{self, Shell, ShellOptions};
 use crate::shell_dlg;
@@ -54,13 +57,16 @@ pub struct Ui {
     settings: Rc<RefCell<Settings>>,
     shell: Rc<RefCell<Shell>>,
     projects: Arc<UiMutex<Projects>>,
+    file_finder: Arc<UiMutex<FileFinder>>,
     plug_manager: Arc<UiMutex<plug_manager::Manager>>,
     file_browser: Arc<UiMutex<FileBrowserWidget>>,
 }
 
 pub struct Components {
     window: Option<ApplicationWindow>,
+    paned: Option<Paned>,
     window_state: WindowState,
+    is_fullscreen: bool,
     open_btn: Button,
 }
 
@@ -80,7 +86,9 @@ impl Components {
         Components {
             open_btn,
             window: None,
+            paned: None,
             window_state: WindowState::load(),
+            is_fullscreen: false,
         }
     }
 
@@ -105,6 +113,7 @@ impl Ui {
         settings.borrow_mut().set_shell(Rc::downgrade(&shell));
 
         let projects = Projects::new(&comps.borrow().open_btn, shell.clone());
+        let file_finder = FileFinder::new(&shell.borrow(), shell.clone());
 
         Ui {
             initialized: false,
@@ -112,12 +121,21 @@ impl Ui {
             shell,
             settings,
             projects,
+            file_finder,
             plug_manager,
             file_browser,
             open_paths,
         }
     }
 
+    pub fn shell(&self) -> Rc<RefCell<Shell>> {
+        self.shell.clone()
+    }
+
+    pub fn window(&self) -> ApplicationWindow {
+        self.comps.borrow().window().clone()
+    }
+
     pub fn init(&mut self, app: &gtk::Application, restore_win_state: bool) {
         if self.initialized {
             return;
@@ -140,6 +158,7 @@ impl Ui {
             self.shell.borrow_mut().init();
 
             comps.window = Some(window.clone());
+            comps.paned = Some(main.clone());
 
             let prefer_dark_theme = env::var("NVIM_GTK_PREFER_DARK_THEME")
                 .map(|opt| opt.trim() == "1")
@@ -216,8 +235,10 @@ impl Ui {
         });
 
         let comps_ref = self.comps.clone();
+        let state_ref = self.shell.borrow().state.clone();
         window.connect_destroy(move |_| {
             comps_ref.borrow().window_state.save();
+            state_ref.borrow().release_buffer_waiters();
         });
 
         let shell = self.shell.borrow();
@@ -250,6 +271,13 @@ impl Ui {
             move |args| set_completeopts(&*shell_ref, args),
         );
 
+        let state_ref = self.shell.borrow().state.clone();
+        shell.state.borrow().subscribe(
+            SubscriptionKey::from("BufDelete"),
+            &["expand('<afile>:p')"],
+            move |args| state_ref.borrow().on_buffer_delete(&args[0]),
+        );
+
         let comps_ref = self.comps.clone();
         let shell_ref = self.shell.clone();
         window.connect_delete_event(move |_, _| gtk_delete(&*comps_ref, &*shell_ref));
@@ -280,14 +308,44 @@ impl Ui {
                 &update_subtitle,
                 &update_completeopt,
             );
+
+            // restore session of current dir, if any, when started without files
+            if files_list.is_empty() && !state_ref.borrow().is_remote() {
+                state_ref
+                    .borrow_mut()
+                    .on_command(NvimCommand::SessionLoad(None));
+            }
+        }));
+
+        let sidebar_action_ref = UiMutex::new(show_sidebar_action.clone());
+        let comps_ref = self.comps.clone();
+        let file_browser_ref = self.file_browser.clone();
+        shell.set_gui_api_cb(Some(move |call, reply| {
+            gui_api_call(
+                call,
+                reply,
+                &comps_ref,
+                &file_browser_ref,
+                &sidebar_action_ref,
+            )
         }));
 
         let sidebar_action = UiMutex::new(show_sidebar_action);
         let comps_ref = self.comps.clone();
         let projects = self.projects.clone();
+        let file_finder = self.file_finder.clone();
+        let file_browser_ref = self.file_browser.clone();
         shell.set_nvim_command_cb(Some(
             move |shell: &mut shell::State, command: NvimCommand| {
-                Ui::nvim_command(shell, command, &sidebar_action, &projects, &comps_ref);
+                Ui::nvim_command(
+                    shell,
+                    command,
+                    &sidebar_action,
+                    &projects,
+                    &file_finder,
+                    &comps_ref,
+                    &file_browser_ref,
+                );
             },
         ));
     }
@@ -330,7 +388,9 @@ impl Ui {
         command: NvimCommand,
         sidebar_action: &UiMutex<SimpleAction>,
         projects: &Arc<UiMutex<Projects>>,
+        file_finder: &Arc<UiMutex<FileFinder>>,
         comps: &UiMutex<Components>,
+        file_browser: &UiMutex<FileBrowserWidget>,
     ) {
         match command {
             NvimCommand::ShowProjectView => {
@@ -339,6 +399,13 @@ impl Ui {
                     Continue(false)
                 }));
             }
+            NvimCommand::ShowFileFinder => {
+                let ignore_patterns = file_browser.borrow().ignore_patterns();
+                gtk::idle_add(clone!(file_finder => move || {
+                    FileFinder::show(&file_finder, &ignore_patterns);
+                    Continue(false)
+                }));
+            }
             NvimCommand::ToggleSidebar => {
                 let action = sidebar_action.borrow();
                 let state = !bool::from_variant(&action.get_state().unwrap()).unwrap();
@@ -364,6 +431,19 @@ impl Ui {
                     settings.set_property_gtk_application_prefer_dark_theme(prefer_dark_theme);
                 }
             }
+            NvimCommand::SessionSave => {
+                save_session(shell, &comps.borrow(), &file_browser.borrow(), false)
+            }
+            NvimCommand::SessionUpdate => {
+                save_session(shell, &comps.borrow(), &file_browser.borrow(), true)
+            }
+            NvimCommand::SessionLoad(project) => restore_session(
+                shell,
+                comps,
+                &file_browser.borrow(),
+                &sidebar_action.borrow(),
+                project,
+            ),
         }
     }
 
@@ -437,6 +517,7 @@ impl Ui {
 
         let section = Menu::new();
         section.append_item(&MenuItem::new(Some("New Window"), Some("app.new-window")));
+        section.append_item(&MenuItem::new(Some("Connect to…"), Some("app.connect")));
         menu.append_section(None, &section);
 
         let section = Menu::new();
@@ -485,7 +566,20 @@ fn gtk_delete(comps: &UiMutex<Components>, shell: &RefCell<Shell>) -> Inhibit {
         return Inhibit(false);
     }
 
+    // buffers stays alive on remote instance, so no need to ask to save them
+    if shell.borrow().is_remote() {
+        comps.borrow().close_window();
+        shell.borrow_mut().detach_ui();
+        return Inhibit(false);
+    }
+
     Inhibit(if shell_dlg::can_close_window(comps, shell) {
+        shell
+            .borrow()
+            .state
+            .borrow_mut()
+            .on_command(NvimCommand::SessionUpdate);
+
         let comps = comps.borrow();
         comps.close_window();
         shell.borrow_mut().detach_ui();
@@ -511,9 +605,201 @@ fn gtk_window_size_allocate(
 }
 
 fn gtk_window_state_event(event: &gdk::EventWindowState, comps: &mut Components) {
-    comps.window_state.is_maximized = event
-        .get_new_window_state()
-        .contains(gdk::WindowState::MAXIMIZED);
+    let window_state = event.get_new_window_state();
+    comps.window_state.is_maximized = window_state.contains(gdk::WindowState::MAXIMIZED);
+    comps.is_fullscreen = window_state.contains(gdk::WindowState::FULLSCREEN);
+}
+
+fn get_current_dir(shell: &shell::State) -> Option<String> {
+    let mut nvim = shell.nvim()?;
+    nvim.eval("getcwd()")
+        .ok_and_report()
+        .and_then(|cwd| cwd.as_str().map(str::to_owned))
+}
+
+/// Save nvim session together with GUI state for current dir
+///
+/// With `only_existing` session is saved only if it was saved before for this dir
+fn save_session(
+    shell: &shell::State,
+    comps: &Components,
+    file_browser: &FileBrowserWidget,
+    only_existing: bool,
+) {
+    let project = match get_current_dir(shell) {
+        Some(project) => project,
+        None => return,
+    };
+
+    if only_existing && session::find(&project).is_none() {
+        return;
+    }
+
+    let session = Session {
+        project,
+        width: comps.window_state.current_width,
+        height: comps.window_state.current_height,
+        is_maximized: comps.window_state.is_maximized,
+        show_sidebar: comps.window_state.show_sidebar,
+        sidebar_width: comps.window_state.sidebar_width,
+        expanded_dirs: file_browser.expanded_dirs(),
+        font: Some(shell.font_desc()),
+    };
+
+    let session_file = match session.session_file() {
+        Ok(session_file) => session_file,
+        Err(err) => {
+            error!("Can't save session: {}", err);
+            return;
+        }
+    };
+
+    if let Some(mut nvim) = shell.nvim() {
+        let command = format!(
+            "mksession! {}",
+            misc::escape_filename(&session_file.to_string_lossy())
+        );
+        if nvim.command(&command).ok_and_report().is_some() {
+            session::store(session);
+        }
+    }
+}
+
+/// Restore session of given project, current dir is used if project is not set
+fn restore_session(
+    shell: &mut shell::State,
+    comps: &UiMutex<Components>,
+    file_browser: &FileBrowserWidget,
+    sidebar_action: &SimpleAction,
+    project: Option<String>,
+) {
+    let project = match project.or_else(|| get_current_dir(shell)) {
+        Some(project) => project,
+        None => return,
+    };
+
+    let session = match session::find(&project) {
+        Some(session) => session,
+        None => {
+            info!("No session saved for {}", project);
+            return;
+        }
+    };
+
+    // sidebar action handler borrows components itself
+    sidebar_action.change_state(&session.show_sidebar.to_variant());
+
+    {
+        let comps = comps.borrow();
+        let window = comps.window();
+        if session.is_maximized {
+            window.maximize();
+        } else {
+            window.unmaximize();
+            window.resize(session.width, session.height);
+        }
+
+        if let Some(ref paned) = comps.paned {
+            paned.set_position(session.sidebar_width);
+        }
+    }
+
+    if let Some(ref font) = session.font {
+        shell.set_font_rpc(font);
+    }
+
+    file_browser.restore_expanded_dirs(&session.project, session.expanded_dirs.clone());
+
+    match session.session_file() {
+        Ok(ref session_file) if session_file.exists() => {
+            if let Some(mut nvim) = shell.nvim() {
+                let command = format!(
+                    "source {}",
+                    misc::escape_filename(&session_file.to_string_lossy())
+                );
+                nvim.command_async(&command).cb(|r| r.report_err()).call();
+            }
+        }
+        Ok(_) => (),
+        Err(err) => error!("Can't restore session: {}", err),
+    }
+}
+
+/// Executes call of plugin GUI api, see `nvim::GuiApiCall`
+fn gui_api_call(
+    call: GuiApiCall,
+    reply: GuiApiReply,
+    comps: &UiMutex<Components>,
+    file_browser: &UiMutex<FileBrowserWidget>,
+    sidebar_action: &UiMutex<SimpleAction>,
+) {
+    let result = match call {
+        GuiApiCall::SidebarGet => Ok(comps.borrow().window_state.show_sidebar.into()),
+        GuiApiCall::SidebarSet(show) => {
+            // sidebar action handler borrows components itself
+            sidebar_action.borrow().change_state(&show.to_variant());
+            Ok(Value::Nil)
+        }
+        GuiApiCall::FileBrowserReveal(path) => {
+            if file_browser.borrow().reveal_path(&path) {
+                Ok(Value::Nil)
+            } else {
+                Err(GuiApiError::Failed(format!(
+                    "Path {} not found in file browser",
+                    path
+                )))
+            }
+        }
+        GuiApiCall::FileBrowserIgnoreGet => Ok(Value::Array(
+            file_browser
+                .borrow()
+                .ignore_patterns()
+                .into_iter()
+                .map(Value::from)
+                .collect(),
+        )),
+        GuiApiCall::FileBrowserIgnoreSet(patterns) => {
+            file_browser.borrow().set_ignore_patterns(patterns);
+            Ok(Value::Nil)
+        }
+        GuiApiCall::TitleSet(title) => {
+            comps.borrow().window().set_title(&title);
+            Ok(Value::Nil)
+        }
+        GuiApiCall::FullscreenGet => Ok(comps.borrow().is_fullscreen.into()),
+        GuiApiCall::FullscreenSet(fullscreen) => {
+            let comps = comps.borrow();
+            if fullscreen {
+                comps.window().fullscreen();
+            } else {
+                comps.window().unfullscreen();
+            }
+            Ok(Value::Nil)
+        }
+        GuiApiCall::Notify { summary, body } => {
+            match comps.borrow().window().get_application() {
+                Some(app) => {
+                    let notification = gio::Notification::new(&summary);
+                    if let Some(ref body) = body {
+                        notification.set_body(Some(body));
+                    }
+                    app.send_notification(None, &notification);
+                    Ok(Value::Nil)
+                }
+                None => Err(GuiApiError::Failed("Window has no application".to_owned())),
+            }
+        }
+        GuiApiCall::FileChooser(args) => {
+            let window = comps.borrow().window().clone();
+            file_chooser::show(Some(window.upcast_ref()), args, move |paths| {
+                reply(Ok(Value::Array(
+                    paths.into_iter().map(Value::from).collect(),
+                )))
+            });
+            return;
+        }
+    };
+    reply(result);
 }
 
 fn set_completeopts(shell: &RefCell<Shell>, args: Vec<String>) {
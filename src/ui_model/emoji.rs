@@ -0,0 +1,86 @@
+//! Width of grapheme clusters with emoji
+//!
+//! `unicode-width` measures single code points, so sequences like `❤️` (text
+//! character with emoji presentation selector) or `👩‍💻` (ZWJ sequence) get wrong
+//! width. Terminals and nvim display such clusters as one double width cell.
+
+use unicode_width::UnicodeWidthStr;
+
+const ZWJ: char = '\u{200D}';
+const EMOJI_PRESENTATION: char = '\u{FE0F}';
+const TEXT_PRESENTATION: char = '\u{FE0E}';
+
+/// Number of cells taken by grapheme cluster, at least one
+pub fn cell_width(grapheme: &str) -> usize {
+    if is_emoji(grapheme) {
+        2
+    } else {
+        grapheme.width().max(1)
+    }
+}
+
+/// Check if grapheme cluster is displayed as emoji
+pub fn is_emoji(grapheme: &str) -> bool {
+    let mut chars = grapheme.chars();
+    let first = match chars.next() {
+        Some(first) => first,
+        None => return false,
+    };
+
+    let mut has_emoji_modifier = false;
+    for ch in chars {
+        match ch {
+            TEXT_PRESENTATION => return false,
+            EMOJI_PRESENTATION | ZWJ => has_emoji_modifier = true,
+            _ => (),
+        }
+    }
+
+    has_emoji_modifier || is_emoji_char(first)
+}
+
+/// Code points with default emoji presentation, only blocks used by emoji are checked
+fn is_emoji_char(ch: char) -> bool {
+    match ch as u32 {
+        // mahjong tile, playing card, squared and enclosed ideographs
+        0x1F004
+            | 0x1F0CF
+            | 0x1F18E
+            | 0x1F191..=0x1F19A
+            // regional indicators, pairs of them are flags
+            | 0x1F1E6..=0x1F1FF
+            | 0x1F201
+            | 0x1F21A
+            | 0x1F22F
+            | 0x1F232..=0x1F23A
+            | 0x1F250..=0x1F251
+            // misc symbols and pictographs, emoticons, transport and map,
+            // geometric shapes extended, supplemental symbols, symbols extended-A
+            | 0x1F300..=0x1F6FF
+            | 0x1F7E0..=0x1F7EB
+            | 0x1F90C..=0x1F9FF
+            | 0x1FA70..=0x1FAFF => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cell_width() {
+        assert_eq!(1, cell_width("a"));
+        assert_eq!(1, cell_width(""));
+        assert_eq!(2, cell_width("あ"));
+        assert_eq!(2, cell_width("😀"));
+        // heart with emoji presentation
+        assert_eq!(2, cell_width("\u{2764}\u{FE0F}"));
+        // woman technologist, ZWJ sequence
+        assert_eq!(2, cell_width("\u{1F469}\u{200D}\u{1F4BB}"));
+        // flag, regional indicators pair
+        assert_eq!(2, cell_width("\u{1F1FA}\u{1F1E6}"));
+        // text presentation
+        assert_eq!(1, cell_width("\u{2764}\u{FE0E}"));
+    }
+}
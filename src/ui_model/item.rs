@@ -8,6 +8,8 @@ pub struct Item {
     pub cells_count: usize,
     pub glyphs: Option<pango::GlyphString>,
     pub ink_overflow: Option<InkOverflow>,
+    /// Emoji glyphs that don't fit cells are drawn scaled down
+    pub glyph_scale: Option<GlyphScale>,
     font: pango::Font,
 }
 
@@ -21,6 +23,7 @@ impl Item {
             cells_count,
             glyphs: None,
             ink_overflow: None,
+            glyph_scale: None,
         }
     }
 
@@ -29,11 +32,25 @@ impl Item {
         self.item = item;
         self.glyphs = None;
         self.ink_overflow = None;
+        self.glyph_scale = None;
     }
 
-    pub fn set_glyphs(&mut self, ctx: &render::Context, glyphs: pango::GlyphString) {
+    /// In case `fit_cells` is given glyphs are scaled down to this number of cells
+    pub fn set_glyphs(
+        &mut self,
+        ctx: &render::Context,
+        glyphs: pango::GlyphString,
+        fit_cells: Option<usize>,
+    ) {
         let mut glyphs = glyphs;
-        let (ink_rect, _) = glyphs.extents(&self.font);
+        let (mut ink_rect, logical_rect) = glyphs.extents(&self.font);
+
+        self.glyph_scale = fit_cells
+            .and_then(|fit_cells| GlyphScale::fit(ctx, &ink_rect, &logical_rect, fit_cells as i32));
+        if let Some(ref glyph_scale) = self.glyph_scale {
+            ink_rect = glyph_scale.transform(ctx, &ink_rect);
+        }
+
         self.ink_overflow = InkOverflow::from(ctx, &ink_rect, self.cells_count as i32);
         self.glyphs = Some(glyphs);
     }
@@ -78,7 +95,8 @@ impl InkOverflow {
 
         let left = if ink_rect.x < 0 { ink_rect.x.abs() } else { 0 };
 
-        let mut right = ink_rect.width - cells_count * cell_metrix.pango_char_width;
+        // ink can start after origin, e.g. wide glyph centered in cells
+        let mut right = ink_rect.x + ink_rect.width - cells_count * cell_metrix.pango_char_width;
         if right < 0 {
             right = 0;
         }
@@ -95,3 +113,76 @@ impl InkOverflow {
         }
     }
 }
+
+/// Scale of glyphs around cells center
+///
+/// Color emoji fonts usually have own metrics, so their glyphs are wider
+/// and taller then cells of monospace font.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GlyphScale {
+    pub scale: f64,
+    /// Horizontal offset from cell start to center glyphs, in pixels
+    pub x_offset: f64,
+}
+
+impl GlyphScale {
+    fn fit(
+        ctx: &render::Context,
+        ink_rect: &pango::Rectangle,
+        logical_rect: &pango::Rectangle,
+        cells_count: i32,
+    ) -> Option<Self> {
+        let cell_metrics = ctx.cell_metrics();
+        let cells_width = f64::from(cells_count * cell_metrics.pango_char_width);
+        let cells_height = f64::from(cell_metrics.pango_ascent + cell_metrics.pango_descent);
+
+        let mut scale: f64 = 1.0;
+        if logical_rect.width > 0 {
+            scale = scale.min(cells_width / f64::from(logical_rect.width));
+        }
+        if ink_rect.height > 0 {
+            scale = scale.min(cells_height / f64::from(ink_rect.height));
+        }
+
+        if scale >= 1.0 || scale <= 0.0 {
+            return None;
+        }
+
+        let x_offset = (cells_width - f64::from(logical_rect.width) * scale) / 2.0;
+        Some(GlyphScale {
+            scale,
+            x_offset: x_offset / f64::from(pango::SCALE),
+        })
+    }
+
+    /// Vertical center of cell relative to baseline, glyphs are scaled around it
+    fn center_y(ctx: &render::Context) -> f64 {
+        let cell_metrics = ctx.cell_metrics();
+        f64::from(cell_metrics.pango_descent - cell_metrics.pango_ascent) / 2.0
+    }
+
+    /// Move `ctx` to origin of scaled glyphs, same as `move_to` for not scaled ones
+    pub fn apply(&self, cairo_ctx: &cairo::Context, ctx: &render::Context, x: f64, baseline: f64) {
+        let center_y = GlyphScale::center_y(ctx) / f64::from(pango::SCALE);
+
+        cairo_ctx.translate(x + self.x_offset, baseline + center_y);
+        cairo_ctx.scale(self.scale, self.scale);
+        cairo_ctx.move_to(0.0, -center_y);
+    }
+
+    /// Ink rectangle of scaled glyphs relative to not scaled origin
+    fn transform(&self, ctx: &render::Context, ink_rect: &pango::Rectangle) -> pango::Rectangle {
+        let center_y = GlyphScale::center_y(ctx);
+        let x_offset = self.x_offset * f64::from(pango::SCALE);
+
+        let x = x_offset + f64::from(ink_rect.x) * self.scale;
+        let y = center_y + (f64::from(ink_rect.y) - center_y) * self.scale;
+
+        pango::Rectangle {
+            x: x.floor() as i32,
+            y: y.floor() as i32,
+            width: (f64::from(ink_rect.width) * self.scale).ceil() as i32,
+            height: (f64::from(ink_rect.height) * self.scale).ceil() as i32,
+        }
+    }
+}
@@ -5,10 +5,11 @@ use pango;
 
 use super::cell::Cell;
 use super::item::Item;
-use crate::color;
 use crate::render;
-use crate::highlight::{HighlightMap, Highlight};
+use crate::sys::pango as sys_pango;
+use crate::highlight::Highlight;
 
+#[derive(Clone)]
 pub struct Line {
     pub line: Box<[Cell]>,
 
@@ -241,17 +242,21 @@ impl<'a> PangoItemPosition<'a> {
     }
 }
 
+/// Line text with attributes used for shaping.
+/// Colors don't change shaping, so ligatures are formed across color-only highlight changes.
 pub struct StyledLine {
     pub line_str: String,
     cell_to_byte: Box<[usize]>,
     pub attr_list: pango::AttrList,
+    /// Byte range where shaping runs are split, see `break_at`
+    break_range: Option<(usize, usize)>,
 }
 
 impl StyledLine {
     pub fn from(
         line: &Line,
-        hl: &HighlightMap,
         font_features: &render::FontFeatures,
+        font_variants: &render::FontVariants,
     ) -> Self {
         let average_capacity = line.line.len() * 4 * 2; // code bytes * grapheme cluster
 
@@ -277,7 +282,13 @@ impl StyledLine {
                 cell_to_byte.push(cell_idx);
             }
 
-            let next = style_attr.next(byte_offset, byte_offset + len, cell, hl);
+            let wide = line.line.get(cell_idx + 1).map_or(false, |c| c.double_width);
+            let next = style_attr.next(
+                byte_offset,
+                byte_offset + len,
+                cell,
+                font_variants.get(wide, cell.hl.bold, cell.hl.italic),
+            );
             if let Some(next) = next {
                 style_attr.insert_into(&attr_list);
                 style_attr = next;
@@ -293,15 +304,31 @@ impl StyledLine {
             line_str,
             cell_to_byte: cell_to_byte.into_boxed_slice(),
             attr_list,
+            break_range: None,
         }
     }
+
+    /// Split shaping runs around cell, so ligatures are not formed over it
+    pub fn break_at(&mut self, cell_idx: usize) {
+        let start = self.cell_to_byte.iter().position(|&c| c == cell_idx);
+        let end = self.cell_to_byte.iter().rposition(|&c| c == cell_idx);
+
+        self.break_range = match (start, end) {
+            (Some(start), Some(end)) => Some((start, end + 1)),
+            _ => None,
+        };
+    }
+
+    pub fn break_range(&self) -> Option<(usize, usize)> {
+        self.break_range
+    }
 }
 
 struct StyleAttr<'c> {
     italic: bool,
     bold: bool,
-    foreground: Option<&'c color::Color>,
-    background: Option<&'c color::Color>,
+    /// Font of variant, see `render::FontVariants`
+    font_desc: Option<&'c pango::FontDescription>,
     empty: bool,
     space: bool,
 
@@ -314,8 +341,7 @@ impl<'c> StyleAttr<'c> {
         StyleAttr {
             italic: false,
             bold: false,
-            foreground: None,
-            background: None,
+            font_desc: None,
             empty: true,
             space: false,
 
@@ -328,13 +354,12 @@ impl<'c> StyleAttr<'c> {
         start_idx: usize,
         end_idx: usize,
         cell: &'c Cell,
-        hl: &'c HighlightMap,
+        font_desc: Option<&'c pango::FontDescription>,
     ) -> Self {
         StyleAttr {
             italic: cell.hl.italic,
             bold: cell.hl.bold,
-            foreground: hl.cell_fg(cell),
-            background: hl.cell_bg(cell),
+            font_desc,
             empty: false,
             space: cell.ch.is_empty(),
 
@@ -348,7 +373,7 @@ impl<'c> StyleAttr<'c> {
         start_idx: usize,
         end_idx: usize,
         cell: &'c Cell,
-        hl: &'c HighlightMap,
+        font_desc: Option<&'c pango::FontDescription>,
     ) -> Option<StyleAttr<'c>> {
         // don't check attr for space
         if self.space && cell.ch.is_empty() {
@@ -357,7 +382,7 @@ impl<'c> StyleAttr<'c> {
         }
 
 
-        let style_attr = Self::from(start_idx, end_idx, cell, hl);
+        let style_attr = Self::from(start_idx, end_idx, cell, font_desc);
 
         if self != &style_attr {
             Some(style_attr)
@@ -386,19 +411,11 @@ impl<'c> StyleAttr<'c> {
             );
         }
 
-        if let Some(fg) = self.foreground {
-            let (r, g, b) = fg.to_u16();
-            self.insert_attr(
-                attr_list,
-                pango::Attribute::new_foreground(r, g, b).unwrap(),
-            );
-        }
-
-        if let Some(bg) = self.background {
-            let (r, g, b) = bg.to_u16();
+        // set fields of font description override weight and style above
+        if let Some(font_desc) = self.font_desc {
             self.insert_attr(
                 attr_list,
-                pango::Attribute::new_background(r, g, b).unwrap(),
+                sys_pango::attribute::new_font_desc(font_desc).unwrap(),
             );
         }
     }
@@ -415,15 +432,16 @@ impl<'c> PartialEq for StyleAttr<'c> {
     fn eq(&self, other: &Self) -> bool {
         self.italic == other.italic
             && self.bold == other.bold
-            && self.foreground == other.foreground
+            && self.font_desc == other.font_desc
             && self.empty == other.empty
-            && self.background == other.background
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use neovim_lib::Value;
+    use std::collections::HashMap;
 
     #[test]
     fn test_styled_line() {
@@ -434,8 +452,8 @@ mod tests {
 
         let styled_line = StyledLine::from(
             &line,
-            &HighlightMap::new(),
             &render::FontFeatures::new(),
+            &render::FontVariants::new(),
         );
         assert_eq!("abc", styled_line.line_str);
         assert_eq!(3, styled_line.cell_to_byte.len());
@@ -443,4 +461,67 @@ mod tests {
         assert_eq!(1, styled_line.cell_to_byte[1]);
         assert_eq!(2, styled_line.cell_to_byte[2]);
     }
+
+    fn styled_line(line: &Line) -> StyledLine {
+        StyledLine::from(
+            line,
+            &render::FontFeatures::new(),
+            &render::FontVariants::new(),
+        )
+    }
+
+    fn hl(attr: &str, value: Value) -> Rc<Highlight> {
+        let mut attrs = HashMap::new();
+        attrs.insert(attr.to_owned(), value);
+        Rc::new(Highlight::from_value_map(&attrs))
+    }
+
+    #[test]
+    fn test_styled_line_color_change() {
+        let mut line = Line::new(3);
+        line[0].ch = "=".to_owned();
+        line[1].ch = ">".to_owned();
+        line[1].hl = hl("foreground", Value::from(0xff0000));
+        line[2].ch = "a".to_owned();
+        line[2].hl = hl("background", Value::from(0x00ff00));
+
+        // only colors differ, so whole line is one shaping run
+        let styled_line = styled_line(&line);
+        let mut attr_iter = styled_line.attr_list.get_iterator().unwrap();
+        assert_eq!(0, attr_iter.range().0);
+        assert!(!attr_iter.next());
+    }
+
+    #[test]
+    fn test_styled_line_font_change() {
+        let mut line = Line::new(3);
+        line[0].ch = "=".to_owned();
+        line[1].ch = ">".to_owned();
+        line[1].hl = hl("bold", Value::from(true));
+        line[2].ch = "a".to_owned();
+
+        let styled_line = styled_line(&line);
+        let mut attr_iter = styled_line.attr_list.get_iterator().unwrap();
+        assert_eq!((0, 1), attr_iter.range());
+        assert!(attr_iter.next());
+        assert_eq!((1, 2), attr_iter.range());
+    }
+
+    #[test]
+    fn test_styled_line_break_at() {
+        let mut line = Line::new(4);
+        line[0].ch = "a".to_owned();
+        line[1].ch = "\u{3042}".to_owned();
+        line[2].double_width = true;
+        line[3].ch = "b".to_owned();
+
+        let mut styled_line = styled_line(&line);
+        assert_eq!(None, styled_line.break_range());
+
+        styled_line.break_at(1);
+        assert_eq!(Some((1, 4)), styled_line.break_range());
+
+        styled_line.break_at(3);
+        assert_eq!(Some((4, 5)), styled_line.break_range());
+    }
 }
@@ -2,17 +2,20 @@ use crate::highlight::Highlight;
 use std::rc::Rc;
 
 mod cell;
+mod emoji;
 mod item;
 mod line;
 mod model_layout;
 mod model_rect;
 
 pub use self::cell::Cell;
+pub use self::emoji::{cell_width, is_emoji};
 pub use self::item::Item;
 pub use self::line::{Line, StyledLine};
 pub use self::model_layout::ModelLayout;
 pub use self::model_rect::{ModelRect, ModelRectVec};
 
+#[derive(Clone)]
 pub struct UiModel {
     pub columns: usize,
     pub rows: usize,
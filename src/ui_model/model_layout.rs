@@ -1,10 +1,8 @@
 use std::cmp::max;
 use std::rc::Rc;
 
-use unicode_width::UnicodeWidthStr;
-
 use crate::highlight::Highlight;
-use crate::ui_model::UiModel;
+use crate::ui_model::{cell_width, UiModel};
 
 pub struct ModelLayout {
     pub model: UiModel,
@@ -118,7 +116,7 @@ impl ModelLayout {
         for content in lines {
             for &(ref hl, ref ch_list) in content {
                 for ch in ch_list {
-                    let ch_width = max(1, ch.width());
+                    let ch_width = cell_width(ch);
 
                     if col_idx + ch_width > self.model.columns {
                         col_idx = 0;
@@ -127,7 +125,7 @@ impl ModelLayout {
 
                     self.model.put_one(row_idx, col_idx, ch, false, hl.clone());
                     if ch_width > 1 {
-                        self.model.put_one(row_idx, col_idx, "", true, hl.clone());
+                        self.model.put_one(row_idx, col_idx + 1, "", true, hl.clone());
                     }
 
                     if max_col_idx < col_idx {
@@ -160,7 +158,11 @@ impl ModelLayout {
         let mut row_count = 0;
 
         for line in lines {
-            let len: usize = line.iter().map(|c| c.1.len()).sum();
+            let len: usize = line
+                .iter()
+                .flat_map(|c| c.1.iter())
+                .map(|ch| cell_width(ch))
+                .sum();
             row_count += len / (max_columns + 1) + 1;
         }
 
@@ -259,4 +261,22 @@ mod tests {
         assert_eq!(1, rows);
         assert_eq!(6, cols);
     }
+
+    #[test]
+    fn test_emoji_width() {
+        let emoji = "\u{1F469}\u{200D}\u{1F4BB}".to_owned();
+        let lines = vec![vec![(
+            Rc::new(Highlight::new()),
+            vec![emoji.clone(), "a".to_owned()],
+        )]];
+        let mut model = ModelLayout::new(7);
+        model.layout(lines);
+
+        let (cols, _) = model.size();
+        assert_eq!(3, cols);
+        let line = &model.model.model()[0].line;
+        assert_eq!(emoji, line[0].ch);
+        assert!(line[1].double_width);
+        assert_eq!("a", line[2].ch);
+    }
 }
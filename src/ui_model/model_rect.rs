@@ -188,7 +188,7 @@ impl ModelRect {
                 if let Some(&Item {
                     ink_overflow: Some(ref overflow),
                     ..
-                }) = line.get_item(self.left)
+                }) = line.get_item(self.right)
                 {
                     if max_x_offset < overflow.right {
                         max_x_offset = overflow.right;
@@ -235,8 +235,8 @@ impl ModelRect {
                 ..
             }) = line.get_item(col)
             {
-                if max_y_offset < overflow.top {
-                    max_y_offset = overflow.top;
+                if max_y_offset < overflow.bot {
+                    max_y_offset = overflow.bot;
                 }
             }
         }
@@ -317,6 +317,9 @@ impl AsRef<ModelRect> for ModelRect {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::render;
+    use crate::ui_model::item::InkOverflow;
+    use crate::ui_model::StyledLine;
 
     #[test]
     fn test_repaint_rect() {
@@ -353,4 +356,43 @@ mod tests {
         assert_eq!(2, rect.right);
     }
 
+    #[test]
+    fn test_to_area_extend_ink() {
+        let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 1, 1).unwrap();
+        let pango_context =
+            pangocairo::functions::create_context(&cairo::Context::new(&surface)).unwrap();
+        let font_ctx = render::Context::new(pango_context);
+
+        let mut model = UiModel::new(2, 6);
+        for line in model.model_mut().iter_mut() {
+            for (cell, ch) in line.line.iter_mut().zip("ab cd ".chars()) {
+                cell.ch = ch.to_string();
+            }
+            let styled_line = StyledLine::from(
+                line,
+                &render::FontFeatures::new(),
+                &render::FontVariants::new(),
+            );
+            line.merge(&styled_line, &font_ctx.itemize(&styled_line));
+        }
+        // only last item of bottom row overflows to the right and bottom
+        model.model_mut()[1].item_line[3]
+            .as_mut()
+            .unwrap()
+            .ink_overflow = Some(InkOverflow {
+            left: 0.0,
+            right: 3.0,
+            top: 0.0,
+            bot: 2.0,
+        });
+
+        let rect = ModelRect::new(0, 1, 0, 4);
+        let (x, y, width, height) =
+            rect.to_area_extend_ink(Some(&model), &CellMetrics::new_hw(10.0, 5.0));
+
+        assert_eq!(0, x);
+        assert_eq!(0, y);
+        assert_eq!(25 + 3, width);
+        assert_eq!(20 + 2, height);
+    }
 }
@@ -30,3 +30,79 @@ impl ValueMapExt for Vec<(Value, Value)> {
         }
     }
 }
+
+/// Convert msgpack value to json, used to record redraw events
+///
+/// Ext values (e.g. window handles) have no json representation, they are stored as null.
+pub fn to_json(value: &Value) -> serde_json::Value {
+    match *value {
+        Value::Nil | Value::Ext(..) => serde_json::Value::Null,
+        Value::Boolean(val) => val.into(),
+        Value::Integer(val) => match (val.as_u64(), val.as_i64()) {
+            (Some(val), _) => val.into(),
+            (None, Some(val)) => val.into(),
+            (None, None) => serde_json::Value::Null,
+        },
+        Value::F32(val) => f64::from(val).into(),
+        Value::F64(val) => val.into(),
+        Value::String(ref val) => String::from_utf8_lossy(val.as_bytes()).into(),
+        Value::Binary(ref val) => String::from_utf8_lossy(val).into(),
+        Value::Array(ref val) => val.iter().map(to_json).collect(),
+        Value::Map(ref val) => serde_json::Value::Object(
+            val.iter()
+                .map(|(key, val)| {
+                    let key = match key.as_str() {
+                        Some(key) => key.to_owned(),
+                        None => key.to_string(),
+                    };
+                    (key, to_json(val))
+                })
+                .collect(),
+        ),
+    }
+}
+
+/// Convert value produced by `to_json` back
+pub fn from_json(json: &serde_json::Value) -> Value {
+    match *json {
+        serde_json::Value::Null => Value::Nil,
+        serde_json::Value::Bool(val) => val.into(),
+        serde_json::Value::Number(ref val) => {
+            if let Some(val) = val.as_u64() {
+                val.into()
+            } else if let Some(val) = val.as_i64() {
+                val.into()
+            } else {
+                val.as_f64().unwrap_or_default().into()
+            }
+        }
+        serde_json::Value::String(ref val) => val.as_str().into(),
+        serde_json::Value::Array(ref val) => Value::Array(val.iter().map(from_json).collect()),
+        serde_json::Value::Object(ref val) => Value::Map(
+            val.iter()
+                .map(|(key, val)| (key.as_str().into(), from_json(val)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        let value = Value::Array(vec![
+            "grid_line".into(),
+            Value::Array(vec![1.into(), (-2).into(), 0.5.into(), Value::Nil]),
+            Value::Map(vec![("bold".into(), true.into())]),
+        ]);
+
+        let json = to_json(&value);
+        assert_eq!(
+            r#"["grid_line",[1,-2,0.5,null],{"bold":true}]"#,
+            json.to_string()
+        );
+        assert_eq!(value, from_json(&json));
+    }
+}